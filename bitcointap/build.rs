@@ -13,7 +13,12 @@ fn main() {
     println!("using vmlinux path: {}", vmlinux_path.display());
     match SkeletonBuilder::new()
         .source(SOURCE)
-        .clang_args([OsStr::new("-I"), vmlinux_path.as_os_str()])
+        .clang_args([
+            OsStr::new("-I"),
+            vmlinux_path.as_os_str(),
+            // atomic fetch-and-add for the event sequence numbers
+            OsStr::new("-mcpu=v3"),
+        ])
         //.clang_args(format!("-I {}", vmlinux_path.display()))
         .build_and_generate(DEST)
     {
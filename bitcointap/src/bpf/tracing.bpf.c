@@ -24,6 +24,95 @@
 #define PAGE_SIZE 4096
 #define NET_MSG_PAGES 128
 
+// EVENT HEADER
+
+// Every event struct starts with this header. Mirrors EventHeader in
+// shared/src/ctypes.rs.
+struct EventHeader {
+    u64     ktime_ns; // bpf_ktime_get_ns() when the tracepoint fired
+    u64     seq;      // the number of events recorded before this one
+    u32     pid;      // the bitcoind process (tgid)
+    u32     target;   // the USDT cookie: the index of the target the probe was attached for
+};
+
+// The sequence number of the next event. Shared by all CPUs, so it's
+// incremented atomically. This needs BPF v3 atomics (Linux 5.12).
+u64 event_seq = 0;
+
+static __always_inline void set_header(struct pt_regs *ctx, struct EventHeader *header) {
+  header->ktime_ns = bpf_ktime_get_ns();
+  header->seq = __sync_fetch_and_add(&event_seq, 1);
+  header->pid = bpf_get_current_pid_tgid() >> 32;
+  header->target = bpf_usdt_cookie(ctx);
+}
+
+// RING BUFFER STATS
+
+// The ids of the ring buffers in the ringbuf_stats map. Keep in sync with
+// RINGBUFFERS in tracepoint.rs.
+enum RingbufId {
+    RB_NET_MSG_SMALL,
+    RB_NET_MSG_MEDIUM,
+    RB_NET_MSG_LARGE,
+    RB_NET_MSG_HUGE,
+    RB_NET_MSG_META,
+    RB_NET_CONN_INBOUND,
+    RB_NET_CONN_OUTBOUND,
+    RB_NET_CONN_CLOSED,
+    RB_NET_CONN_INBOUND_EVICTED,
+    RB_NET_CONN_MISBEHAVING,
+    RB_ADDRMAN_INSERT_NEW,
+    RB_ADDRMAN_INSERT_TRIED,
+    RB_MEMPOOL_ADDED,
+    RB_MEMPOOL_REMOVED,
+    RB_MEMPOOL_REPLACED,
+    RB_MEMPOOL_REJECTED,
+    RB_VALIDATION_BLOCK_CONNECTED,
+    RB_UTXOCACHE_FLUSH,
+    RB_UTXOCACHE_ADD,
+    RB_UTXOCACHE_SPENT,
+    RB_UTXOCACHE_UNCACHE,
+    RB_COIN_SELECTION_SELECTED_COINS,
+    RB_COIN_SELECTION_NORMAL_CREATE_TX,
+    RB_COIN_SELECTION_ATTEMPTING_APS_CREATE_TX,
+    RB_COIN_SELECTION_APS_CREATE_TX,
+    RB_COUNT,
+};
+
+// The events lost in the kernel, per ring buffer.
+struct RingbufStats {
+    u64     reserve_failures; // the ring buffer was full
+    u64     oversized;        // the P2P message was larger than MAX_HUGE_MSG_LENGTH
+    u64     read_failures;    // reading the event data from bitcoind failed
+};
+
+struct {
+    __uint(type, BPF_MAP_TYPE_PERCPU_ARRAY);
+    __uint(max_entries, RB_COUNT);
+    __type(key, u32);
+    __type(value, struct RingbufStats);
+} ringbuf_stats SEC(".maps");
+
+// Increments a counter of the ring buffer with the given id. The map is
+// per-CPU, so no atomic operation is needed.
+#define COUNT(rb, counter) { \
+    u32 key = rb; \
+    struct RingbufStats *stats = bpf_map_lookup_elem(&ringbuf_stats, &key); \
+    if (stats) stats->counter++; \
+}
+
+// bpf_ringbuf_output() counting a full ring buffer as reserve failure.
+#define RINGBUF_OUTPUT(ringbuf, rb, data) ({ \
+    long ret = bpf_ringbuf_output(&ringbuf, data, sizeof(*data), 0); \
+    if (ret) COUNT(rb, reserve_failures); \
+    ret; \
+})
+
+// bpf_probe_read_user() counting failed reads.
+#define PROBE_READ_USER(rb, dst, size, src) { \
+    if (bpf_probe_read_user(dst, size, src) < 0) COUNT(rb, read_failures); \
+}
+
 // NET MESSAGES
 
 #define METADATA_SIZE 8 + MAX_PEER_ADDR_LENGTH + MAX_PEER_CONN_TYPE_LENGTH + MAX_MSG_TYPE_LENGTH + 1 + 8
@@ -38,32 +127,119 @@ struct Metadata {
 };
 
 struct SmallP2PMessage {
+    struct EventHeader header;
     struct Metadata   meta;
     u8                payload[MAX_SMALL_MSG_LENGTH];
 };
 
 struct MediumP2PMessage {
+    struct EventHeader header;
     struct Metadata   meta;
     u8                payload[MAX_MEDIUM_MSG_LENGTH];
 };
 
 struct LargeP2PMessage
 {
+    struct EventHeader header;
     struct Metadata   meta;
     u8                payload[MAX_LARGE_MSG_LENGTH];
 };
 
 struct HugeP2PMessage
 {
+    struct EventHeader header;
     struct Metadata   meta;
     u8                payload[MAX_HUGE_MSG_LENGTH];
 };
 
+// Recorded into net_msg_meta when only the metadata is wanted
+struct MetaP2PMessage {
+    struct EventHeader header;
+    struct Metadata   meta;
+};
+
 RINGBUFFER(net_msg_small, (MAX_SMALL_MSG_LENGTH + METADATA_SIZE) * 1024) // ~ 1 MB
 RINGBUFFER(net_msg_medium, (MAX_MEDIUM_MSG_LENGTH + METADATA_SIZE) * 1024) // ~ 4.2 MB
 RINGBUFFER(net_msg_large, (MAX_LARGE_MSG_LENGTH + METADATA_SIZE) * 1024) // ~ 67 MB
 RINGBUFFER(net_msg_huge, (MAX_HUGE_MSG_LENGTH + METADATA_SIZE) * 128) // ~ 536 MB
+RINGBUFFER(net_msg_meta, (24 + METADATA_SIZE) * 8192) // ~ 1.5 MB
+
+// Set by the tap before loading. Only the metadata of the P2P messages is
+// recorded into net_msg_meta, the payload is not read.
+const volatile bool net_msg_metadata_only = false;
+
+
+// NET MESSAGE FILTER
+
+#define FILTER_ANY 0
+#define FILTER_ALLOW 1
+#define FILTER_DENY 2
 
+#define MAX_FILTER_MSG_TYPES 64
+#define MAX_FILTER_PEERS 4096
+
+// Which P2P messages are passed on to the ring buffers. Written by the tap,
+// all zero passes every message.
+struct NetMsgFilter {
+    u64     max_payload_size;
+    u8      msg_type_mode;    // FILTER_* for net_msg_filter_msg_types
+    u8      peer_mode;        // FILTER_* for net_msg_filter_peers
+    bool    skip_inbound;
+    bool    skip_outbound;
+    bool    limit_payload_size;
+};
+
+struct {
+    __uint(type, BPF_MAP_TYPE_ARRAY);
+    __uint(max_entries, 1);
+    __type(key, u32);
+    __type(value, struct NetMsgFilter);
+} net_msg_filter SEC(".maps");
+
+struct {
+    __uint(type, BPF_MAP_TYPE_HASH);
+    __uint(max_entries, MAX_FILTER_MSG_TYPES);
+    __type(key, char[MAX_MSG_TYPE_LENGTH]);
+    __type(value, u8);
+} net_msg_filter_msg_types SEC(".maps");
+
+struct {
+    __uint(type, BPF_MAP_TYPE_HASH);
+    __uint(max_entries, MAX_FILTER_PEERS);
+    __type(key, u64);
+    __type(value, u8);
+} net_msg_filter_peers SEC(".maps");
+
+// Is the P2P message passed by the filter? Checked before anything is
+// copied into a ring buffer.
+static __always_inline bool net_msg_wanted(u64 id, bool inbound, void *msg_type, u64 msg_size) {
+  u32 zero = 0;
+  struct NetMsgFilter *filter = bpf_map_lookup_elem(&net_msg_filter, &zero);
+  if (!filter) {
+    return true;
+  }
+  if (inbound ? filter->skip_inbound : filter->skip_outbound) {
+    return false;
+  }
+  if (filter->limit_payload_size && msg_size > filter->max_payload_size) {
+    return false;
+  }
+  if (filter->peer_mode != FILTER_ANY) {
+    bool listed = bpf_map_lookup_elem(&net_msg_filter_peers, &id) != NULL;
+    if (listed != (filter->peer_mode == FILTER_ALLOW)) {
+      return false;
+    }
+  }
+  if (filter->msg_type_mode != FILTER_ANY) {
+    char type[MAX_MSG_TYPE_LENGTH] = {};
+    bpf_probe_read_user_str(&type, sizeof(type), msg_type);
+    bool listed = bpf_map_lookup_elem(&net_msg_filter_msg_types, &type) != NULL;
+    if (listed != (filter->msg_type_mode == FILTER_ALLOW)) {
+      return false;
+    }
+  }
+  return true;
+}
 
 // Helper function to set some of the tracepoint arguments to Metadata.
 void set_meta_data1(struct Metadata *meta, u64 id, bool inbound, u64 msg_size) {
@@ -79,53 +255,78 @@ void set_meta_data2(struct Metadata *meta, void *addr, void* conn_type, void* ms
   bpf_probe_read_user_str(&meta->msg_type, sizeof(meta->msg_type), msg_type);
 }
 
+// Records only the metadata of a P2P message into net_msg_meta.
+static __always_inline int record_net_msg_meta(struct pt_regs *ctx, u64 id, bool inbound, void *addr, void *conn_type, void *msg_type, u64 msg_size) {
+  struct MetaP2PMessage *msg = bpf_ringbuf_reserve(&net_msg_meta, sizeof(struct MetaP2PMessage), 0);
+  if (!msg) {
+    COUNT(RB_NET_MSG_META, reserve_failures);
+    return -1;
+  }
+  set_header(ctx, &msg->header);
+  set_meta_data1(&msg->meta, id, inbound, msg_size);
+  set_meta_data2(&msg->meta, addr, conn_type, msg_type);
+  bpf_ringbuf_submit(msg, 0);
+  return 0;
+}
+
 SEC("usdt")
 int BPF_USDT(handle_net_msg_inbound, u64 id, void *addr, void *conn_type, void *msg_type, u64 msg_size, void *msg_payload)
 {
   bool IS_INBOUND = true;
+  if (!net_msg_wanted(id, IS_INBOUND, msg_type, msg_size)) {
+    return 0;
+  }
+  if (net_msg_metadata_only) {
+    return record_net_msg_meta(ctx, id, IS_INBOUND, addr, conn_type, msg_type, msg_size);
+  }
   if (msg_size <= MAX_SMALL_MSG_LENGTH) {
     struct SmallP2PMessage *msg = bpf_ringbuf_reserve(&net_msg_small, sizeof(struct SmallP2PMessage), 0);
     if (msg) {
+      set_header(ctx, &msg->header);
       set_meta_data1(&msg->meta, id, IS_INBOUND, msg_size);
       set_meta_data2(&msg->meta, addr, conn_type, msg_type);
-      bpf_probe_read_user(&msg->payload, msg_size, msg_payload);
+      PROBE_READ_USER(RB_NET_MSG_SMALL, &msg->payload, msg_size, msg_payload);
       bpf_ringbuf_submit(msg, 0);
       return 0;
     }
-    bpf_printk("small inbound msg: not able to reserve. msg size is %d", msg_size);
+    COUNT(RB_NET_MSG_SMALL, reserve_failures);
   } else if (msg_size <= MAX_MEDIUM_MSG_LENGTH) {
     struct MediumP2PMessage *msg = bpf_ringbuf_reserve(&net_msg_medium, sizeof(struct MediumP2PMessage), 0);
     if (msg) {
+      set_header(ctx, &msg->header);
       set_meta_data1(&msg->meta, id, IS_INBOUND, msg_size);
       set_meta_data2(&msg->meta, addr, conn_type, msg_type);
       bpf_probe_read_user_str(&msg->meta.msg_type, sizeof(msg->meta.msg_type), msg_type);
-      bpf_probe_read_user(&msg->payload, msg_size, msg_payload);
+      PROBE_READ_USER(RB_NET_MSG_MEDIUM, &msg->payload, msg_size, msg_payload);
       bpf_ringbuf_submit(msg, 0);
       return 0;
     }
-    bpf_printk("medium inbound msg: not able to reserve. msg size is %d", msg_size);
+    COUNT(RB_NET_MSG_MEDIUM, reserve_failures);
   } else if (msg_size <= MAX_LARGE_MSG_LENGTH) {
     struct LargeP2PMessage *msg = bpf_ringbuf_reserve(&net_msg_large, sizeof(struct LargeP2PMessage), 0);
     if (msg) {
+      set_header(ctx, &msg->header);
       set_meta_data1(&msg->meta, id, IS_INBOUND, msg_size);
       set_meta_data2(&msg->meta, addr, conn_type, msg_type);
-      bpf_probe_read_user(&msg->payload, msg_size, msg_payload);
+      PROBE_READ_USER(RB_NET_MSG_LARGE, &msg->payload, msg_size, msg_payload);
       bpf_ringbuf_submit(msg, 0);
       return 0;
     }
-    bpf_printk("large inbound msg: not able to reserve. msg size is %d", msg_size);
+    COUNT(RB_NET_MSG_LARGE, reserve_failures);
   } else if (msg_size <= MAX_HUGE_MSG_LENGTH) {
     struct HugeP2PMessage *msg = bpf_ringbuf_reserve(&net_msg_huge, sizeof(struct HugeP2PMessage), 0);
     if (msg) {
+      set_header(ctx, &msg->header);
       set_meta_data1(&msg->meta, id, IS_INBOUND, msg_size);
       set_meta_data2(&msg->meta, addr, conn_type, msg_type);
-      bpf_probe_read_user(&msg->payload, msg_size, msg_payload);
+      PROBE_READ_USER(RB_NET_MSG_HUGE, &msg->payload, msg_size, msg_payload);
       bpf_ringbuf_submit(msg, 0);
       return 0;
     }
-    bpf_printk("huge inbound msg: not able to reserve. msg size is %d", msg_size);
+    COUNT(RB_NET_MSG_HUGE, reserve_failures);
+  } else {
+    COUNT(RB_NET_MSG_HUGE, oversized);
   }
-  bpf_printk("inbound msg: return -1.( msg size is %d", msg_size);
   return -1;
 }
 
@@ -133,49 +334,60 @@ SEC("usdt")
 int BPF_USDT(handle_net_msg_outbound, u64 id, void *addr, void *conn_type, void *msg_type, u64 msg_size, void *msg_payload)
 {
   bool IS_INBOUND = false;
+  if (!net_msg_wanted(id, IS_INBOUND, msg_type, msg_size)) {
+    return 0;
+  }
+  if (net_msg_metadata_only) {
+    return record_net_msg_meta(ctx, id, IS_INBOUND, addr, conn_type, msg_type, msg_size);
+  }
   if (msg_size <= MAX_SMALL_MSG_LENGTH) {
     struct SmallP2PMessage *msg = bpf_ringbuf_reserve(&net_msg_small, sizeof(struct SmallP2PMessage), 0);
     if (msg) {
+      set_header(ctx, &msg->header);
       set_meta_data1(&msg->meta, id, IS_INBOUND, msg_size);
       set_meta_data2(&msg->meta, addr, conn_type, msg_type);
-      bpf_probe_read_user(&msg->payload, msg_size, msg_payload);
+      PROBE_READ_USER(RB_NET_MSG_SMALL, &msg->payload, msg_size, msg_payload);
       bpf_ringbuf_submit(msg, 0);
       return 0;
     }
-    bpf_printk("small outbound msg: not able to reserve. msg size is %d", msg_size);
+    COUNT(RB_NET_MSG_SMALL, reserve_failures);
   } else if (msg_size <= MAX_MEDIUM_MSG_LENGTH) {
     struct MediumP2PMessage *msg = bpf_ringbuf_reserve(&net_msg_medium, sizeof(struct MediumP2PMessage), 0);
     if (msg) {
+      set_header(ctx, &msg->header);
       set_meta_data1(&msg->meta, id, IS_INBOUND, msg_size);
       set_meta_data2(&msg->meta, addr, conn_type, msg_type);
       bpf_probe_read_user_str(&msg->meta.msg_type, sizeof(msg->meta.msg_type), msg_type);
-      bpf_probe_read_user(&msg->payload, msg_size, msg_payload);
+      PROBE_READ_USER(RB_NET_MSG_MEDIUM, &msg->payload, msg_size, msg_payload);
       bpf_ringbuf_submit(msg, 0);
       return 0;
     }
-    bpf_printk("medium outbound msg: not able to reserve. msg size is %d", msg_size);
+    COUNT(RB_NET_MSG_MEDIUM, reserve_failures);
   } else if (msg_size <= MAX_LARGE_MSG_LENGTH) {
     struct LargeP2PMessage *msg = bpf_ringbuf_reserve(&net_msg_large, sizeof(struct LargeP2PMessage), 0);
     if (msg) {
+      set_header(ctx, &msg->header);
       set_meta_data1(&msg->meta, id, IS_INBOUND, msg_size);
       set_meta_data2(&msg->meta, addr, conn_type, msg_type);
-      bpf_probe_read_user(&msg->payload, msg_size, msg_payload);
+      PROBE_READ_USER(RB_NET_MSG_LARGE, &msg->payload, msg_size, msg_payload);
       bpf_ringbuf_submit(msg, 0);
       return 0;
     }
-    bpf_printk("large outbound msg: not able to reserve. msg size is %d", msg_size);
+    COUNT(RB_NET_MSG_LARGE, reserve_failures);
   } else if (msg_size <= MAX_HUGE_MSG_LENGTH) {
     struct HugeP2PMessage *msg = bpf_ringbuf_reserve(&net_msg_huge, sizeof(struct HugeP2PMessage), 0);
     if (msg) {
+      set_header(ctx, &msg->header);
       set_meta_data1(&msg->meta, id, IS_INBOUND, msg_size);
       set_meta_data2(&msg->meta, addr, conn_type, msg_type);
-      bpf_probe_read_user(&msg->payload, msg_size, msg_payload);
+      PROBE_READ_USER(RB_NET_MSG_HUGE, &msg->payload, msg_size, msg_payload);
       bpf_ringbuf_submit(msg, 0);
       return 0;
     }
-    bpf_printk("huge outbound msg: not able to reserve. msg size is %d", msg_size);
+    COUNT(RB_NET_MSG_HUGE, reserve_failures);
+  } else {
+    COUNT(RB_NET_MSG_HUGE, oversized);
   }
-  bpf_printk("outbound msg: return -1. msg size is %d", msg_size);
   return -1;
 }
 
@@ -201,24 +413,28 @@ struct Connection
 
 struct ClosedConnection
 {
+    struct EventHeader header;
     struct Connection conn;
     u64    time_established;
 };
 
 struct InboundConnection
 {
+    struct EventHeader header;
     struct  Connection conn;
     u64     existing_connections;
 };
 
 struct OutboundConnection
 {
+    struct EventHeader header;
     struct  Connection conn;
     u64     existing_connections;
 };
 
 struct MisbehavingConnection
 {
+    struct EventHeader header;
     u64     id;
     s32     score_before;
     s32     howmuch;
@@ -241,48 +457,53 @@ void set_conn_data2(struct Connection *conn, void *addr, void *type) {
 SEC("usdt")
 int BPF_USDT(handle_net_conn_inbound, u64 id, void *addr, void *type, u64 network, u64 existing_connections) {
     struct InboundConnection inbound = {};
+    set_header(ctx, &inbound.header);
     set_conn_data1(&inbound.conn, id, network);
     set_conn_data2(&inbound.conn, addr, type);
     inbound.existing_connections = existing_connections;
-    return bpf_ringbuf_output(&net_conn_inbound, &inbound, sizeof(inbound), 0);
+    return RINGBUF_OUTPUT(net_conn_inbound, RB_NET_CONN_INBOUND, &inbound);
 };
 
 SEC("usdt")
 int BPF_USDT(handle_net_conn_outbound, u64 id, void *addr, void *type, u64 network, u64 existing_connections) {
     struct OutboundConnection outbound = {};
+    set_header(ctx, &outbound.header);
     set_conn_data1(&outbound.conn, id, network);
     set_conn_data2(&outbound.conn, addr, type);
     outbound.existing_connections = existing_connections;
-    return bpf_ringbuf_output(&net_conn_outbound, &outbound, sizeof(outbound), 0);
+    return RINGBUF_OUTPUT(net_conn_outbound, RB_NET_CONN_OUTBOUND, &outbound);
 };
 
 SEC("usdt")
 int BPF_USDT(handle_net_conn_closed, u64 id, void *addr, void *type, u64 network, u64 time_established) {
     struct ClosedConnection closed = {};
+    set_header(ctx, &closed.header);
     set_conn_data1(&closed.conn, id, network);
     set_conn_data2(&closed.conn, addr, type);
     closed.time_established = time_established;
-    return bpf_ringbuf_output(&net_conn_closed, &closed, sizeof(closed), 0);
+    return RINGBUF_OUTPUT(net_conn_closed, RB_NET_CONN_CLOSED, &closed);
 };
 
 SEC("usdt")
 int BPF_USDT(handle_net_conn_inbound_evicted, u64 id, void *addr, void *type, u64 network, u64 time_established) {
     struct ClosedConnection evicted = {};
+    set_header(ctx, &evicted.header);
     set_conn_data1(&evicted.conn, id, network);
     set_conn_data2(&evicted.conn, addr, type);
     evicted.time_established = time_established;
-    return bpf_ringbuf_output(&net_conn_inbound_evicted, &evicted, sizeof(evicted), 0);
+    return RINGBUF_OUTPUT(net_conn_inbound_evicted, RB_NET_CONN_INBOUND_EVICTED, &evicted);
 };
 
 SEC("usdt")
 int BPF_USDT(handle_net_conn_misbehaving, u64 id, s32 score_before, s32 howmuch, void *message, bool threshold_exceeded) {
     struct MisbehavingConnection misbehaving = {};
+    set_header(ctx, &misbehaving.header);
     misbehaving.id = id;
     misbehaving.score_before = score_before;
     misbehaving.howmuch = howmuch;
     bpf_probe_read_user_str(&misbehaving.message, sizeof(misbehaving.message), message);
     misbehaving.threshold_exceeded = threshold_exceeded;
-    return bpf_ringbuf_output(&net_conn_misbehaving, &misbehaving, sizeof(misbehaving), 0);
+    return RINGBUF_OUTPUT(net_conn_misbehaving, RB_NET_CONN_MISBEHAVING, &misbehaving);
 };
 
 // ADDRMAN
@@ -293,6 +514,7 @@ RINGBUFFER(addrman_insert_new, ADDRMAN_PAGES)
 RINGBUFFER(addrman_insert_tried, ADDRMAN_PAGES)
 
 struct AddrmanNew {
+    struct EventHeader header;
     bool    inserted;
     s32     bucket;
     s32     bucket_pos;
@@ -303,6 +525,7 @@ struct AddrmanNew {
 };
 
 struct AddrmanTried {
+    struct EventHeader header;
     s32     bucket;
     s32     bucket_pos;
     char    addr[MAX_PEER_ADDR_LENGTH];
@@ -314,6 +537,7 @@ struct AddrmanTried {
 SEC("usdt")
 int BPF_USDT(handle_addrman_new, bool inserted, s32 bucket, s32 bucket_pos, void *addr, u32 addr_AS, void *source, u32 source_AS) {
     struct AddrmanNew new = {};
+    set_header(ctx, &new.header);
     new.inserted = inserted;
     new.bucket = bucket;
     new.bucket_pos = bucket_pos;
@@ -321,19 +545,20 @@ int BPF_USDT(handle_addrman_new, bool inserted, s32 bucket, s32 bucket_pos, void
     new.addr_AS = addr_AS;
     bpf_probe_read_user_str(&new.source, sizeof(new.source), source);
     new.source_AS = source_AS;
-    return bpf_ringbuf_output(&addrman_insert_new, &new, sizeof(new), 0);
+    return RINGBUF_OUTPUT(addrman_insert_new, RB_ADDRMAN_INSERT_NEW, &new);
 };
 
 SEC("usdt")
 int BPF_USDT(handle_addrman_tried, s32 bucket, s32 bucket_pos, void *addr, u32 addr_AS, void *source, u32 source_AS) {
     struct AddrmanTried tried = {};
+    set_header(ctx, &tried.header);
     tried.bucket = bucket;
     tried.bucket_pos = bucket_pos;
     bpf_probe_read_user_str(&tried.addr, sizeof(tried.addr), addr);
     tried.addr_AS = addr_AS;
     bpf_probe_read_user_str(&tried.source, sizeof(tried.source), source);
     tried.source_AS = source_AS;
-    return bpf_ringbuf_output(&addrman_insert_tried, &tried, sizeof(tried), 0);
+    return RINGBUF_OUTPUT(addrman_insert_tried, RB_ADDRMAN_INSERT_TRIED, &tried);
 };
 
 // MEMPOOL
@@ -350,12 +575,14 @@ RINGBUFFER(mempool_rejected, MEMPOOL_PAGES)
 #define REJECTION_REASON_LENGTH 113
 
 struct MempoolAdded {
+    struct EventHeader header;
     u8      txid[TXID_LENGHT];
     s32     vsize;
     s64     fee;
 };
 
 struct MempoolRemoved {
+    struct EventHeader header;
     u8      txid[TXID_LENGHT];
     char    reason[REMOVAL_REASON_LENGTH];
     s32     vsize;
@@ -364,6 +591,7 @@ struct MempoolRemoved {
 };
 
 struct MempoolReplaced {
+    struct EventHeader header;
     u8      replaced_txid[TXID_LENGHT];
     s32     replaced_vsize;
     s64     replaced_fee;
@@ -374,6 +602,7 @@ struct MempoolReplaced {
 };
 
 struct MempoolRejected {
+    struct EventHeader header;
     u8      txid[TXID_LENGHT];
     char    reason[REJECTION_REASON_LENGTH];
 };
@@ -381,21 +610,23 @@ struct MempoolRejected {
 SEC("usdt")
 int BPF_USDT(handle_mempool_added, void *txid, s32 vsize, s64 fee) {
     struct MempoolAdded added = {};
-    bpf_probe_read_user(&added.txid, sizeof(added.txid), txid);
+    set_header(ctx, &added.header);
+    PROBE_READ_USER(RB_MEMPOOL_ADDED, &added.txid, sizeof(added.txid), txid);
     added.vsize = vsize;
     added.fee = fee;
-    return bpf_ringbuf_output(&mempool_added, &added, sizeof(added), 0);
+    return RINGBUF_OUTPUT(mempool_added, RB_MEMPOOL_ADDED, &added);
 };
 
 SEC("usdt")
 int BPF_USDT(handle_mempool_removed, void *txid, void *reason, s32 vsize, s64 fee, u64 entry_time) {
     struct MempoolRemoved removed = {};
-    bpf_probe_read_user(&removed.txid, sizeof(removed.txid), txid);
+    set_header(ctx, &removed.header);
+    PROBE_READ_USER(RB_MEMPOOL_REMOVED, &removed.txid, sizeof(removed.txid), txid);
     bpf_probe_read_user_str(&removed.reason, sizeof(removed.reason), reason);
     removed.vsize = vsize;
     removed.fee = fee;
     removed.entry_time = entry_time;
-    return bpf_ringbuf_output(&mempool_removed, &removed, sizeof(removed), 0);
+    return RINGBUF_OUTPUT(mempool_removed, RB_MEMPOOL_REMOVED, &removed);
 };
 
 SEC("usdt")
@@ -404,22 +635,24 @@ int BPF_USDT(handle_mempool_replaced,
     void *replacement_txid, s32 replacement_vsize, s64 replacement_fee
 ) {
     struct MempoolReplaced replaced = {};
-    bpf_probe_read_user(&replaced.replaced_txid, sizeof(replaced.replaced_txid), replaced_txid);
+    set_header(ctx, &replaced.header);
+    PROBE_READ_USER(RB_MEMPOOL_REPLACED, &replaced.replaced_txid, sizeof(replaced.replaced_txid), replaced_txid);
     replaced.replaced_vsize = replaced_vsize;
     replaced.replaced_fee = replaced_fee;
     replaced.replaced_entry_time = replaced_entry_time;
-    bpf_probe_read_user(&replaced.replacement_txid, sizeof(replaced.replacement_txid), replacement_txid);
+    PROBE_READ_USER(RB_MEMPOOL_REPLACED, &replaced.replacement_txid, sizeof(replaced.replacement_txid), replacement_txid);
     replaced.replacement_vsize = replacement_vsize;
     replaced.replacement_fee = replacement_fee;
-    return bpf_ringbuf_output(&mempool_replaced, &replaced, sizeof(replaced), 0);
+    return RINGBUF_OUTPUT(mempool_replaced, RB_MEMPOOL_REPLACED, &replaced);
 };
 
 SEC("usdt")
 int BPF_USDT(handle_mempool_rejected, void *txid, void *reason) {
     struct MempoolRejected rejected = {};
-    bpf_probe_read_user(&rejected.txid, sizeof(rejected.txid), txid);
+    set_header(ctx, &rejected.header);
+    PROBE_READ_USER(RB_MEMPOOL_REJECTED, &rejected.txid, sizeof(rejected.txid), txid);
     bpf_probe_read_user_str(&rejected.reason, sizeof(rejected.reason), reason);
-    return bpf_ringbuf_output(&mempool_rejected, &rejected, sizeof(rejected), 0);
+    return RINGBUF_OUTPUT(mempool_rejected, RB_MEMPOOL_REJECTED, &rejected);
 };
 
 // VALIDATION
@@ -431,6 +664,7 @@ RINGBUFFER(validation_block_connected, VALIDATION_BLOCK_CONNECTED_PAGES)
 #define HASH_LENGHT 32
 
 struct BlockConnected {
+  struct EventHeader header;
   u8     hash[HASH_LENGHT];
   s32    height;
   u64    transactions;
@@ -442,13 +676,175 @@ struct BlockConnected {
 SEC("usdt")
 int BPF_USDT(handle_validation_block_connected, void *hash, s32 height, u64 transactions, s32 inputs, u64 sigops, u64 connection_time) {
     struct BlockConnected connected = {};
-    bpf_probe_read_user(&connected.hash, sizeof(connected.hash), hash);
+    set_header(ctx, &connected.header);
+    PROBE_READ_USER(RB_VALIDATION_BLOCK_CONNECTED, &connected.hash, sizeof(connected.hash), hash);
     connected.height = height;
     connected.transactions = transactions;
     connected.inputs = inputs;
     connected.sigops = sigops;
     connected.connection_time = connection_time;
-    return bpf_ringbuf_output(&validation_block_connected, &connected, sizeof(connected), 0);
+    return RINGBUF_OUTPUT(validation_block_connected, RB_VALIDATION_BLOCK_CONNECTED, &connected);
+};
+
+// UTXO CACHE
+
+#define UTXOCACHE_FLUSH_PAGES 64
+// Every coin of a connected block is added and spent, during IBD that's a
+// lot of events.
+#define UTXOCACHE_COINS 65536
+
+struct UtxocacheFlush {
+  struct EventHeader header;
+  s64    duration;
+  u32    mode;
+  u64    coins_count;
+  u64    coins_mem_usage;
+  bool   is_flush_for_prune;
+};
+
+struct UtxocacheCoin {
+  struct EventHeader header;
+  u8     txid[TXID_LENGHT];
+  u32    index;
+  u32    height;
+  s64    value;
+  bool   is_coinbase;
+};
+
+RINGBUFFER(utxocache_flush, UTXOCACHE_FLUSH_PAGES)
+// 80 bytes per coin. libbpf rounds the sizes up to a power of two.
+RINGBUFFER(utxocache_add, sizeof(struct UtxocacheCoin) * UTXOCACHE_COINS) // 5 MiB -> 8 MiB
+RINGBUFFER(utxocache_spent, sizeof(struct UtxocacheCoin) * UTXOCACHE_COINS) // 5 MiB -> 8 MiB
+RINGBUFFER(utxocache_uncache, sizeof(struct UtxocacheCoin) * UTXOCACHE_COINS / 16) // 320 KiB -> 512 KiB
+
+SEC("usdt")
+int BPF_USDT(handle_utxocache_flush, s64 duration, u32 mode, u64 coins_count, u64 coins_mem_usage, bool is_flush_for_prune) {
+    struct UtxocacheFlush flush = {};
+    set_header(ctx, &flush.header);
+    flush.duration = duration;
+    flush.mode = mode;
+    flush.coins_count = coins_count;
+    flush.coins_mem_usage = coins_mem_usage;
+    flush.is_flush_for_prune = is_flush_for_prune;
+    return RINGBUF_OUTPUT(utxocache_flush, RB_UTXOCACHE_FLUSH, &flush);
+};
+
+// utxocache:add, utxocache:spent and utxocache:uncache pass the same arguments
+static __always_inline void set_coin(struct pt_regs *ctx, struct UtxocacheCoin *coin, u32 rb, void *txid, u32 index, u32 height, s64 value, bool is_coinbase) {
+    set_header(ctx, &coin->header);
+    PROBE_READ_USER(rb, &coin->txid, sizeof(coin->txid), txid);
+    coin->index = index;
+    coin->height = height;
+    coin->value = value;
+    coin->is_coinbase = is_coinbase;
+}
+
+SEC("usdt")
+int BPF_USDT(handle_utxocache_add, void *txid, u32 index, u32 height, s64 value, bool is_coinbase) {
+    struct UtxocacheCoin coin = {};
+    set_coin(ctx, &coin, RB_UTXOCACHE_ADD, txid, index, height, value, is_coinbase);
+    return RINGBUF_OUTPUT(utxocache_add, RB_UTXOCACHE_ADD, &coin);
+};
+
+SEC("usdt")
+int BPF_USDT(handle_utxocache_spent, void *txid, u32 index, u32 height, s64 value, bool is_coinbase) {
+    struct UtxocacheCoin coin = {};
+    set_coin(ctx, &coin, RB_UTXOCACHE_SPENT, txid, index, height, value, is_coinbase);
+    return RINGBUF_OUTPUT(utxocache_spent, RB_UTXOCACHE_SPENT, &coin);
+};
+
+SEC("usdt")
+int BPF_USDT(handle_utxocache_uncache, void *txid, u32 index, u32 height, s64 value, bool is_coinbase) {
+    struct UtxocacheCoin coin = {};
+    set_coin(ctx, &coin, RB_UTXOCACHE_UNCACHE, txid, index, height, value, is_coinbase);
+    return RINGBUF_OUTPUT(utxocache_uncache, RB_UTXOCACHE_UNCACHE, &coin);
+};
+
+// COIN SELECTION
+
+#define COIN_SELECTION_PAGES 64
+
+// Longer wallet names are truncated
+#define MAX_WALLET_NAME_LENGTH 128
+#define MAX_ALGORITHM_LENGTH 16
+
+RINGBUFFER(coin_selection_selected_coins, COIN_SELECTION_PAGES)
+RINGBUFFER(coin_selection_normal_create_tx, COIN_SELECTION_PAGES)
+RINGBUFFER(coin_selection_attempting_aps_create_tx, COIN_SELECTION_PAGES)
+RINGBUFFER(coin_selection_aps_create_tx, COIN_SELECTION_PAGES)
+
+struct SelectedCoins {
+  struct EventHeader header;
+  char   wallet_name[MAX_WALLET_NAME_LENGTH];
+  char   algorithm[MAX_ALGORITHM_LENGTH];
+  s64    target;
+  s64    waste;
+  s64    selected_value;
+};
+
+struct NormalCreateTx {
+  struct EventHeader header;
+  char   wallet_name[MAX_WALLET_NAME_LENGTH];
+  bool   success;
+  s64    fee;
+  s32    change_pos;
+};
+
+struct AttemptingApsCreateTx {
+  struct EventHeader header;
+  char   wallet_name[MAX_WALLET_NAME_LENGTH];
+};
+
+struct ApsCreateTx {
+  struct EventHeader header;
+  char   wallet_name[MAX_WALLET_NAME_LENGTH];
+  bool   use_aps;
+  bool   success;
+  s64    fee;
+  s32    change_pos;
+};
+
+SEC("usdt")
+int BPF_USDT(handle_coin_selection_selected_coins, void *wallet_name, void *algorithm, s64 target, s64 waste, s64 selected_value) {
+    struct SelectedCoins selected = {};
+    set_header(ctx, &selected.header);
+    bpf_probe_read_user_str(&selected.wallet_name, sizeof(selected.wallet_name), wallet_name);
+    bpf_probe_read_user_str(&selected.algorithm, sizeof(selected.algorithm), algorithm);
+    selected.target = target;
+    selected.waste = waste;
+    selected.selected_value = selected_value;
+    return RINGBUF_OUTPUT(coin_selection_selected_coins, RB_COIN_SELECTION_SELECTED_COINS, &selected);
+};
+
+SEC("usdt")
+int BPF_USDT(handle_coin_selection_normal_create_tx, void *wallet_name, bool success, s64 fee, s32 change_pos) {
+    struct NormalCreateTx created = {};
+    set_header(ctx, &created.header);
+    bpf_probe_read_user_str(&created.wallet_name, sizeof(created.wallet_name), wallet_name);
+    created.success = success;
+    created.fee = fee;
+    created.change_pos = change_pos;
+    return RINGBUF_OUTPUT(coin_selection_normal_create_tx, RB_COIN_SELECTION_NORMAL_CREATE_TX, &created);
+};
+
+SEC("usdt")
+int BPF_USDT(handle_coin_selection_attempting_aps_create_tx, void *wallet_name) {
+    struct AttemptingApsCreateTx attempting = {};
+    set_header(ctx, &attempting.header);
+    bpf_probe_read_user_str(&attempting.wallet_name, sizeof(attempting.wallet_name), wallet_name);
+    return RINGBUF_OUTPUT(coin_selection_attempting_aps_create_tx, RB_COIN_SELECTION_ATTEMPTING_APS_CREATE_TX, &attempting);
+};
+
+SEC("usdt")
+int BPF_USDT(handle_coin_selection_aps_create_tx, void *wallet_name, bool use_aps, bool success, s64 fee, s32 change_pos) {
+    struct ApsCreateTx created = {};
+    set_header(ctx, &created.header);
+    bpf_probe_read_user_str(&created.wallet_name, sizeof(created.wallet_name), wallet_name);
+    created.use_aps = use_aps;
+    created.success = success;
+    created.fee = fee;
+    created.change_pos = change_pos;
+    return RINGBUF_OUTPUT(coin_selection_aps_create_tx, RB_COIN_SELECTION_APS_CREATE_TX, &created);
 };
 
 char LICENSE[] SEC("license") = "Dual BSD/GPL";
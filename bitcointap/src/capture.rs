@@ -0,0 +1,613 @@
+use crate::RuntimeError;
+use crate::clock::realtime_offset_ns;
+use crate::tracepoint::RINGBUFFERS;
+use shared::ctypes::{
+    AddrmanInsertNew, AddrmanInsertTried, ClosedConnection, CoinSelectionApsCreateTx,
+    CoinSelectionAttemptingApsCreateTx, CoinSelectionNormalCreateTx, CoinSelectionSelectedCoins,
+    EventHeader, InboundConnection, MempoolAdded, MempoolRejected, MempoolRemoved, MempoolReplaced,
+    MisbehavingConnection, OutboundConnection, P2PMessageMetadata, UtxocacheCoin, UtxocacheFlush,
+    ValidationBlockConnected,
+};
+use shared::log;
+use shared::validation::ConnectionTimeUnit;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, ErrorKind, Read, Write};
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// The first bytes of a capture file
+pub const CAPTURE_MAGIC: &[u8; 4] = b"BTAP";
+
+/// The version of the capture file format. Bumped on incompatible changes
+/// of the header or the records.
+pub const CAPTURE_VERSION: u32 = 1;
+
+/// The header of a capture file. All integers are little-endian, strings
+/// are prefixed with their length as u16. After the magic and the format
+/// version follow:
+///
+/// - the bitcointap version (string)
+/// - the time the capture started, in nanoseconds since the UNIX epoch (u64)
+/// - the offset of CLOCK_REALTIME to the kernel timestamps' CLOCK_MONOTONIC
+///   in nanoseconds (u64)
+/// - the number of targets (u32), each with its label (string), bitcoind
+///   version (string, empty if unknown) and the unit of the block connection
+///   times (u8, 0 for µs and 1 for ns)
+/// - the number of ring buffer layouts (u32), each with the ring buffer name
+///   (string), the size of the fixed part of its records (u32) and the
+///   fingerprint of their fields (u64)
+///
+/// Then the records follow until the end of the file, each with the index
+/// of its ring buffer in the layouts (u16), the kernel timestamp (u64), the
+/// number of bytes (u32) and the bytes as read from the ring buffer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaptureHeader {
+    pub version: u32,
+    /// The version of the bitcointap that wrote the capture
+    pub bitcointap_version: String,
+    /// When the capture started, in nanoseconds since the UNIX epoch
+    pub started_ns: u64,
+    /// Add this to a kernel timestamp to get nanoseconds since the UNIX
+    /// epoch on the machine the capture was taken on
+    pub realtime_offset_ns: u64,
+    /// The targets, indexed by [`EventHeader::target`]
+    pub targets: Vec<CaptureTarget>,
+    pub layouts: Vec<RecordLayout>,
+}
+
+/// A bitcoind process recorded in a capture
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaptureTarget {
+    pub label: String,
+    pub bitcoind_version: Option<String>,
+    pub connection_time_unit: ConnectionTimeUnit,
+}
+
+/// The name of a ring buffer and the size of the fixed part of its records,
+/// the `#[repr(C)]` struct in [`shared::ctypes`]. P2P messages are followed
+/// by their payload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordLayout {
+    pub ringbuffer: String,
+    pub size: u32,
+    /// A hash of the size and the field offsets of the struct, to tell
+    /// records of the same size but with moved fields apart
+    pub fingerprint: u64,
+}
+
+impl RecordLayout {
+    /// The layouts of the ring buffers of this build
+    pub fn current() -> Vec<RecordLayout> {
+        RINGBUFFERS
+            .iter()
+            .map(|name| RecordLayout {
+                ringbuffer: name.to_string(),
+                size: record_size(name) as u32,
+                fingerprint: record_fingerprint(name),
+            })
+            .collect()
+    }
+}
+
+/// The size and the field offsets of a `#[repr(C)]` struct, without the
+/// fields of its [`EventHeader`]
+macro_rules! fields {
+    ($type:ty: $($($field:ident).+),+) => {
+        (
+            mem::size_of::<$type>(),
+            vec![$(mem::offset_of!($type, $($field).+)),+],
+        )
+    };
+}
+
+/// The size of the fixed part of the records in the ring buffer and the
+/// offsets of their fields
+fn record_fields(ringbuffer: &str) -> (usize, Vec<usize>) {
+    let (size, mut offsets) = fields!(EventHeader: ktime_ns, seq, pid, target);
+    let (size, fields) = match ringbuffer {
+        "net_msg_small" | "net_msg_medium" | "net_msg_large" | "net_msg_huge" | "net_msg_meta" => {
+            let (meta_size, meta) = fields!(P2PMessageMetadata:
+                peer_id, peer_addr, peer_conn_type, msg_type, msg_inbound, msg_size);
+            (
+                size + meta_size,
+                meta.into_iter().map(|offset| size + offset).collect(),
+            )
+        }
+        "net_conn_inbound" => fields!(InboundConnection:
+            connection.id, connection.addr, connection.conn_type, connection.network,
+            existing_connections),
+        "net_conn_outbound" => fields!(OutboundConnection:
+            connection.id, connection.addr, connection.conn_type, connection.network,
+            existing_connections),
+        "net_conn_closed" | "net_conn_inbound_evicted" => fields!(ClosedConnection:
+            connection.id, connection.addr, connection.conn_type, connection.network,
+            time_established),
+        "net_conn_misbehaving" => fields!(MisbehavingConnection:
+            id, score_before, score_increase, message, threshold_exceeded),
+        "addrman_insert_new" => fields!(AddrmanInsertNew:
+            inserted, bucket, bucket_pos, addr, addr_as, source, source_as),
+        "addrman_insert_tried" => fields!(AddrmanInsertTried:
+            bucket, bucket_pos, addr, addr_as, source, source_as),
+        "mempool_added" => fields!(MempoolAdded: txid, vsize, fee),
+        "mempool_removed" => fields!(MempoolRemoved: txid, reason, vsize, fee, entry_time),
+        "mempool_replaced" => fields!(MempoolReplaced:
+            replaced_txid, replaced_vsize, replaced_fee, replaced_entry_time,
+            replacement_txid, replacement_vsize, replacement_fee),
+        "mempool_rejected" => fields!(MempoolRejected: txid, reason),
+        "validation_block_connected" => fields!(ValidationBlockConnected:
+            hash, height, transactions, inputs, sigops, connection_time),
+        "utxocache_flush" => fields!(UtxocacheFlush:
+            duration, mode, coins_count, coins_mem_usage, is_flush_for_prune),
+        "utxocache_add" | "utxocache_spent" | "utxocache_uncache" => fields!(UtxocacheCoin:
+            txid, index, height, value, is_coinbase),
+        "coin_selection_selected_coins" => fields!(CoinSelectionSelectedCoins:
+            wallet_name, algorithm, target, waste, selected_value),
+        "coin_selection_normal_create_tx" => fields!(CoinSelectionNormalCreateTx:
+            wallet_name, success, fee, change_pos),
+        "coin_selection_attempting_aps_create_tx" => fields!(CoinSelectionAttemptingApsCreateTx:
+            wallet_name),
+        "coin_selection_aps_create_tx" => fields!(CoinSelectionApsCreateTx:
+            wallet_name, use_aps, success, fee, change_pos),
+        _ => (size, vec![]),
+    };
+    offsets.extend(fields);
+    (size, offsets)
+}
+
+/// The size of the fixed part of the records in the ring buffer
+pub(crate) fn record_size(ringbuffer: &str) -> usize {
+    record_fields(ringbuffer).0
+}
+
+/// The FNV-1a hash of the size and the field offsets of the records in the
+/// ring buffer. Unlike the std hashers, it's the same for every build.
+pub(crate) fn record_fingerprint(ringbuffer: &str) -> u64 {
+    let (size, offsets) = record_fields(ringbuffer);
+    let mut hash = 0xcbf2_9ce4_8422_2325u64;
+    for byte in [size]
+        .iter()
+        .chain(&offsets)
+        .flat_map(|n| (*n as u64).to_le_bytes())
+    {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    hash
+}
+
+/// Writes the raw ring buffer records of a running tap to a capture file
+pub(crate) struct CaptureWriter {
+    out: BufWriter<File>,
+    layouts: Vec<RecordLayout>,
+    /// Only the first write error is logged
+    failed: bool,
+}
+
+impl CaptureWriter {
+    /// Create the capture file and write the header
+    pub(crate) fn create(path: &Path, targets: Vec<CaptureTarget>) -> Result<Self, RuntimeError> {
+        Self::with_layouts(path, targets, RecordLayout::current())
+    }
+
+    /// Write other layouts than the ones of this build, e.g. to test
+    /// replaying captures of other versions
+    pub(crate) fn with_layouts(
+        path: &Path,
+        targets: Vec<CaptureTarget>,
+        layouts: Vec<RecordLayout>,
+    ) -> Result<Self, RuntimeError> {
+        let header = CaptureHeader {
+            version: CAPTURE_VERSION,
+            bitcointap_version: env!("CARGO_PKG_VERSION").to_string(),
+            started_ns: SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)?
+                .as_nanos() as u64,
+            realtime_offset_ns: realtime_offset_ns().unwrap_or_default(),
+            targets,
+            layouts,
+        };
+        let mut out = BufWriter::new(File::create(path)?);
+        write_header(&mut out, &header)?;
+        log::info!("Recording the ring buffer records to '{}'", path.display());
+        Ok(CaptureWriter {
+            out,
+            layouts: header.layouts,
+            failed: false,
+        })
+    }
+
+    /// Append a record read from the ring buffer. P2P messages are cut after
+    /// their payload, the rest of the ring buffer slot is padding.
+    pub(crate) fn write(&mut self, ringbuffer: &str, data: &[u8]) {
+        let Some(index) = self
+            .layouts
+            .iter()
+            .position(|layout| layout.ringbuffer == ringbuffer)
+        else {
+            return;
+        };
+        let ktime_ns = match data.len() >= mem::size_of::<EventHeader>() {
+            true => EventHeader::from_bytes(data).ktime_ns,
+            false => 0,
+        };
+        let data = record_bytes(ringbuffer, data);
+        let result = write_record(&mut self.out, index as u16, ktime_ns, data);
+        self.check(result);
+    }
+
+    /// Write the buffered records to the file. A capture cut off by a crash
+    /// only loses the records since the last flush.
+    pub(crate) fn flush(&mut self) {
+        let result = self.out.flush();
+        self.check(result);
+    }
+
+    fn check(&mut self, result: io::Result<()>) {
+        if let Err(e) = result {
+            if !self.failed {
+                log::error!("Could not write to the capture file: {}", e);
+            }
+            self.failed = true;
+        }
+    }
+}
+
+impl Drop for CaptureWriter {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+/// The bytes of the record without the unused part of its ring buffer slot:
+/// P2P messages end after `msg_size` bytes of payload
+fn record_bytes<'a>(ringbuffer: &str, data: &'a [u8]) -> &'a [u8] {
+    let fixed = record_size(ringbuffer);
+    if !ringbuffer.starts_with("net_msg_") || data.len() < fixed {
+        return data;
+    }
+    let meta = P2PMessageMetadata::from_bytes(&data[mem::size_of::<EventHeader>()..]);
+    let payload = usize::try_from(meta.msg_size).unwrap_or(usize::MAX);
+    &data[..fixed.saturating_add(payload).min(data.len())]
+}
+
+/// A record of a capture file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaptureRecord {
+    /// The index of its ring buffer in [`CaptureHeader::layouts`]
+    pub layout: u16,
+    /// When the tracepoint fired, `bpf_ktime_get_ns()` on the machine the
+    /// capture was taken on
+    pub ktime_ns: u64,
+    /// The bytes as read from the ring buffer
+    pub data: Vec<u8>,
+}
+
+/// Reads a capture file written by [`crate::BitcoinTap::capture`]. The
+/// records are read one after another. A capture cut off while writing a
+/// record ends before it.
+pub struct CaptureReader {
+    input: BufReader<File>,
+    path: PathBuf,
+    header: CaptureHeader,
+}
+
+impl CaptureReader {
+    /// Open the capture file and read its header
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, RuntimeError> {
+        let path = path.as_ref().to_owned();
+        let mut input = BufReader::new(File::open(&path)?);
+        let header = read_header(&mut input)
+            .map_err(|e| RuntimeError::Capture(path.clone(), e.to_string()))?;
+        Ok(CaptureReader {
+            input,
+            path,
+            header,
+        })
+    }
+
+    pub fn header(&self) -> &CaptureHeader {
+        &self.header
+    }
+
+    /// The next record, or `None` at the end of the capture
+    pub fn next_record(&mut self) -> Result<Option<CaptureRecord>, RuntimeError> {
+        let record = match read_record(&mut self.input) {
+            Ok(record) => record,
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => {
+                log::warn!(
+                    "The capture '{}' ends within a record. Skipping it.",
+                    self.path.display()
+                );
+                None
+            }
+            Err(e) => return Err(e.into()),
+        };
+        match record {
+            Some(record) if record.layout as usize >= self.header.layouts.len() => {
+                Err(RuntimeError::Capture(
+                    self.path.clone(),
+                    format!("a record has the unknown layout {}", record.layout),
+                ))
+            }
+            record => Ok(record),
+        }
+    }
+}
+
+impl Iterator for CaptureReader {
+    type Item = Result<CaptureRecord, RuntimeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_record().transpose()
+    }
+}
+
+fn write_header(out: &mut impl Write, header: &CaptureHeader) -> io::Result<()> {
+    out.write_all(CAPTURE_MAGIC)?;
+    out.write_all(&header.version.to_le_bytes())?;
+    write_str(out, &header.bitcointap_version)?;
+    out.write_all(&header.started_ns.to_le_bytes())?;
+    out.write_all(&header.realtime_offset_ns.to_le_bytes())?;
+    out.write_all(&(header.targets.len() as u32).to_le_bytes())?;
+    for target in &header.targets {
+        write_str(out, &target.label)?;
+        write_str(out, target.bitcoind_version.as_deref().unwrap_or_default())?;
+        let unit: u8 = match target.connection_time_unit {
+            ConnectionTimeUnit::Microseconds => 0,
+            ConnectionTimeUnit::Nanoseconds => 1,
+        };
+        out.write_all(&[unit])?;
+    }
+    out.write_all(&(header.layouts.len() as u32).to_le_bytes())?;
+    for layout in &header.layouts {
+        write_str(out, &layout.ringbuffer)?;
+        out.write_all(&layout.size.to_le_bytes())?;
+        out.write_all(&layout.fingerprint.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+pub(crate) fn write_record(
+    out: &mut impl Write,
+    index: u16,
+    ktime_ns: u64,
+    data: &[u8],
+) -> io::Result<()> {
+    out.write_all(&index.to_le_bytes())?;
+    out.write_all(&ktime_ns.to_le_bytes())?;
+    out.write_all(&(data.len() as u32).to_le_bytes())?;
+    out.write_all(data)
+}
+
+/// Strings longer than u16::MAX bytes are cut off
+fn write_str(out: &mut impl Write, s: &str) -> io::Result<()> {
+    let bytes = &s.as_bytes()[..s.len().min(u16::MAX as usize)];
+    out.write_all(&(bytes.len() as u16).to_le_bytes())?;
+    out.write_all(bytes)
+}
+
+fn read_header(input: &mut impl Read) -> io::Result<CaptureHeader> {
+    let mut magic = [0u8; 4];
+    input.read_exact(&mut magic)?;
+    if &magic != CAPTURE_MAGIC {
+        return Err(io::Error::new(ErrorKind::InvalidData, "not a capture file"));
+    }
+    let version = read_u32(input)?;
+    if version != CAPTURE_VERSION {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            format!(
+                "unsupported format version {}, expected {}",
+                version, CAPTURE_VERSION
+            ),
+        ));
+    }
+    let bitcointap_version = read_str(input)?;
+    let started_ns = read_u64(input)?;
+    let realtime_offset_ns = read_u64(input)?;
+    let mut targets = vec![];
+    for _ in 0..read_u32(input)? {
+        let label = read_str(input)?;
+        let bitcoind_version = Some(read_str(input)?).filter(|version| !version.is_empty());
+        let mut unit = [0u8; 1];
+        input.read_exact(&mut unit)?;
+        let connection_time_unit = match unit[0] {
+            0 => ConnectionTimeUnit::Microseconds,
+            1 => ConnectionTimeUnit::Nanoseconds,
+            unit => {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!("unknown connection time unit {}", unit),
+                ));
+            }
+        };
+        targets.push(CaptureTarget {
+            label,
+            bitcoind_version,
+            connection_time_unit,
+        });
+    }
+    let mut layouts = vec![];
+    for _ in 0..read_u32(input)? {
+        let ringbuffer = read_str(input)?;
+        let size = read_u32(input)?;
+        let fingerprint = read_u64(input)?;
+        layouts.push(RecordLayout {
+            ringbuffer,
+            size,
+            fingerprint,
+        });
+    }
+    Ok(CaptureHeader {
+        version,
+        bitcointap_version,
+        started_ns,
+        realtime_offset_ns,
+        targets,
+        layouts,
+    })
+}
+
+/// `None` at the end of the input
+fn read_record(input: &mut impl BufRead) -> io::Result<Option<CaptureRecord>> {
+    if input.fill_buf()?.is_empty() {
+        return Ok(None);
+    }
+    let mut layout = [0u8; 2];
+    input.read_exact(&mut layout)?;
+    let ktime_ns = read_u64(input)?;
+    let len = read_u32(input)?;
+    // Don't trust the length with the allocation, the file might be cut off
+    let mut data = vec![];
+    input.by_ref().take(len as u64).read_to_end(&mut data)?;
+    if data.len() != len as usize {
+        return Err(ErrorKind::UnexpectedEof.into());
+    }
+    Ok(Some(CaptureRecord {
+        layout: u16::from_le_bytes(layout),
+        ktime_ns,
+        data,
+    }))
+}
+
+fn read_u32(input: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    input.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(input: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0u8; 8];
+    input.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_str(input: &mut impl Read) -> io::Result<String> {
+    let mut len = [0u8; 2];
+    input.read_exact(&mut len)?;
+    let mut bytes = vec![0u8; u16::from_le_bytes(len) as usize];
+    input.read_exact(&mut bytes)?;
+    String::from_utf8(bytes).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header() -> CaptureHeader {
+        CaptureHeader {
+            version: CAPTURE_VERSION,
+            bitcointap_version: "0.1.0".to_string(),
+            started_ns: 1_700_000_000_000_000_000,
+            realtime_offset_ns: 1_699_999_000_000_000_000,
+            targets: vec![
+                CaptureTarget {
+                    label: "main".to_string(),
+                    bitcoind_version: Some("v28.1.0".to_string()),
+                    connection_time_unit: ConnectionTimeUnit::Nanoseconds,
+                },
+                CaptureTarget {
+                    label: "signet".to_string(),
+                    bitcoind_version: None,
+                    connection_time_unit: ConnectionTimeUnit::Microseconds,
+                },
+            ],
+            layouts: RecordLayout::current(),
+        }
+    }
+
+    #[test]
+    fn header_round_trip() {
+        let mut bytes = vec![];
+        write_header(&mut bytes, &header()).unwrap();
+        assert_eq!(read_header(&mut bytes.as_slice()).unwrap(), header());
+    }
+
+    #[test]
+    fn reject_other_captures() {
+        let mut bytes = vec![];
+        write_header(&mut bytes, &header()).unwrap();
+
+        let mut other_version = bytes.clone();
+        other_version[4..8].copy_from_slice(&(CAPTURE_VERSION + 1).to_le_bytes());
+        let error = read_header(&mut other_version.as_slice()).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+
+        let mut other_magic = bytes.clone();
+        other_magic[..4].copy_from_slice(b"PCAP");
+        let error = read_header(&mut other_magic.as_slice()).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+
+        let error = read_header(&mut &bytes[..bytes.len() - 1]).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn record_round_trip() {
+        let mut bytes = vec![];
+        write_record(&mut bytes, 3, 42, &[1, 2, 3]).unwrap();
+        let mut input = bytes.as_slice();
+        let record = read_record(&mut input).unwrap();
+        assert_eq!(
+            record,
+            Some(CaptureRecord {
+                layout: 3,
+                ktime_ns: 42,
+                data: vec![1, 2, 3],
+            })
+        );
+        assert_eq!(read_record(&mut input).unwrap(), None);
+
+        let error = read_record(&mut &bytes[..bytes.len() - 1]).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn net_msgs_without_padding() {
+        let path = std::env::temp_dir().join(format!(
+            "bitcointap-capture-padding-{}.btap",
+            std::process::id()
+        ));
+        let fixed = record_size("net_msg_huge");
+        // The whole slot of a 300 byte message in net_msg_huge
+        let mut slot = vec![0u8; fixed + 4 * 1024 * 1024];
+        let msg_size_at =
+            mem::size_of::<EventHeader>() + mem::offset_of!(P2PMessageMetadata, msg_size);
+        slot[msg_size_at..][..8].copy_from_slice(&300u64.to_le_bytes());
+        let mempool = vec![0u8; record_size("mempool_added")];
+
+        let mut writer = CaptureWriter::create(&path, vec![]).unwrap();
+        writer.write("net_msg_huge", &slot);
+        writer.write("mempool_added", &mempool);
+        drop(writer);
+
+        let sizes: Vec<usize> = CaptureReader::open(&path)
+            .unwrap()
+            .map(|record| record.unwrap().data.len())
+            .collect();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(sizes, vec![fixed + 300, mempool.len()]);
+    }
+
+    #[test]
+    fn fingerprints() {
+        // Ring buffers of the same struct share the fingerprint
+        assert_eq!(
+            record_fingerprint("utxocache_add"),
+            record_fingerprint("utxocache_uncache")
+        );
+        assert_eq!(
+            record_fingerprint("net_msg_small"),
+            record_fingerprint("net_msg_meta")
+        );
+        assert_ne!(
+            record_fingerprint("mempool_added"),
+            record_fingerprint("mempool_rejected")
+        );
+        assert_ne!(
+            record_fingerprint("utxocache_add"),
+            record_fingerprint("utxocache_flush")
+        );
+    }
+}
@@ -0,0 +1,27 @@
+use std::mem::MaybeUninit;
+
+fn clock_ns(clock: libc::clockid_t) -> Option<u64> {
+    let mut ts = MaybeUninit::<libc::timespec>::uninit();
+    if unsafe { libc::clock_gettime(clock, ts.as_mut_ptr()) } != 0 {
+        return None;
+    }
+    let ts = unsafe { ts.assume_init() };
+    Some(ts.tv_sec as u64 * 1_000_000_000 + ts.tv_nsec as u64)
+}
+
+/// Convert a `bpf_ktime_get_ns()` timestamp (CLOCK_MONOTONIC) into
+/// nanoseconds since the UNIX epoch. The offset between the two clocks is
+/// taken now, so wall clock adjustments apply to earlier events as well.
+pub(crate) fn ktime_to_unix_ns(ktime_ns: u64) -> Option<u64> {
+    let realtime = clock_ns(libc::CLOCK_REALTIME)?;
+    let monotonic = clock_ns(libc::CLOCK_MONOTONIC)?;
+    (realtime + ktime_ns).checked_sub(monotonic)
+}
+
+/// The offset of CLOCK_REALTIME to CLOCK_MONOTONIC: add it to a
+/// `bpf_ktime_get_ns()` timestamp to get nanoseconds since the UNIX epoch
+pub(crate) fn realtime_offset_ns() -> Option<u64> {
+    let realtime = clock_ns(libc::CLOCK_REALTIME)?;
+    let monotonic = clock_ns(libc::CLOCK_MONOTONIC)?;
+    realtime.checked_sub(monotonic)
+}
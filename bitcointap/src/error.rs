@@ -1,8 +1,10 @@
+use crate::BitcoindProcess;
 use shared::log::SetLoggerError;
 use std::error;
 use std::fmt;
 use std::io::Error as IoError;
 use std::num::ParseIntError;
+use std::path::PathBuf;
 use std::time::SystemTimeError;
 
 #[derive(Debug)]
@@ -15,6 +17,33 @@ pub enum RuntimeError {
     SystemTime(SystemTimeError),
     SetLogger(SetLoggerError),
     MissingBitcoind,
+    AmbiguousBitcoind(Vec<BitcoindProcess>),
+    NoTracepoints,
+    /// The binary has no USDT probe for a selected tracepoint
+    ProbeNotFound {
+        provider: String,
+        name: String,
+        path: PathBuf,
+    },
+    /// Attaching the BPF program to a tracepoint of the process failed
+    AttachFailed {
+        tracepoint: String,
+        pid: i32,
+        error: libbpf_rs::Error,
+    },
+    PidNotRunning(i32),
+    BinaryNotFound(PathBuf),
+    /// Loading the BPF programs was not permitted. Needs root, or CAP_BPF
+    /// and CAP_PERFMON.
+    PermissionDenied(libbpf_rs::Error),
+    /// Setting up the polling of the BPF ring buffers failed
+    RingBufferBuild(libbpf_rs::Error),
+    Elf(PathBuf, String),
+    /// The capture file can't be read, or a record in it can't be replayed
+    Capture(PathBuf, String),
+    InvalidFilter(String),
+    DuplicateTarget(String),
+    Stopped,
 }
 
 impl fmt::Display for RuntimeError {
@@ -27,6 +56,62 @@ impl fmt::Display for RuntimeError {
             RuntimeError::SetLogger(e) => write!(f, "set logger error {}", e),
             RuntimeError::NoSuchBPFMap(map) => write!(f, "could not find the BPF map {}", map),
             RuntimeError::MissingBitcoind => write!(f, "Could not find bitcoind"),
+            RuntimeError::AmbiguousBitcoind(processes) => {
+                write!(f, "found more than one bitcoind:")?;
+                for process in processes {
+                    write!(f, "\n  {}", process)?;
+                }
+                Ok(())
+            }
+            RuntimeError::NoTracepoints => write!(f, "no tracepoints enabled"),
+            RuntimeError::ProbeNotFound {
+                provider,
+                name,
+                path,
+            } => write!(
+                f,
+                "the tracepoint {}:{} is not available in '{}'",
+                provider,
+                name,
+                path.display()
+            ),
+            RuntimeError::AttachFailed {
+                tracepoint,
+                pid,
+                error,
+            } => write!(
+                f,
+                "could not attach to the tracepoint {} of PID={}: {}",
+                tracepoint, pid, error
+            ),
+            RuntimeError::PidNotRunning(pid) => write!(f, "no process with PID={} is running", pid),
+            RuntimeError::BinaryNotFound(path) => {
+                write!(f, "the binary '{}' does not exist", path.display())
+            }
+            RuntimeError::PermissionDenied(e) => write!(
+                f,
+                "permission denied, run as root or with CAP_BPF and CAP_PERFMON: {}",
+                e
+            ),
+            RuntimeError::RingBufferBuild(e) => {
+                write!(f, "could not set up the BPF ring buffers: {}", e)
+            }
+            RuntimeError::Elf(path, e) => {
+                write!(
+                    f,
+                    "could not read USDT probes of '{}': {}",
+                    path.display(),
+                    e
+                )
+            }
+            RuntimeError::Capture(path, e) => {
+                write!(f, "could not read the capture '{}': {}", path.display(), e)
+            }
+            RuntimeError::InvalidFilter(e) => write!(f, "invalid filter: {}", e),
+            RuntimeError::DuplicateTarget(label) => {
+                write!(f, "more than one target is labeled '{}'", label)
+            }
+            RuntimeError::Stopped => write!(f, "the tap is not running"),
             RuntimeError::NoSuchBPFProg(prog) => {
                 write!(f, "could not find the BPF program {}", prog)
             }
@@ -45,6 +130,19 @@ impl error::Error for RuntimeError {
             RuntimeError::NoSuchBPFMap(_) => None,
             RuntimeError::NoSuchBPFProg(_) => None,
             RuntimeError::MissingBitcoind => None,
+            RuntimeError::AmbiguousBitcoind(_) => None,
+            RuntimeError::NoTracepoints => None,
+            RuntimeError::ProbeNotFound { .. } => None,
+            RuntimeError::AttachFailed { ref error, .. } => Some(error),
+            RuntimeError::PidNotRunning(_) => None,
+            RuntimeError::BinaryNotFound(_) => None,
+            RuntimeError::PermissionDenied(ref e) => Some(e),
+            RuntimeError::RingBufferBuild(ref e) => Some(e),
+            RuntimeError::Elf(_, _) => None,
+            RuntimeError::Capture(_, _) => None,
+            RuntimeError::InvalidFilter(_) => None,
+            RuntimeError::DuplicateTarget(_) => None,
+            RuntimeError::Stopped => None,
         }
     }
 }
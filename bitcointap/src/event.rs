@@ -1,10 +1,14 @@
 pub use shared::addrman::AddrmanEvent as AddrmanMsg;
 pub use shared::addrman::addrman_event::Event as AddrmanEvent;
+pub use shared::coin_selection::CoinSelectionEvent as CoinSelectionMsg;
+pub use shared::coin_selection::coin_selection_event::Event as CoinSelectionEvent;
 pub use shared::event_msg::EventMsg;
 pub use shared::event_msg::event_msg::Event;
 pub use shared::mempool::MempoolEvent as MempoolMsg;
 pub use shared::mempool::mempool_event::Event as MempoolEvent;
 pub use shared::net_conn::ConnectionEvent as ConnectionMsg;
 pub use shared::net_conn::connection_event::Event as ConnectionEvent;
+pub use shared::utxocache::UtxocacheEvent as UtxocacheMsg;
+pub use shared::utxocache::utxocache_event::Event as UtxocacheEvent;
 pub use shared::validation::ValidationEvent as ValidationMsg;
 pub use shared::validation::validation_event::Event as ValidationEvent;
@@ -0,0 +1,354 @@
+use crate::RuntimeError;
+use crate::tap::find_map;
+use libbpf_rs::{Map, MapCore, MapFlags, Object};
+use std::borrow::Borrow;
+use std::collections::BTreeSet;
+
+const FILTER_MAP: &str = "net_msg_filter";
+const FILTER_MSG_TYPES_MAP: &str = "net_msg_filter_msg_types";
+const FILTER_PEERS_MAP: &str = "net_msg_filter_peers";
+
+const FILTER_ANY: u8 = 0;
+const FILTER_ALLOW: u8 = 1;
+const FILTER_DENY: u8 = 2;
+
+const MAX_MSG_TYPE_LENGTH: usize = 12;
+/// The number of message types a filter can list
+pub const MAX_FILTER_MSG_TYPES: usize = 64;
+/// The number of peers a filter can list
+pub const MAX_FILTER_PEERS: usize = 4096;
+
+/// An allow or deny list
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum ListFilter<T> {
+    /// Everything passes
+    #[default]
+    Any,
+    /// Only the listed entries pass
+    Allow(BTreeSet<T>),
+    /// Everything but the listed entries passes
+    Deny(BTreeSet<T>),
+}
+
+impl<T: Ord> ListFilter<T> {
+    /// Does `entry` pass the filter?
+    pub fn matches<Q: Ord + ?Sized>(&self, entry: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        match self {
+            ListFilter::Any => true,
+            ListFilter::Allow(list) => list.contains(entry),
+            ListFilter::Deny(list) => !list.contains(entry),
+        }
+    }
+
+    fn mode(&self) -> u8 {
+        match self {
+            ListFilter::Any => FILTER_ANY,
+            ListFilter::Allow(_) => FILTER_ALLOW,
+            ListFilter::Deny(_) => FILTER_DENY,
+        }
+    }
+
+    fn entries(&self) -> impl Iterator<Item = &T> {
+        match self {
+            ListFilter::Any => None,
+            ListFilter::Allow(list) | ListFilter::Deny(list) => Some(list.iter()),
+        }
+        .into_iter()
+        .flatten()
+    }
+}
+
+/// Selects the P2P messages of the `net:inbound_message` and
+/// `net:outbound_message` tracepoints. The filter is applied by the BPF
+/// programs, filtered messages are never copied into the ring buffers. The
+/// default filter passes every message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetMsgFilter {
+    /// The message types, e.g. `version` or `addrv2`, of at most 12 bytes
+    pub msg_types: ListFilter<String>,
+    /// The peer ids
+    pub peers: ListFilter<u64>,
+    pub inbound: bool,
+    pub outbound: bool,
+    /// Skip messages with a larger payload
+    pub max_payload_size: Option<u64>,
+}
+
+impl Default for NetMsgFilter {
+    fn default() -> Self {
+        NetMsgFilter {
+            msg_types: ListFilter::Any,
+            peers: ListFilter::Any,
+            inbound: true,
+            outbound: true,
+            max_payload_size: None,
+        }
+    }
+}
+
+impl NetMsgFilter {
+    /// A filter passing every message
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only pass messages of these types
+    pub fn allow_msg_types(mut self, types: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.msg_types = ListFilter::Allow(types.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Skip messages of these types
+    pub fn deny_msg_types(mut self, types: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.msg_types = ListFilter::Deny(types.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Only pass messages from and to these peers
+    pub fn allow_peers(mut self, peers: impl IntoIterator<Item = u64>) -> Self {
+        self.peers = ListFilter::Allow(peers.into_iter().collect());
+        self
+    }
+
+    /// Skip messages from and to these peers
+    pub fn deny_peers(mut self, peers: impl IntoIterator<Item = u64>) -> Self {
+        self.peers = ListFilter::Deny(peers.into_iter().collect());
+        self
+    }
+
+    /// Pass inbound messages
+    pub fn inbound(mut self, enable: bool) -> Self {
+        self.inbound = enable;
+        self
+    }
+
+    /// Pass outbound messages
+    pub fn outbound(mut self, enable: bool) -> Self {
+        self.outbound = enable;
+        self
+    }
+
+    /// Skip messages with a payload larger than `bytes`
+    pub fn max_payload_size(mut self, bytes: u64) -> Self {
+        self.max_payload_size = Some(bytes);
+        self
+    }
+
+    /// Does the message pass the filter? This is what the BPF programs check.
+    pub fn matches(&self, peer_id: u64, msg_type: &str, inbound: bool, payload_size: u64) -> bool {
+        (if inbound { self.inbound } else { self.outbound })
+            && self.max_payload_size.is_none_or(|max| payload_size <= max)
+            && self.peers.matches(&peer_id)
+            && self.msg_type_matches(msg_type)
+    }
+
+    /// Message types are compared by their BPF map keys, as a type of
+    /// [`MAX_MSG_TYPE_LENGTH`] bytes is truncated like in the BPF programs
+    fn msg_type_matches(&self, msg_type: &str) -> bool {
+        let key = msg_type_key(msg_type);
+        let listed = self
+            .msg_types
+            .entries()
+            .any(|entry| msg_type_key(entry) == key);
+        match self.msg_types {
+            ListFilter::Any => true,
+            ListFilter::Allow(_) => listed,
+            ListFilter::Deny(_) => !listed,
+        }
+    }
+
+    /// Check that the filter fits into the BPF maps
+    pub fn validate(&self) -> Result<(), RuntimeError> {
+        if let Some(msg_type) = self
+            .msg_types
+            .entries()
+            .find(|msg_type| msg_type.len() > MAX_MSG_TYPE_LENGTH)
+        {
+            return Err(RuntimeError::InvalidFilter(format!(
+                "the message type '{}' is longer than {} bytes",
+                msg_type, MAX_MSG_TYPE_LENGTH
+            )));
+        }
+        if self.msg_types.entries().count() > MAX_FILTER_MSG_TYPES {
+            return Err(RuntimeError::InvalidFilter(format!(
+                "more than {} message types",
+                MAX_FILTER_MSG_TYPES
+            )));
+        }
+        if self.peers.entries().count() > MAX_FILTER_PEERS {
+            return Err(RuntimeError::InvalidFilter(format!(
+                "more than {} peers",
+                MAX_FILTER_PEERS
+            )));
+        }
+        Ok(())
+    }
+
+    /// Write the filter into the BPF maps. It applies to the next message.
+    pub(crate) fn write_to(&self, obj: &Object) -> Result<(), RuntimeError> {
+        self.validate()?;
+
+        let msg_types = self
+            .msg_types
+            .entries()
+            .map(|msg_type| msg_type_key(msg_type).to_vec())
+            .collect();
+        replace_keys(&find_map(obj, FILTER_MSG_TYPES_MAP)?, msg_types)?;
+
+        let peers = self
+            .peers
+            .entries()
+            .map(|peer| peer.to_ne_bytes().to_vec())
+            .collect();
+        replace_keys(&find_map(obj, FILTER_PEERS_MAP)?, peers)?;
+
+        // struct NetMsgFilter in tracing.bpf.c
+        let mut value = [0u8; 16];
+        value[..8].copy_from_slice(&self.max_payload_size.unwrap_or_default().to_ne_bytes());
+        value[8] = self.msg_types.mode();
+        value[9] = self.peers.mode();
+        value[10] = !self.inbound as u8;
+        value[11] = !self.outbound as u8;
+        value[12] = self.max_payload_size.is_some() as u8;
+        find_map(obj, FILTER_MAP)?.update(&0u32.to_ne_bytes(), &value, MapFlags::ANY)?;
+        Ok(())
+    }
+}
+
+/// The message type as `net_msg_wanted` in tracing.bpf.c reads it with
+/// `bpf_probe_read_user_str` into a `char[MAX_MSG_TYPE_LENGTH]`: at most
+/// `MAX_MSG_TYPE_LENGTH - 1` bytes, NUL-terminated and NUL-padded.
+fn msg_type_key(msg_type: &str) -> [u8; MAX_MSG_TYPE_LENGTH] {
+    let mut key = [0u8; MAX_MSG_TYPE_LENGTH];
+    let bytes = msg_type.as_bytes();
+    let len = bytes.len().min(MAX_MSG_TYPE_LENGTH - 1);
+    key[..len].copy_from_slice(&bytes[..len]);
+    key
+}
+
+/// Make `keys` the keys of the BPF hash map
+fn replace_keys(map: &Map, keys: Vec<Vec<u8>>) -> Result<(), RuntimeError> {
+    let old: Vec<Vec<u8>> = map.keys().filter(|key| !keys.contains(key)).collect();
+    for key in old {
+        map.delete(&key)?;
+    }
+    for key in keys {
+        map.update(&key, &[1], MapFlags::ANY)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn msg_type_keys() {
+        assert_eq!(&msg_type_key("ping"), b"ping\0\0\0\0\0\0\0\0");
+        assert_eq!(&msg_type_key("sendheaders"), b"sendheaders\0");
+        // bpf_probe_read_user_str leaves room for the NUL
+        assert_eq!(&msg_type_key("sendtemplate"), b"sendtemplat\0");
+        assert_eq!(&msg_type_key(""), &[0u8; MAX_MSG_TYPE_LENGTH]);
+    }
+
+    #[test]
+    fn matches_everything_by_default() {
+        let filter = NetMsgFilter::new();
+        assert!(filter.matches(1, "ping", true, 8));
+        assert!(filter.matches(2, "block", false, 4_000_000));
+    }
+
+    #[test]
+    fn matches_directions() {
+        let filter = NetMsgFilter::new().inbound(false);
+        assert!(!filter.matches(1, "ping", true, 8));
+        assert!(filter.matches(1, "ping", false, 8));
+
+        let filter = NetMsgFilter::new().outbound(false);
+        assert!(filter.matches(1, "ping", true, 8));
+        assert!(!filter.matches(1, "ping", false, 8));
+    }
+
+    #[test]
+    fn matches_payload_size() {
+        let filter = NetMsgFilter::new().max_payload_size(8);
+        assert!(filter.matches(1, "ping", true, 8));
+        assert!(!filter.matches(1, "block", true, 9));
+    }
+
+    #[test]
+    fn matches_peers() {
+        let filter = NetMsgFilter::new().allow_peers([1, 2]);
+        assert!(filter.matches(1, "ping", true, 8));
+        assert!(!filter.matches(3, "ping", true, 8));
+
+        let filter = NetMsgFilter::new().deny_peers([1, 2]);
+        assert!(!filter.matches(1, "ping", true, 8));
+        assert!(filter.matches(3, "ping", true, 8));
+    }
+
+    #[test]
+    fn matches_msg_types() {
+        let filter = NetMsgFilter::new().allow_msg_types(["ping", "pong"]);
+        assert!(filter.matches(1, "ping", true, 8));
+        assert!(!filter.matches(1, "inv", true, 37));
+
+        let filter = NetMsgFilter::new().deny_msg_types(["inv"]);
+        assert!(filter.matches(1, "ping", true, 8));
+        assert!(!filter.matches(1, "inv", true, 37));
+    }
+
+    #[test]
+    fn matches_truncated_msg_types() {
+        // A recorded message type has at most 11 bytes
+        let filter = NetMsgFilter::new().allow_msg_types(["sendtemplate"]);
+        assert!(filter.matches(1, "sendtemplat", true, 8));
+        assert!(filter.matches(1, "sendtemplate", true, 8));
+        assert!(!filter.matches(1, "sendtempla", true, 8));
+    }
+
+    #[test]
+    fn reject_long_msg_types() {
+        assert!(
+            NetMsgFilter::new()
+                .allow_msg_types(["sendheaders!"])
+                .validate()
+                .is_ok()
+        );
+        assert!(matches!(
+            NetMsgFilter::new()
+                .allow_msg_types(["sendheaders!!"])
+                .validate(),
+            Err(RuntimeError::InvalidFilter(_))
+        ));
+        assert!(matches!(
+            NetMsgFilter::new()
+                .deny_msg_types(["sendheaders!!"])
+                .validate(),
+            Err(RuntimeError::InvalidFilter(_))
+        ));
+    }
+
+    #[test]
+    fn reject_long_lists() {
+        let types = (0..=MAX_FILTER_MSG_TYPES).map(|i| i.to_string());
+        assert!(matches!(
+            NetMsgFilter::new().allow_msg_types(types).validate(),
+            Err(RuntimeError::InvalidFilter(_))
+        ));
+        let peers = 0..=MAX_FILTER_PEERS as u64;
+        assert!(matches!(
+            NetMsgFilter::new().deny_peers(peers).validate(),
+            Err(RuntimeError::InvalidFilter(_))
+        ));
+        assert!(
+            NetMsgFilter::new()
+                .allow_peers(0..MAX_FILTER_PEERS as u64)
+                .validate()
+                .is_ok()
+        );
+    }
+}
@@ -0,0 +1,55 @@
+use crate::RuntimeError;
+use crate::tap::find_map;
+use crate::tracepoint::RINGBUFFERS;
+use libbpf_rs::{MapCore, MapFlags, Object};
+use shared::ctypes::RingBufferStats;
+use std::collections::BTreeMap;
+
+/// The name of the BPF map with the [`RingBufferStats`]
+const RINGBUF_STATS_MAP: &str = "ringbuf_stats";
+
+/// The events lost in the kernel, before they reached us
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KernelStats {
+    /// The counters of the active ring buffers by name, e.g. `net_msg_small`
+    pub ringbuffers: BTreeMap<&'static str, RingBufferStats>,
+}
+
+impl KernelStats {
+    /// The counters of all ring buffers summed up
+    pub fn total(&self) -> RingBufferStats {
+        let mut total = RingBufferStats::default();
+        for stats in self.ringbuffers.values() {
+            total += *stats;
+        }
+        total
+    }
+
+    /// The number of events lost in the kernel. If this is zero, no event
+    /// of the active tracepoints was dropped.
+    pub fn lost(&self) -> u64 {
+        self.total().lost()
+    }
+}
+
+/// Read the counters of the given ring buffers. The BPF map is per-CPU, the
+/// counters are summed up over all CPUs.
+pub(crate) fn read_kernel_stats(
+    obj: &Object,
+    ringbuffers: &[&'static str],
+) -> Result<KernelStats, RuntimeError> {
+    let map = find_map(obj, RINGBUF_STATS_MAP)?;
+    let mut stats = KernelStats::default();
+    for name in ringbuffers {
+        let Some(id) = RINGBUFFERS.iter().position(|rb| rb == name) else {
+            continue;
+        };
+        let key = (id as u32).to_ne_bytes();
+        let mut total = RingBufferStats::default();
+        for value in map.lookup_percpu(&key, MapFlags::ANY)?.unwrap_or_default() {
+            total += RingBufferStats::from_bytes(&value);
+        }
+        stats.ringbuffers.insert(name, total);
+    }
+    Ok(stats)
+}
@@ -1,18 +1,50 @@
+mod capture;
+mod clock;
 mod error;
 mod event;
+mod filter;
+mod kernel_stats;
+mod preflight;
+mod process;
+mod queue;
+mod reorder;
+mod replay;
+mod sink;
+#[cfg(feature = "async")]
+mod stream;
 mod tap;
 mod tracepoint;
+mod usdt;
+mod version;
 
 #[path = "tracing.gen.rs"]
 pub mod tracing;
 
+pub use capture::{
+    CAPTURE_MAGIC, CAPTURE_VERSION, CaptureHeader, CaptureReader, CaptureRecord, CaptureTarget,
+    RecordLayout,
+};
 pub use error::RuntimeError;
 pub use event::{
-    AddrmanEvent, AddrmanMsg, ConnectionEvent, ConnectionMsg, Event, EventMsg, MempoolEvent,
-    MempoolMsg, ValidationEvent, ValidationMsg,
+    AddrmanEvent, AddrmanMsg, CoinSelectionEvent, CoinSelectionMsg, ConnectionEvent, ConnectionMsg,
+    Event, EventMsg, MempoolEvent, MempoolMsg, UtxocacheEvent, UtxocacheMsg, ValidationEvent,
+    ValidationMsg,
 };
-pub use tap::{BitcoinTap, PidSource, TapMsg};
+pub use filter::{ListFilter, MAX_FILTER_MSG_TYPES, MAX_FILTER_PEERS, NetMsgFilter};
+pub use kernel_stats::KernelStats;
+pub use preflight::{Check, CheckStatus, Preflight};
+pub use process::{BitcoindProcess, find_bitcoind_processes};
+pub use queue::{Backpressure, DEFAULT_QUEUE_CAPACITY, EventReceiver, QueueStats};
+pub use replay::ReplaySpeed;
+pub use shared::ctypes::RingBufferStats;
+pub use shared::validation::ConnectionTimeUnit;
+#[cfg(feature = "async")]
+pub use stream::TapStream;
+pub use tap::{BitcoinTap, PidSource, TapHandle, TapMsg, Target};
 pub use tracepoint::{
-    TRACEPOINTS_ADDRMAN, TRACEPOINTS_MEMPOOL, TRACEPOINTS_NET_CONN, TRACEPOINTS_NET_MESSAGE,
-    TRACEPOINTS_VALIDATION, Tracepoint,
+    RINGBUFFERS, TRACEPOINTS_ADDRMAN, TRACEPOINTS_COIN_SELECTION, TRACEPOINTS_MEMPOOL,
+    TRACEPOINTS_NET_CONN, TRACEPOINTS_NET_MESSAGE, TRACEPOINTS_UTXOCACHE, TRACEPOINTS_VALIDATION,
+    Tracepoint, TracepointGroup, find_tracepoint,
 };
+pub use usdt::{ElfError, UsdtProbe, parse_usdt_probes, read_usdt_probes};
+pub use version::{BitcoindVersion, parse_bitcoind_version, read_bitcoind_version};
@@ -1,9 +1,16 @@
 #![cfg_attr(feature = "strict", deny(warnings))]
 
-use bitcointap::{BitcoinTap, PidSource, RuntimeError, TapMsg};
-use clap::{Parser, arg, command};
+use bitcointap::{
+    Backpressure, BitcoinTap, BitcoindProcess, Check, CheckStatus, ConnectionTimeUnit,
+    DEFAULT_QUEUE_CAPACITY, NetMsgFilter, PidSource, RINGBUFFERS, ReplaySpeed, RuntimeError,
+    TapMsg, Target, Tracepoint, TracepointGroup, UsdtProbe, find_bitcoind_processes,
+    find_tracepoint,
+};
+use clap::{Parser, Subcommand, arg, command};
 use shared::log;
-use std::path::PathBuf;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 /// The peer-observer extractor hooks into a Bitcoin Core binary with
 /// tracepoints and publishes events into a NATS pub-sub queue.
@@ -11,30 +18,364 @@ use std::path::PathBuf;
 #[command(version, about, long_about = None)]
 struct Args {
     /// Path to the Bitcoin Core (bitcoind) binary that should be hooked into.
-    #[arg(short, long, default_value = "")]
+    #[arg(short, long, global = true, default_value = "")]
     bitcoind_path: String,
 
     /// PID (Process ID) of the Bitcoin Core (bitcoind) binary that should be hooked into.
     /// If this is set, the --bitcoind-pid-file argument isn't used.
     // TODO: remove the default value once https://github.com/bitcoin/bitcoin/pull/26593 is merged
-    #[arg(long, default_value_t = -1)]
+    #[arg(long, global = true, default_value_t = -1)]
     bitcoind_pid: i32,
 
     /// File containing the PID (Process ID) of the Bitcoin Core (bitcoind) binary that should be hooked into.
     /// If --bitcoind-pid is set, this flag is ignored.
-    #[arg(long, default_value = "")]
+    #[arg(long, global = true, default_value = "")]
     bitcoind_pid_file: String,
 
+    /// Attach to the bitcoind in the container with this (host) PID, e.g. from
+    /// `docker inspect --format '{{.State.Pid}}' <container>`. The binary is
+    /// read through /proc/<pid>/root, --bitcoind-path is not needed. With
+    /// --reattach, container restarts are followed through the cgroup of the
+    /// container, which requires cgroup v2.
+    #[arg(long, global = true, value_name = "PID", conflicts_with = "cgroup")]
+    container_pid: Option<i32>,
+
+    /// Attach to the bitcoind in this cgroup, e.g.
+    /// /sys/fs/cgroup/system.slice/docker-<id>.scope. Requires cgroup v2. With
+    /// --reattach, this survives container restarts.
+    #[arg(long, global = true, value_name = "PATH")]
+    cgroup: Option<PathBuf>,
+
+    /// Only look for bitcoind processes running this chain: main, test,
+    /// testnet4, signet or regtest. Used when neither a PID nor a PID file
+    /// is given.
+    #[arg(long, global = true)]
+    chain: Option<String>,
+
+    /// Label the events of bitcoind with this, e.g. mainnet. Defaults to the
+    /// chain of a bitcoind found in /proc, or else the path of the binary.
+    #[arg(long)]
+    label: Option<String>,
+
+    /// Attach to the bitcoind process with this PID and label its events,
+    /// given as <label>=<pid>, e.g. testnet4=1234. Can be used multiple times
+    /// to tap several nodes at once. Replaces the --bitcoind-* flags.
+    #[arg(
+        long = "target",
+        global = true,
+        value_name = "LABEL=PID",
+        value_parser = parse_target,
+    )]
+    targets: Vec<(String, i32)>,
+
+    /// The unit bitcoind passes block connection times in: "us" up to
+    /// Bitcoin Core 24.x, "ns" since 25.x. Detected from the version of the
+    /// binary by default. The events always have them in µs.
+    #[arg(long, global = true, value_name = "UNIT", value_parser = parse_connection_time_unit)]
+    connection_time_unit: Option<ConnectionTimeUnit>,
+
     /// If used, libbpf will print debug information about the BPF maps,
     /// programs, and tracepoints during extractor startup. This can be
     /// useful during debugging.
     #[arg(long, default_value_t = false)]
     libbpf_debug: bool,
 
+    /// Keep running when bitcoind exits and re-attach once it restarts,
+    /// e.g. after an upgrade.
+    #[arg(long, default_value_t = false)]
+    reattach: bool,
+
+    /// The number of events that are queued for output before --backpressure
+    /// kicks in.
+    #[arg(long, default_value_t = DEFAULT_QUEUE_CAPACITY)]
+    queue_capacity: usize,
+
+    /// What to do when the event queue is full: "block" reading the ring
+    /// buffers, "drop-newest" or "drop-oldest" events.
+    #[arg(long, default_value = "block", value_parser = parse_backpressure)]
+    backpressure: Backpressure,
+
+    /// Log the number of queued and dropped events, and the events lost in
+    /// the kernel, every N seconds. 0 disables the stats.
+    #[arg(long, default_value_t = 10)]
+    stats_interval: u64,
+
+    /// Hold events back for N milliseconds to output them in the order their
+    /// tracepoints fired, across all ring buffers. 0 disables the reordering.
+    #[arg(long, default_value_t = 0)]
+    reorder_window: u64,
+
+    /// Set the size of a BPF ring buffer given as <name>=<bytes>, e.g.
+    /// net_msg_huge=64M. K, M and G suffixes are powers of 1024. Can be used
+    /// multiple times. See --list-tracepoints for the ring buffer names.
+    #[arg(
+        long = "ringbuffer-size",
+        global = true,
+        value_name = "NAME=SIZE",
+        value_parser = parse_ringbuffer_size,
+    )]
+    ringbuffer_sizes: Vec<(String, u32)>,
+
     /// The log level the extractor should run with. Valid log levels are "trace",
     /// "debug", "info", "warn", "error". See https://docs.rs/log/latest/log/enum.Level.html
     #[arg(short, long, default_value_t = log::Level::Debug)]
     log_level: log::Level,
+
+    /// Enable the P2P message tracepoints (net:inbound_message, net:outbound_message).
+    #[arg(long, global = true, default_value_t = false)]
+    net_msg: bool,
+
+    /// Enable the P2P connection tracepoints (net:*_connection).
+    #[arg(long, global = true, default_value_t = false)]
+    net_conn: bool,
+
+    /// Enable the mempool tracepoints.
+    #[arg(long, global = true, default_value_t = false)]
+    mempool: bool,
+
+    /// Enable the validation tracepoints.
+    #[arg(long, global = true, default_value_t = false)]
+    validation: bool,
+
+    /// Enable the addrman tracepoints.
+    #[arg(long, global = true, default_value_t = false)]
+    addrman: bool,
+
+    /// Enable the UTXO cache tracepoints (utxocache:flush, add, spent and
+    /// uncache). Every coin of a block is added and spent, so these fire a lot.
+    #[arg(long, global = true, default_value_t = false)]
+    utxocache: bool,
+
+    /// Enable the wallet's coin selection tracepoints (coin_selection:selected_coins,
+    /// normal_create_tx_internal, attempting_aps_create_tx and aps_create_tx_internal).
+    #[arg(long, global = true, default_value_t = false)]
+    coin_selection: bool,
+
+    /// Enable a single tracepoint given as <context>:<name>, e.g. net:inbound_message.
+    /// Can be used multiple times. See --list-tracepoints for the known tracepoints.
+    /// If neither a tracepoint nor a group is selected, all but the addrman,
+    /// utxocache and coin selection tracepoints are enabled.
+    #[arg(
+        long = "tracepoint",
+        global = true,
+        value_name = "CONTEXT:NAME",
+        value_parser = parse_tracepoint,
+    )]
+    tracepoints: Vec<Tracepoint<'static>>,
+
+    /// Only record the metadata of P2P messages (peer, type, direction and
+    /// size), not their payload.
+    #[arg(long, default_value_t = false)]
+    net_msg_metadata_only: bool,
+
+    /// Only pass P2P messages of this type, e.g. version. Can be used
+    /// multiple times. The messages are filtered in the kernel.
+    #[arg(
+        long = "msg-type",
+        value_name = "TYPE",
+        conflicts_with = "skip_msg_types"
+    )]
+    msg_types: Vec<String>,
+
+    /// Skip P2P messages of this type, e.g. block. Can be used multiple times.
+    #[arg(long = "skip-msg-type", value_name = "TYPE")]
+    skip_msg_types: Vec<String>,
+
+    /// Only pass P2P messages from and to the peer with this id. Can be used
+    /// multiple times.
+    #[arg(long = "peer", value_name = "ID", conflicts_with = "skip_peers")]
+    peers: Vec<u64>,
+
+    /// Skip P2P messages from and to the peer with this id. Can be used
+    /// multiple times.
+    #[arg(long = "skip-peer", value_name = "ID")]
+    skip_peers: Vec<u64>,
+
+    /// Only pass inbound P2P messages.
+    #[arg(long, default_value_t = false, conflicts_with = "outbound_only")]
+    inbound_only: bool,
+
+    /// Only pass outbound P2P messages.
+    #[arg(long, default_value_t = false)]
+    outbound_only: bool,
+
+    /// Skip P2P messages with a payload larger than this many bytes.
+    #[arg(long, value_name = "BYTES")]
+    max_payload_size: Option<u64>,
+
+    /// List the known tracepoints and exit. With --bitcoind-path, also show
+    /// which of them the binary supports.
+    #[arg(long, default_value_t = false)]
+    list_tracepoints: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Check if bitcointap can attach: the privileges, the kernel, the
+    /// memory limits, and the binary and process of bitcoind. Exits with 1
+    /// if a check fails.
+    Doctor,
+    /// Record the raw ring buffer records to a capture file, e.g. to decode
+    /// or replay them later. Takes the same flags as tapping.
+    Record {
+        /// The capture file to write
+        #[arg(long, value_name = "FILE")]
+        out: PathBuf,
+    },
+    /// Replay a capture file written with record and print its events, as
+    /// if they came from bitcoind. Needs neither root nor bitcoind. Without
+    /// tracepoint flags, all recorded events are replayed.
+    Replay {
+        /// The capture file to read
+        #[arg(long = "in", value_name = "FILE")]
+        input: PathBuf,
+
+        /// Keep the time between the events as recorded instead of
+        /// replaying as fast as possible
+        #[arg(long, default_value_t = false)]
+        original_speed: bool,
+    },
+}
+
+impl Args {
+    /// The P2P message filter set with the filter flags
+    fn net_msg_filter(&self) -> NetMsgFilter {
+        let mut filter = NetMsgFilter::new()
+            .inbound(!self.outbound_only)
+            .outbound(!self.inbound_only);
+        if !self.msg_types.is_empty() {
+            filter = filter.allow_msg_types(&self.msg_types);
+        } else if !self.skip_msg_types.is_empty() {
+            filter = filter.deny_msg_types(&self.skip_msg_types);
+        }
+        if !self.peers.is_empty() {
+            filter = filter.allow_peers(self.peers.iter().copied());
+        } else if !self.skip_peers.is_empty() {
+            filter = filter.deny_peers(self.skip_peers.iter().copied());
+        }
+        if let Some(bytes) = self.max_payload_size {
+            filter = filter.max_payload_size(bytes);
+        }
+        filter
+    }
+
+    /// The tracepoint groups selected with the group flags
+    fn groups(&self) -> Vec<TracepointGroup> {
+        [
+            (self.net_msg, TracepointGroup::NetMessage),
+            (self.net_conn, TracepointGroup::NetConn),
+            (self.mempool, TracepointGroup::Mempool),
+            (self.validation, TracepointGroup::Validation),
+            (self.addrman, TracepointGroup::Addrman),
+            (self.utxocache, TracepointGroup::Utxocache),
+            (self.coin_selection, TracepointGroup::CoinSelection),
+        ]
+        .into_iter()
+        .filter_map(|(enabled, group)| enabled.then_some(group))
+        .collect()
+    }
+}
+
+fn parse_tracepoint(s: &str) -> Result<Tracepoint<'static>, String> {
+    let (context, name) = s
+        .split_once(':')
+        .ok_or_else(|| format!("expected <context>:<name>, got '{}'", s))?;
+    find_tracepoint(context, name)
+        .copied()
+        .ok_or_else(|| format!("unknown tracepoint '{}'. See --list-tracepoints", s))
+}
+
+fn parse_target(s: &str) -> Result<(String, i32), String> {
+    let (label, pid) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected <label>=<pid>, got '{}'", s))?;
+    let pid = pid.parse().map_err(|_| format!("invalid PID '{}'", pid))?;
+    Ok((label.to_string(), pid))
+}
+
+fn parse_backpressure(s: &str) -> Result<Backpressure, String> {
+    match s {
+        "block" => Ok(Backpressure::Block),
+        "drop-newest" => Ok(Backpressure::DropNewest),
+        "drop-oldest" => Ok(Backpressure::DropOldest),
+        _ => Err(format!(
+            "unknown policy '{}', expected block, drop-newest or drop-oldest",
+            s
+        )),
+    }
+}
+
+fn parse_connection_time_unit(s: &str) -> Result<ConnectionTimeUnit, String> {
+    match s {
+        "us" | "µs" => Ok(ConnectionTimeUnit::Microseconds),
+        "ns" => Ok(ConnectionTimeUnit::Nanoseconds),
+        _ => Err(format!("unknown unit '{}', expected us or ns", s)),
+    }
+}
+
+fn parse_ringbuffer_size(s: &str) -> Result<(String, u32), String> {
+    let (name, size) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected <name>=<size>, got '{}'", s))?;
+    if !RINGBUFFERS.contains(&name) {
+        return Err(format!(
+            "unknown ring buffer '{}'. See --list-tracepoints",
+            name
+        ));
+    }
+    let (digits, unit) = match size.char_indices().last() {
+        Some((i, 'K' | 'k')) => (&size[..i], 1 << 10),
+        Some((i, 'M' | 'm')) => (&size[..i], 1 << 20),
+        Some((i, 'G' | 'g')) => (&size[..i], 1 << 30),
+        _ => (size, 1),
+    };
+    digits
+        .parse::<u32>()
+        .ok()
+        .and_then(|n| n.checked_mul(unit))
+        .map(|bytes| (name.to_string(), bytes))
+        .ok_or_else(|| format!("invalid ring buffer size '{}'", size))
+}
+
+/// Prints the known tracepoints. With `probes`, also prints if the bitcoind
+/// binary supports them and the probes of the binary we don't know about.
+fn list_tracepoints(probes: Option<&[UsdtProbe]>) {
+    for group in TracepointGroup::ALL {
+        println!("{}:", group);
+        for tracepoint in group.tracepoints() {
+            let available = match probes {
+                Some(probes) if probes.iter().any(|p| p.matches(tracepoint)) => "available",
+                Some(_) => "missing",
+                None => "",
+            };
+            println!(
+                "  {:<40} {:<36} {}",
+                tracepoint.to_string(),
+                tracepoint.function,
+                available
+            );
+        }
+    }
+
+    println!("ring buffers (see --ringbuffer-size):");
+    for name in RINGBUFFERS {
+        println!("  {}", name);
+    }
+
+    let Some(probes) = probes else { return };
+    let unknown: Vec<_> = probes
+        .iter()
+        .filter(|probe| find_tracepoint(&probe.provider, &probe.name).is_none())
+        .collect();
+    if !unknown.is_empty() {
+        println!("not supported by bitcointap:");
+        for probe in unknown {
+            println!("  {}", probe);
+        }
+    }
 }
 
 fn run() -> Result<(), RuntimeError> {
@@ -42,20 +383,98 @@ fn run() -> Result<(), RuntimeError> {
 
     simple_logger::init_with_level(args.log_level)?;
 
-    let (pid_src, path) = find_bitcoind_and_pid(&args)?;
+    if args.list_tracepoints {
+        if args.bitcoind_path.is_empty() {
+            list_tracepoints(None);
+        } else {
+            let probes = BitcoinTap::available_tracepoints(&args.bitcoind_path)?;
+            list_tracepoints(Some(&probes));
+        }
+        return Ok(());
+    }
 
-    log::info!("using pid source {:?}", &pid_src);
+    if let Some(Command::Doctor) = args.command {
+        // Still check the system when bitcoind can't be found
+        let (targets, error) = match targets(&args) {
+            Ok(targets) => (targets, None),
+            Err(e) => (
+                vec![Target::new(&args.bitcoind_path, PidSource::DefaultPid)],
+                Some(e),
+            ),
+        };
+        let mut preflight = tap(&args, targets).preflight();
+        if let Some(e) = error {
+            preflight.checks.push(Check {
+                name: "bitcoind".to_string(),
+                status: CheckStatus::Fail,
+                detail: e.to_string(),
+            });
+        }
+        print!("{}", preflight);
+        if !preflight.ok() {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
 
-    let mut tap = BitcoinTap::new(path)
-        .pid_source(pid_src)
-        .debug(args.libbpf_debug)
-        .attach()?;
+    let mut tap = match &args.command {
+        Some(Command::Replay {
+            input,
+            original_speed,
+        }) => replay(&args, input, *original_speed),
+        _ => tap(&args, targets(&args)?),
+    };
+    // When recording, the events are only counted
+    let capture = match &args.command {
+        Some(Command::Record { out }) => Some(out.clone()),
+        _ => None,
+    };
+    if let Some(out) = &capture {
+        tap = tap.capture(out);
+    }
+    let mut tap = tap.attach()?;
+
+    // The stats are totals, only warn when they grew
+    let mut dropped = 0;
+    let mut lost = 0;
+    let mut recorded = 0u64;
 
     while let Ok(ev) = &tap.events().recv() {
         match ev {
+            TapMsg::Event(_) if capture.is_some() => recorded += 1,
             TapMsg::Event(ev) => println!("{}", serde_json::to_string(ev).expect("json msg")),
             TapMsg::Error(err) => log::error!("{err}"),
-            TapMsg::Detached => break,
+            TapMsg::Detached { label: Some(label) } => {
+                log::warn!("bitcoind '{label}' exited, waiting for it..")
+            }
+            TapMsg::Detached { label: None } => break,
+            TapMsg::Reattached { label, pid } => {
+                log::info!("re-attached to bitcoind '{label}' with PID={pid}")
+            }
+            TapMsg::Stats(stats) if stats.total_dropped() > dropped => {
+                dropped = stats.total_dropped();
+                log::warn!(
+                    "{} of {} events queued, dropped: {:?}",
+                    stats.queued,
+                    stats.capacity,
+                    stats.dropped
+                )
+            }
+            TapMsg::Stats(stats) => {
+                log::debug!("{} of {} events queued", stats.queued, stats.capacity);
+                if capture.is_some() {
+                    log::info!("{} events recorded", recorded);
+                }
+            }
+            TapMsg::KernelStats(stats) if stats.lost() > lost => {
+                lost = stats.lost();
+                log::warn!(
+                    "{} events lost in the kernel: {}",
+                    stats.lost(),
+                    stats.total()
+                )
+            }
+            TapMsg::KernelStats(stats) => log::debug!("kernel: {}", stats.total()),
         }
     }
 
@@ -64,72 +483,159 @@ fn run() -> Result<(), RuntimeError> {
     Ok(())
 }
 
-fn main() {
-    if let Err(e) = run() {
-        log::error!("Fatal error during extractor runtime: {}", e);
+/// The bitcoind processes to attach to
+fn targets(args: &Args) -> Result<Vec<Target>, RuntimeError> {
+    let mut targets = vec![];
+    for (label, pid) in &args.targets {
+        let path = std::fs::read_link(format!("/proc/{}/exe", pid))
+            .map_err(|_| RuntimeError::PidNotRunning(*pid))?;
+        targets.push(Target::new(path, PidSource::Manual(*pid)).label(label));
     }
+    if targets.is_empty() {
+        let target = find_bitcoind(args)?;
+        log::info!("using pid source {:?}", &target.pid_source);
+        targets.push(match &args.label {
+            Some(label) => target.label(label),
+            None => target,
+        });
+    }
+    if let Some(unit) = args.connection_time_unit {
+        targets = targets
+            .into_iter()
+            .map(|target| target.connection_time_unit(unit))
+            .collect();
+    }
+    Ok(targets)
 }
 
-fn get_running_bitcoind_pid_and_path() -> Option<(i32, PathBuf)> {
-    let output = std::process::Command::new("pgrep")
-        .arg("-a")
-        .arg("bitcoin")
-        .output()
-        .ok()?;
-
-    if !output.status.success() {
-        return None;
+/// The tap configured with the flags
+fn tap(args: &Args, targets: Vec<Target>) -> BitcoinTap {
+    let mut targets = targets.into_iter();
+    let first = targets.next().expect("a target");
+    let mut tap = BitcoinTap::new(first.path)
+        .pid_source(first.pid_source)
+        .label(first.label)
+        .debug(args.libbpf_debug)
+        .supervise(args.reattach)
+        .queue_capacity(args.queue_capacity)
+        .backpressure(args.backpressure)
+        .stats_interval((args.stats_interval > 0).then(|| Duration::from_secs(args.stats_interval)))
+        .reorder_window(
+            (args.reorder_window > 0).then(|| Duration::from_millis(args.reorder_window)),
+        )
+        .groups(args.groups())
+        .tracepoints(args.tracepoints.iter().copied())
+        .net_msg_filter(args.net_msg_filter())
+        .net_msg_metadata_only(args.net_msg_metadata_only);
+    if let Some(unit) = first.connection_time_unit {
+        tap = tap.connection_time_unit(unit);
     }
+    for (name, bytes) in &args.ringbuffer_sizes {
+        tap = tap.ringbuffer_size(name, *bytes);
+    }
+    for target in targets {
+        tap = tap.target(target);
+    }
+    tap
+}
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    for line in stdout.lines() {
-        let mut parts = line.split_whitespace();
-        let pid_str = parts.next()?;
-        let pid: i32 = pid_str.parse().ok()?;
+/// The replay of a capture configured with the flags
+fn replay(args: &Args, input: &Path, original_speed: bool) -> BitcoinTap {
+    let speed = match original_speed {
+        true => ReplaySpeed::Original,
+        false => ReplaySpeed::Fast,
+    };
+    BitcoinTap::replay(input)
+        .replay_speed(speed)
+        .queue_capacity(args.queue_capacity)
+        .backpressure(args.backpressure)
+        .stats_interval((args.stats_interval > 0).then(|| Duration::from_secs(args.stats_interval)))
+        .groups(args.groups())
+        .tracepoints(args.tracepoints.iter().copied())
+        .net_msg_filter(args.net_msg_filter())
+        .net_msg_metadata_only(args.net_msg_metadata_only)
+}
 
-        for part in parts.clone() {
-            if part.contains("bitcoin") {
-                return Some((pid, PathBuf::from(part)));
-            }
-        }
+fn main() {
+    if let Err(e) = run() {
+        log::error!("Fatal error during extractor runtime: {}", e);
     }
-
-    None
 }
 
-fn find_bitcoind_and_pid(args: &Args) -> Result<(PidSource, PathBuf), RuntimeError> {
+/// The bitcoind to attach to. An explicit PID or PID file wins, otherwise
+/// the running bitcoind processes are looked up in /proc.
+fn find_bitcoind(args: &Args) -> Result<Target, RuntimeError> {
+    // The label defaults to the chain of the bitcoind found
+    if let Some(pid) = args.container_pid {
+        return Ok(Target::new("", PidSource::Container(pid)));
+    }
+    if let Some(cgroup) = &args.cgroup {
+        return Ok(Target::new("", PidSource::Cgroup(cgroup.clone())));
+    }
+
+    let path = PathBuf::from(&args.bitcoind_path);
     let pid_src = match args.bitcoind_pid {
-        -1 => {
-            if args.bitcoind_pid_file != "" {
-                PidSource::File(PathBuf::from(&args.bitcoind_pid_file))
-            } else {
-                PidSource::DefaultPid
-            }
+        -1 if !args.bitcoind_pid_file.is_empty() => {
+            PidSource::File(PathBuf::from(&args.bitcoind_pid_file))
         }
+        -1 => PidSource::Auto,
         pid => PidSource::Manual(pid),
     };
 
-    let detected = get_running_bitcoind_pid_and_path();
-
-    let pid_src = if let PidSource::DefaultPid = pid_src {
-        if let Some((detected_pid, _)) = &detected {
-            PidSource::Manual(*detected_pid)
-        } else {
-            PidSource::DefaultPid
+    let pid = match &pid_src {
+        PidSource::Manual(pid) => *pid,
+        PidSource::File(pid_file) => std::fs::read_to_string(pid_file)?.trim().parse()?,
+        _ => {
+            let mut processes = find_bitcoind_processes();
+            processes.retain(|process| {
+                (args.bitcoind_path.is_empty() || process.executes(&path))
+                    && args
+                        .chain
+                        .as_ref()
+                        .is_none_or(|chain| *chain == process.chain)
+            });
+            let process = match processes.len() {
+                // Trace all processes of the binary, once they start
+                0 if !args.bitcoind_path.is_empty() => {
+                    return Ok(Target::new(path, PidSource::DefaultPid));
+                }
+                0 => return Err(RuntimeError::MissingBitcoind),
+                1 => processes.remove(0),
+                _ => pick_bitcoind(processes)?,
+            };
+            log::info!("Found bitcoind {}", process);
+            return Ok(
+                Target::new(process.path, PidSource::Manual(process.pid)).label(process.chain)
+            );
         }
-    } else {
-        pid_src
     };
 
-    let path = if args.bitcoind_path != "" {
-        PathBuf::from(&args.bitcoind_path)
-    } else {
-        if let Some((_, detected_path)) = detected {
-            detected_path
-        } else {
-            return Err(RuntimeError::MissingBitcoind);
-        }
+    let path = match args.bitcoind_path.as_str() {
+        "" => std::fs::read_link(format!("/proc/{}/exe", pid))
+            .map_err(|_| RuntimeError::PidNotRunning(pid))?,
+        _ => path,
     };
+    Ok(Target::new(path, pid_src))
+}
 
-    Ok((pid_src, path))
+/// Let the user pick one of several bitcoind processes
+fn pick_bitcoind(mut processes: Vec<BitcoindProcess>) -> Result<BitcoindProcess, RuntimeError> {
+    if !std::io::stdin().is_terminal() {
+        return Err(RuntimeError::AmbiguousBitcoind(processes));
+    }
+    eprintln!("Found more than one bitcoind:");
+    for (i, process) in processes.iter().enumerate() {
+        eprintln!("  [{}] {}", i + 1, process);
+    }
+    loop {
+        eprint!("Attach to [1-{}]: ", processes.len());
+        let mut choice = String::new();
+        if std::io::stdin().read_line(&mut choice)? == 0 {
+            return Err(RuntimeError::AmbiguousBitcoind(processes));
+        }
+        match choice.trim().parse::<usize>() {
+            Ok(n) if (1..=processes.len()).contains(&n) => return Ok(processes.remove(n - 1)),
+            _ => eprintln!("Invalid choice '{}'", choice.trim()),
+        }
+    }
 }
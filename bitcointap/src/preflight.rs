@@ -0,0 +1,473 @@
+use crate::process::process_alive;
+use crate::tap::{DEFAULT_PID, Target, bitcoind_pid, find_bitcoind};
+use crate::usdt::BitcoindBinary;
+use crate::{PidSource, RuntimeError, Tracepoint, tracing};
+use libbpf_rs::skel::{OpenSkel, SkelBuilder};
+use libbpf_rs::{MapCore, OpenObject};
+use std::collections::BTreeMap;
+use std::ffi::CStr;
+use std::fmt;
+use std::fs;
+use std::mem::MaybeUninit;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+
+/// Ring buffers need Linux 5.8
+const MIN_KERNEL_RINGBUF: (u32, u32) = (5, 8);
+/// The atomic event sequence counter needs BPF v3 atomics of Linux 5.12
+const MIN_KERNEL_ATOMICS: (u32, u32) = (5, 12);
+/// Since Linux 5.11, BPF memory is charged to the cgroup instead of the
+/// memlock limit
+const MIN_KERNEL_MEMCG: (u32, u32) = (5, 11);
+
+const CAP_SYS_ADMIN: u32 = 21;
+const CAP_PERFMON: u32 = 38;
+const CAP_BPF: u32 = 39;
+
+const PAGE_SIZE: u64 = 4096;
+
+/// The outcome of a [`Check`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CheckStatus {
+    Pass,
+    /// Might cause problems, attaching can still work
+    Warn,
+    /// Attaching will fail
+    Fail,
+}
+
+impl fmt::Display for CheckStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CheckStatus::Pass => write!(f, "ok"),
+            CheckStatus::Warn => write!(f, "warn"),
+            CheckStatus::Fail => write!(f, "FAIL"),
+        }
+    }
+}
+
+/// A single preflight check
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Check {
+    /// What was checked, e.g. `privileges` or `binary mainnet`
+    pub name: String,
+    pub status: CheckStatus,
+    /// What was found, and what to do about a failure
+    pub detail: String,
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{:>4}] {}: {}", self.status, self.name, self.detail)
+    }
+}
+
+/// The results of [`crate::BitcoinTap::preflight`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Preflight {
+    pub checks: Vec<Check>,
+}
+
+impl Preflight {
+    /// Did no check fail?
+    pub fn ok(&self) -> bool {
+        self.failures().next().is_none()
+    }
+
+    /// The failed checks
+    pub fn failures(&self) -> impl Iterator<Item = &Check> {
+        self.checks
+            .iter()
+            .filter(|check| check.status == CheckStatus::Fail)
+    }
+
+    fn push(&mut self, name: impl Into<String>, status: CheckStatus, detail: impl Into<String>) {
+        self.checks.push(Check {
+            name: name.into(),
+            status,
+            detail: detail.into(),
+        });
+    }
+}
+
+impl fmt::Display for Preflight {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for check in &self.checks {
+            writeln!(f, "{}", check)?;
+        }
+        Ok(())
+    }
+}
+
+/// Check the privileges, the kernel and the memory limits
+pub(crate) fn check_system(
+    preflight: &mut Preflight,
+    tracepoints: &[Tracepoint<'static>],
+    ringbuffer_sizes: &BTreeMap<String, u32>,
+) {
+    check_privileges(preflight);
+    let kernel = kernel_version();
+    check_kernel(preflight, kernel);
+    check_btf(preflight);
+    check_memlock(preflight, kernel, tracepoints, ringbuffer_sizes);
+}
+
+/// Check the binary and the process of a target
+pub(crate) fn check_target(
+    preflight: &mut Preflight,
+    target: &Target,
+    tracepoints: &[Tracepoint<'static>],
+) {
+    let process = format!("process {}", target.label).trim_end().to_string();
+    let binary = format!("binary {}", target.label).trim_end().to_string();
+    let (pid, path) = match target.pid_source {
+        // The binary is found through the process
+        PidSource::Auto | PidSource::Container(_) | PidSource::Cgroup(_) => {
+            match find_bitcoind(&target.pid_source, &target.path) {
+                Ok(found) => (found.pid, found.path),
+                Err(e) => {
+                    if !target.path.as_os_str().is_empty() {
+                        check_binary(preflight, &binary, target, &target.path, tracepoints);
+                    }
+                    preflight.push(process, CheckStatus::Fail, e.to_string());
+                    return;
+                }
+            }
+        }
+        ref source => {
+            check_binary(preflight, &binary, target, &target.path, tracepoints);
+            match bitcoind_pid(source, &target.path) {
+                Ok(pid) => (pid, target.path.clone()),
+                Err(e) => {
+                    preflight.push(process, CheckStatus::Fail, e.to_string());
+                    return;
+                }
+            }
+        }
+    };
+    if path != target.path {
+        check_binary(preflight, &binary, target, &path, tracepoints);
+    }
+
+    if pid == DEFAULT_PID {
+        preflight.push(
+            process,
+            CheckStatus::Pass,
+            "no PID given, all processes executing the binary are traced",
+        );
+    } else if !process_alive(pid) {
+        preflight.push(
+            process,
+            CheckStatus::Fail,
+            format!("no process with PID={} is running", pid),
+        );
+    } else if !executes(pid, &path) {
+        preflight.push(
+            process,
+            CheckStatus::Fail,
+            format!(
+                "PID={} is running '{}', not '{}'",
+                pid,
+                fs::read_link(format!("/proc/{}/exe", pid))
+                    .unwrap_or_default()
+                    .display(),
+                path.display()
+            ),
+        );
+    } else {
+        preflight.push(
+            process,
+            CheckStatus::Pass,
+            format!("PID={} is running '{}'", pid, path.display()),
+        );
+    }
+}
+
+/// Does the process execute the binary at `path`?
+fn executes(pid: i32, path: &Path) -> bool {
+    let (Ok(binary), Ok(exe)) = (
+        fs::metadata(path),
+        fs::metadata(format!("/proc/{}/exe", pid)),
+    ) else {
+        return false;
+    };
+    binary.dev() == exe.dev() && binary.ino() == exe.ino()
+}
+
+fn check_privileges(preflight: &mut Preflight) {
+    let name = "privileges";
+    if unsafe { libc::geteuid() } == 0 {
+        preflight.push(name, CheckStatus::Pass, "running as root");
+        return;
+    }
+    let Some(caps) = effective_capabilities() else {
+        preflight.push(
+            name,
+            CheckStatus::Fail,
+            "not root and could not read the capabilities from /proc/self/status",
+        );
+        return;
+    };
+    let has = |cap: u32| caps & (1 << cap) != 0;
+    if has(CAP_SYS_ADMIN) || (has(CAP_BPF) && has(CAP_PERFMON)) {
+        preflight.push(
+            name,
+            CheckStatus::Pass,
+            "running with CAP_BPF and CAP_PERFMON",
+        );
+    } else {
+        preflight.push(
+            name,
+            CheckStatus::Fail,
+            "needs root, or CAP_BPF and CAP_PERFMON (Linux 5.8+), or CAP_SYS_ADMIN",
+        );
+    }
+}
+
+/// The `CapEff` bit set of this process
+fn effective_capabilities() -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let caps = status
+        .lines()
+        .find_map(|line| line.strip_prefix("CapEff:"))?;
+    u64::from_str_radix(caps.trim(), 16).ok()
+}
+
+/// The major and minor version of the running kernel
+fn kernel_version() -> Option<(u32, u32)> {
+    let mut uts = MaybeUninit::<libc::utsname>::uninit();
+    if unsafe { libc::uname(uts.as_mut_ptr()) } != 0 {
+        return None;
+    }
+    let uts = unsafe { uts.assume_init() };
+    let release = unsafe { CStr::from_ptr(uts.release.as_ptr()) }.to_string_lossy();
+    let mut parts = release.split(|c: char| !c.is_ascii_digit());
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next()?.parse().ok()?;
+    Some((major, minor))
+}
+
+fn check_kernel(preflight: &mut Preflight, kernel: Option<(u32, u32)>) {
+    let name = "kernel";
+    match kernel {
+        None => preflight.push(name, CheckStatus::Warn, "could not read the kernel version"),
+        Some(version) if version < MIN_KERNEL_RINGBUF => preflight.push(
+            name,
+            CheckStatus::Fail,
+            format!(
+                "Linux {}.{} has no BPF ring buffers, they need {}.{}",
+                version.0, version.1, MIN_KERNEL_RINGBUF.0, MIN_KERNEL_RINGBUF.1
+            ),
+        ),
+        Some(version) if version < MIN_KERNEL_ATOMICS => preflight.push(
+            name,
+            CheckStatus::Fail,
+            format!(
+                "Linux {}.{} lacks the BPF atomics for the event sequence numbers, they need {}.{}",
+                version.0, version.1, MIN_KERNEL_ATOMICS.0, MIN_KERNEL_ATOMICS.1
+            ),
+        ),
+        Some(version) => preflight.push(
+            name,
+            CheckStatus::Pass,
+            format!("Linux {}.{}", version.0, version.1),
+        ),
+    }
+}
+
+fn check_btf(preflight: &mut Preflight) {
+    let name = "BTF";
+    if Path::new("/sys/kernel/btf/vmlinux").exists() {
+        preflight.push(
+            name,
+            CheckStatus::Pass,
+            "/sys/kernel/btf/vmlinux is available",
+        );
+    } else {
+        preflight.push(
+            name,
+            CheckStatus::Warn,
+            "/sys/kernel/btf/vmlinux is missing, the kernel was built without CONFIG_DEBUG_INFO_BTF",
+        );
+    }
+}
+
+/// The sizes of the ring buffers of the tracepoints in bytes, as the kernel
+/// allocates them
+fn ringbuffer_bytes(
+    tracepoints: &[Tracepoint<'static>],
+    ringbuffer_sizes: &BTreeMap<String, u32>,
+) -> Result<u64, RuntimeError> {
+    let mut storage = MaybeUninit::<OpenObject>::uninit();
+    let open_skel = tracing::TracingSkelBuilder::default().open(&mut storage)?;
+    let mut names: Vec<&str> = tracepoints
+        .iter()
+        .flat_map(|tracepoint| tracepoint.ringbuffers.iter().copied())
+        .collect();
+    names.sort();
+    names.dedup();
+
+    let mut total = 0;
+    for name in names {
+        let size = match ringbuffer_sizes.get(name) {
+            Some(size) => *size,
+            None => open_skel
+                .open_object()
+                .maps()
+                .find(|map| map.name() == name)
+                .ok_or_else(|| RuntimeError::NoSuchBPFMap(name.to_string()))?
+                .max_entries(),
+        };
+        // Rounded up to a power of two number of pages
+        total += (size as u64).max(PAGE_SIZE).next_power_of_two();
+    }
+    Ok(total)
+}
+
+fn check_memlock(
+    preflight: &mut Preflight,
+    kernel: Option<(u32, u32)>,
+    tracepoints: &[Tracepoint<'static>],
+    ringbuffer_sizes: &BTreeMap<String, u32>,
+) {
+    let name = "memlock";
+    let bytes = match ringbuffer_bytes(tracepoints, ringbuffer_sizes) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            preflight.push(
+                name,
+                CheckStatus::Fail,
+                format!("could not open the BPF object: {}", e),
+            );
+            return;
+        }
+    };
+    let mib = bytes >> 20;
+    if kernel.is_some_and(|version| version >= MIN_KERNEL_MEMCG) {
+        preflight.push(
+            name,
+            CheckStatus::Pass,
+            format!("the ring buffers take {} MiB, charged to the cgroup", mib),
+        );
+        return;
+    }
+
+    let mut limit = MaybeUninit::<libc::rlimit>::uninit();
+    if unsafe { libc::getrlimit(libc::RLIMIT_MEMLOCK, limit.as_mut_ptr()) } != 0 {
+        preflight.push(name, CheckStatus::Warn, "could not read RLIMIT_MEMLOCK");
+        return;
+    }
+    let limit = unsafe { limit.assume_init() };
+    let root = unsafe { libc::geteuid() } == 0;
+    if limit.rlim_cur == libc::RLIM_INFINITY || limit.rlim_cur as u64 >= bytes {
+        preflight.push(
+            name,
+            CheckStatus::Pass,
+            format!("the ring buffers take {} MiB", mib),
+        );
+    } else if root {
+        // libbpf raises the limit when allowed to
+        preflight.push(
+            name,
+            CheckStatus::Pass,
+            format!(
+                "the ring buffers take {} MiB, more than RLIMIT_MEMLOCK of {} MiB, root can raise it",
+                mib,
+                limit.rlim_cur as u64 >> 20
+            ),
+        );
+    } else {
+        preflight.push(
+            name,
+            CheckStatus::Fail,
+            format!(
+                "the ring buffers take {} MiB, more than RLIMIT_MEMLOCK of {} MiB. Raise it with ulimit -l or shrink them with --ringbuffer-size",
+                mib,
+                limit.rlim_cur as u64 >> 20
+            ),
+        );
+    }
+}
+
+fn check_binary(
+    preflight: &mut Preflight,
+    name: &str,
+    target: &Target,
+    path: &Path,
+    tracepoints: &[Tracepoint<'static>],
+) {
+    if path.as_os_str().is_empty() {
+        preflight.push(name, CheckStatus::Fail, "no bitcoind binary given");
+        return;
+    }
+    if !path.exists() {
+        preflight.push(
+            name,
+            CheckStatus::Fail,
+            format!("'{}' does not exist", path.display()),
+        );
+        return;
+    }
+    let BitcoindBinary { probes, version } = match BitcoindBinary::read(path) {
+        Ok(binary) => binary,
+        Err(e) => {
+            preflight.push(name, CheckStatus::Fail, e.to_string());
+            return;
+        }
+    };
+    if probes.is_empty() {
+        preflight.push(
+            name,
+            CheckStatus::Fail,
+            format!(
+                "'{}' has no USDT probes, build bitcoind with tracepoints enabled",
+                path.display()
+            ),
+        );
+        return;
+    }
+    let missing: Vec<String> = tracepoints
+        .iter()
+        .filter(|tracepoint| !probes.iter().any(|probe| probe.matches(tracepoint)))
+        .map(|tracepoint| tracepoint.to_string())
+        .collect();
+    if missing.is_empty() {
+        preflight.push(
+            name,
+            CheckStatus::Pass,
+            format!("'{}' has {} USDT probes", path.display(), probes.len()),
+        );
+    } else {
+        preflight.push(
+            name,
+            CheckStatus::Warn,
+            format!(
+                "'{}' lacks the tracepoints {}",
+                path.display(),
+                missing.join(", ")
+            ),
+        );
+    }
+
+    match (version, target.connection_time_unit) {
+        (Some(version), unit) => preflight.push(
+            name,
+            CheckStatus::Pass,
+            format!(
+                "bitcoind {}, block connection times in {}",
+                version,
+                unit.unwrap_or(version.connection_time_unit())
+            ),
+        ),
+        (_, Some(unit)) => preflight.push(
+            name,
+            CheckStatus::Pass,
+            format!("block connection times in {} as given", unit),
+        ),
+        (_, None) => preflight.push(
+            name,
+            CheckStatus::Warn,
+            "unknown bitcoind version, block connection times are assumed to be in ns",
+        ),
+    }
+}
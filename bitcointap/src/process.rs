@@ -0,0 +1,436 @@
+use crate::RuntimeError;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+/// Is the process with the given pid running? Zombies count as exited.
+pub(crate) fn process_alive(pid: i32) -> bool {
+    let Ok(stat) = fs::read_to_string(format!("/proc/{}/stat", pid)) else {
+        return false;
+    };
+    // The process name in the second field can contain spaces and parens,
+    // the state follows after the last ')'.
+    let state = stat
+        .rsplit_once(')')
+        .and_then(|(_, rest)| rest.trim_start().chars().next());
+    !matches!(state, None | Some('Z') | Some('X'))
+}
+
+/// The pids of all running processes executing the binary at `path`
+pub(crate) fn pids_executing(path: &Path) -> Vec<i32> {
+    let Ok(binary) = fs::canonicalize(path) else {
+        return vec![];
+    };
+    let Ok(entries) = fs::read_dir("/proc") else {
+        return vec![];
+    };
+
+    let mut pids: Vec<i32> = entries
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse().ok())
+        .filter(|pid| fs::read_link(format!("/proc/{}/exe", pid)).is_ok_and(|exe| exe == binary))
+        .collect();
+    pids.sort();
+    pids
+}
+
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+/// A running bitcoind process, see [`find_bitcoind_processes`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BitcoindProcess {
+    pub pid: i32,
+    /// The binary, as a path we can open. For a bitcoind in a container,
+    /// this is below `/proc/<pid>/root`.
+    pub path: PathBuf,
+    /// The chain: main, test, testnet4, signet or regtest
+    pub chain: String,
+    /// The `-datadir`, or the default one in the process' home directory
+    pub datadir: Option<PathBuf>,
+    /// Runs in another mount namespace, e.g. in a container
+    pub containerized: bool,
+}
+
+impl BitcoindProcess {
+    /// Does the process execute the binary at `path`?
+    pub fn executes(&self, path: &Path) -> bool {
+        let (Ok(binary), Ok(exe)) = (fs::metadata(path), fs::metadata(&self.path)) else {
+            return false;
+        };
+        binary.dev() == exe.dev() && binary.ino() == exe.ino()
+    }
+}
+
+impl fmt::Display for BitcoindProcess {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "PID={} chain={} '{}'",
+            self.pid,
+            self.chain,
+            self.path.display()
+        )?;
+        if let Some(datadir) = &self.datadir {
+            write!(f, " datadir='{}'", datadir.display())?;
+        }
+        if self.containerized {
+            write!(f, " (container)")?;
+        }
+        Ok(())
+    }
+}
+
+/// Find the running bitcoind processes by the binary they execute. Unlike
+/// matching command lines, this skips bitcoin-qt, bitcoin-cli and wrapper
+/// scripts. Processes we lack the permissions to inspect are skipped.
+pub fn find_bitcoind_processes() -> Vec<BitcoindProcess> {
+    let Ok(entries) = fs::read_dir("/proc") else {
+        return vec![];
+    };
+    let mut processes: Vec<BitcoindProcess> = entries
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse().ok())
+        .filter_map(bitcoind_process)
+        .collect();
+    processes.sort_by_key(|process| process.pid);
+    processes
+}
+
+/// The bitcoind process for [`crate::PidSource::Auto`]: the only one
+/// executing the binary at `path`, or the only one at all if `path` is empty
+pub(crate) fn auto_bitcoind(path: &Path) -> Result<BitcoindProcess, RuntimeError> {
+    let mut processes = find_bitcoind_processes();
+    if !path.as_os_str().is_empty() {
+        processes.retain(|process| process.executes(path));
+    }
+    single_bitcoind(processes)
+}
+
+/// The bitcoind in the container of the process with the (host) `pid`: the
+/// one sharing its PID namespace
+pub(crate) fn container_bitcoind(pid: i32) -> Result<BitcoindProcess, RuntimeError> {
+    let namespace = fs::read_link(format!("/proc/{}/ns/pid", pid))?;
+    let mut processes = find_bitcoind_processes();
+    processes.retain(|process| {
+        fs::read_link(format!("/proc/{}/ns/pid", process.pid)).is_ok_and(|ns| ns == namespace)
+    });
+    single_bitcoind(processes)
+}
+
+/// The bitcoind in the cgroup, e.g. the one of a container. Both the path
+/// below `/sys/fs/cgroup` and the one in `/proc/<pid>/cgroup` work.
+pub(crate) fn cgroup_bitcoind(cgroup: &Path) -> Result<BitcoindProcess, RuntimeError> {
+    let cgroup = match cgroup.starts_with(CGROUP_ROOT) {
+        true => cgroup.to_owned(),
+        false => Path::new(CGROUP_ROOT).join(cgroup.strip_prefix("/").unwrap_or(cgroup)),
+    };
+    let mut pids = vec![];
+    cgroup_pids(&cgroup, &mut pids)?;
+    let mut processes = find_bitcoind_processes();
+    processes.retain(|process| pids.contains(&process.pid));
+    single_bitcoind(processes)
+}
+
+/// The cgroup v2 of the process as listed in `/proc/<pid>/cgroup`, e.g.
+/// `/system.slice/docker-<id>.scope`. Unlike the pid, it stays the same
+/// when a container restarts.
+pub(crate) fn process_cgroup(pid: i32) -> Result<PathBuf, RuntimeError> {
+    let cgroups = fs::read_to_string(format!("/proc/{}/cgroup", pid))?;
+    cgroups
+        .lines()
+        .find_map(|line| line.strip_prefix("0::"))
+        .filter(|cgroup| *cgroup != "/")
+        .map(PathBuf::from)
+        .ok_or_else(|| {
+            RuntimeError::Io(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("the process {} is not in a cgroup v2 of its own", pid),
+            ))
+        })
+}
+
+/// The pids in the cgroup and its child cgroups
+fn cgroup_pids(cgroup: &Path, pids: &mut Vec<i32>) -> Result<(), RuntimeError> {
+    let procs = fs::read_to_string(cgroup.join("cgroup.procs"))?;
+    pids.extend(
+        procs
+            .lines()
+            .filter_map(|pid| pid.trim().parse::<i32>().ok()),
+    );
+    for entry in fs::read_dir(cgroup)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            cgroup_pids(&entry.path(), pids)?;
+        }
+    }
+    Ok(())
+}
+
+fn single_bitcoind(mut processes: Vec<BitcoindProcess>) -> Result<BitcoindProcess, RuntimeError> {
+    match processes.len() {
+        0 => Err(RuntimeError::MissingBitcoind),
+        1 => Ok(processes.remove(0)),
+        _ => Err(RuntimeError::AmbiguousBitcoind(processes)),
+    }
+}
+
+fn bitcoind_process(pid: i32) -> Option<BitcoindProcess> {
+    let exe = fs::read_link(format!("/proc/{}/exe", pid)).ok()?;
+    if exe.file_name()? != "bitcoind" {
+        return None;
+    }
+
+    // The exe link shows the path in the process' mount namespace
+    let root = PathBuf::from(format!("/proc/{}/root", pid));
+    let containerized = fs::read_link(format!("/proc/{}/ns/mnt", pid)).ok()
+        != fs::read_link("/proc/self/ns/mnt").ok();
+    let in_root = |path: &Path| match containerized {
+        true => root.join(path.strip_prefix("/").unwrap_or(path)),
+        false => path.to_owned(),
+    };
+
+    let args = read_nul_separated(&format!("/proc/{}/cmdline", pid));
+    let options: Vec<(String, Option<String>)> = args
+        .iter()
+        .skip(1)
+        .filter_map(|arg| parse_option(arg))
+        .collect();
+    let environ = read_nul_separated(&format!("/proc/{}/environ", pid));
+    let datadir = datadir(&options, &environ);
+    let chain = chain(&options, || {
+        fs::read_to_string(in_root(&conf_path(&options, datadir.as_deref()))).ok()
+    });
+
+    Some(BitcoindProcess {
+        pid,
+        path: in_root(&exe),
+        chain,
+        datadir,
+        containerized,
+    })
+}
+
+/// The `-datadir`, or `.bitcoin` in the `HOME` of the process' environment
+fn datadir(options: &[(String, Option<String>)], environ: &[String]) -> Option<PathBuf> {
+    option_value(options, "datadir")
+        .map(PathBuf::from)
+        .or_else(|| {
+            let home = environ.iter().find_map(|var| var.strip_prefix("HOME="))?;
+            Some(Path::new(home).join(".bitcoin"))
+        })
+}
+
+/// The `-conf` file, relative to the datadir unless it's absolute
+fn conf_path(options: &[(String, Option<String>)], datadir: Option<&Path>) -> PathBuf {
+    let conf = option_value(options, "conf").unwrap_or("bitcoin.conf");
+    match datadir {
+        Some(datadir) => datadir.join(conf),
+        None => PathBuf::from(conf),
+    }
+}
+
+/// The chain selected on the command line, which takes precedence over the
+/// config file read by `read_conf`. Defaults to main.
+fn chain(
+    options: &[(String, Option<String>)],
+    read_conf: impl FnOnce() -> Option<String>,
+) -> String {
+    chain_from_options(options)
+        .or_else(|| chain_from_options(&parse_conf(&read_conf()?)))
+        .unwrap_or_else(|| "main".to_string())
+}
+
+fn read_nul_separated(path: &str) -> Vec<String> {
+    fs::read(path)
+        .map(|bytes| {
+            bytes
+                .split(|b| *b == 0)
+                .filter(|arg| !arg.is_empty())
+                .map(|arg| String::from_utf8_lossy(arg).into_owned())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Split a `-name=value` or `--name` command line option
+fn parse_option(arg: &str) -> Option<(String, Option<String>)> {
+    let option = arg.strip_prefix("--").or_else(|| arg.strip_prefix('-'))?;
+    Some(match option.split_once('=') {
+        Some((name, value)) => (name.to_string(), Some(value.to_string())),
+        None => (option.to_string(), None),
+    })
+}
+
+/// The options of the top section of a bitcoin.conf
+fn parse_conf(conf: &str) -> Vec<(String, Option<String>)> {
+    conf.lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .take_while(|line| !line.starts_with('['))
+        .filter_map(|line| line.split_once('='))
+        .map(|(name, value)| (name.trim().to_string(), Some(value.trim().to_string())))
+        .collect()
+}
+
+/// The value of the last occurrence of an option
+fn option_value<'a>(options: &'a [(String, Option<String>)], name: &str) -> Option<&'a str> {
+    options
+        .iter()
+        .rev()
+        .find(|(option, _)| option == name)
+        .and_then(|(_, value)| value.as_deref())
+}
+
+/// The chain selected with `-chain`, `-testnet`, `-testnet4`, `-signet` or
+/// `-regtest`
+fn chain_from_options(options: &[(String, Option<String>)]) -> Option<String> {
+    options.iter().rev().find_map(|(name, value)| {
+        let enabled = value.as_deref().is_none_or(|value| value != "0");
+        match name.as_str() {
+            "chain" => value.clone(),
+            "testnet" if enabled => Some("test".to_string()),
+            "testnet4" | "signet" | "regtest" if enabled => Some(name.clone()),
+            _ => None,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(args: &[&str]) -> Vec<(String, Option<String>)> {
+        args.iter().filter_map(|arg| parse_option(arg)).collect()
+    }
+
+    fn chain_of(args: &[&str]) -> Option<String> {
+        chain_from_options(&options(args))
+    }
+
+    #[test]
+    fn parse_options() {
+        assert_eq!(
+            options(&[
+                "-datadir=/data",
+                "--regtest",
+                "-rpcauth=user:a=b",
+                "bitcoind"
+            ]),
+            vec![
+                ("datadir".to_string(), Some("/data".to_string())),
+                ("regtest".to_string(), None),
+                ("rpcauth".to_string(), Some("user:a=b".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn last_option_wins() {
+        let options = options(&["-datadir=/a", "-datadir=/b", "-conf"]);
+        assert_eq!(option_value(&options, "datadir"), Some("/b"));
+        assert_eq!(option_value(&options, "conf"), None);
+        assert_eq!(option_value(&options, "rpcport"), None);
+    }
+
+    #[test]
+    fn chains() {
+        assert_eq!(chain_of(&[]), None);
+        assert_eq!(chain_of(&["-testnet"]), Some("test".to_string()));
+        assert_eq!(chain_of(&["-testnet=1"]), Some("test".to_string()));
+        assert_eq!(chain_of(&["-testnet4"]), Some("testnet4".to_string()));
+        assert_eq!(chain_of(&["--signet"]), Some("signet".to_string()));
+        assert_eq!(chain_of(&["-regtest"]), Some("regtest".to_string()));
+        assert_eq!(chain_of(&["-chain=signet"]), Some("signet".to_string()));
+        assert_eq!(chain_of(&["-testnet=0", "-regtest=0"]), None);
+    }
+
+    #[test]
+    fn chain_precedence() {
+        // The last enabled chain option wins, a disabled one is skipped
+        assert_eq!(
+            chain_of(&["-testnet", "-regtest"]),
+            Some("regtest".to_string())
+        );
+        assert_eq!(
+            chain_of(&["-regtest", "-chain=test"]),
+            Some("test".to_string())
+        );
+        assert_eq!(
+            chain_of(&["-chain=signet", "-testnet=0"]),
+            Some("signet".to_string())
+        );
+
+        // The command line takes precedence over the config file
+        let conf = || Some("regtest=1\n".to_string());
+        assert_eq!(chain(&options(&["-signet"]), conf), "signet");
+        assert_eq!(chain(&options(&["-testnet=0"]), conf), "regtest");
+        assert_eq!(chain(&options(&[]), || None), "main");
+        assert_eq!(chain(&options(&[]), || Some(String::new())), "main");
+    }
+
+    #[test]
+    fn conf_sections() {
+        let conf = "\
+# A comment, testnet=1
+server=1
+signet = 1 # trailing comment
+chain=regtest
+
+[main]
+rpcport=8332
+
+[test]
+testnet4=1
+";
+        assert_eq!(
+            parse_conf(conf),
+            vec![
+                ("server".to_string(), Some("1".to_string())),
+                ("signet".to_string(), Some("1".to_string())),
+                ("chain".to_string(), Some("regtest".to_string())),
+            ]
+        );
+        assert_eq!(
+            chain_from_options(&parse_conf(conf)),
+            Some("regtest".to_string())
+        );
+        assert_eq!(chain_from_options(&parse_conf("[test]\ntestnet=1\n")), None);
+    }
+
+    #[test]
+    fn datadirs() {
+        let environ = [
+            "PATH=/usr/bin".to_string(),
+            "HOME=/home/bitcoin".to_string(),
+        ];
+        assert_eq!(
+            datadir(&options(&["-datadir=/data"]), &environ),
+            Some(PathBuf::from("/data"))
+        );
+        assert_eq!(
+            datadir(&options(&[]), &environ),
+            Some(PathBuf::from("/home/bitcoin/.bitcoin"))
+        );
+        assert_eq!(datadir(&options(&[]), &environ[..1]), None);
+    }
+
+    #[test]
+    fn conf_paths() {
+        let datadir = Some(Path::new("/data"));
+        assert_eq!(
+            conf_path(&options(&[]), datadir),
+            PathBuf::from("/data/bitcoin.conf")
+        );
+        assert_eq!(
+            conf_path(&options(&["-conf=node.conf"]), datadir),
+            PathBuf::from("/data/node.conf")
+        );
+        assert_eq!(
+            conf_path(&options(&["-conf=/etc/bitcoin/bitcoin.conf"]), datadir),
+            PathBuf::from("/etc/bitcoin/bitcoin.conf")
+        );
+        assert_eq!(
+            conf_path(&options(&[]), None),
+            PathBuf::from("bitcoin.conf")
+        );
+    }
+}
@@ -0,0 +1,459 @@
+use crate::{Event, TapMsg};
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::mpsc::{RecvError, RecvTimeoutError, SendError, TryRecvError};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// The number of events the queue holds by default
+pub const DEFAULT_QUEUE_CAPACITY: usize = 100_000;
+
+/// What happens to new events when the event queue is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Backpressure {
+    /// Stop consuming the ring buffers until there is room in the queue
+    /// again. Once the ring buffers are full, the kernel drops the events.
+    #[default]
+    Block,
+    /// Drop the new event
+    DropNewest,
+    /// Drop the oldest queued event to make room for the new one
+    DropOldest,
+}
+
+/// A snapshot of the event queue
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QueueStats {
+    /// The number of events waiting to be read
+    pub queued: usize,
+    pub capacity: usize,
+    /// The number of dropped events by event type, e.g. `mempool`
+    pub dropped: BTreeMap<&'static str, u64>,
+}
+
+impl QueueStats {
+    /// The number of dropped events of all types
+    pub fn total_dropped(&self) -> u64 {
+        self.dropped.values().sum()
+    }
+}
+
+/// The event type a message is accounted as. These are the field names of
+/// the `event` oneof in `EventMsg`.
+fn event_type(msg: &TapMsg) -> &'static str {
+    match msg {
+        TapMsg::Event(event_msg) => match event_msg.event {
+            Some(Event::Msg(_)) => "msg",
+            Some(Event::Conn(_)) => "conn",
+            Some(Event::Addrman(_)) => "addrman",
+            Some(Event::Mempool(_)) => "mempool",
+            Some(Event::Validation(_)) => "validation",
+            Some(Event::Utxocache(_)) => "utxocache",
+            Some(Event::CoinSelection(_)) => "coin_selection",
+            None => "unknown",
+        },
+        _ => "control",
+    }
+}
+
+struct State {
+    messages: VecDeque<TapMsg>,
+    /// The number of [`TapMsg::Event`]s in `messages`
+    events: usize,
+    dropped: BTreeMap<&'static str, u64>,
+    senders: usize,
+    /// The receiver is gone
+    closed: bool,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    capacity: usize,
+    policy: Backpressure,
+    not_empty: Condvar,
+    not_full: Condvar,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        // A panic while holding the lock leaves the queue itself intact
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn stats(&self) -> QueueStats {
+        let state = self.lock();
+        QueueStats {
+            queued: state.events,
+            capacity: self.capacity,
+            dropped: state.dropped.clone(),
+        }
+    }
+}
+
+/// Create a bounded event queue. Only [`TapMsg::Event`]s count towards the
+/// capacity and are subject to the policy, the other messages are always
+/// queued. With `blocking` false, [`Backpressure::Block`] lets the queue
+/// grow instead of blocking the sender. This is for senders that only
+/// produce as many events as there is room for.
+pub(crate) fn queue(
+    capacity: usize,
+    policy: Backpressure,
+    blocking: bool,
+) -> (EventSender, EventReceiver) {
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            messages: VecDeque::new(),
+            events: 0,
+            dropped: BTreeMap::new(),
+            senders: 1,
+            closed: false,
+        }),
+        capacity: capacity.max(1),
+        policy,
+        not_empty: Condvar::new(),
+        not_full: Condvar::new(),
+    });
+    let sender = EventSender {
+        shared: shared.clone(),
+        blocking,
+    };
+    (sender, EventReceiver { shared })
+}
+
+/// The sending half of the event queue
+pub(crate) struct EventSender {
+    shared: Arc<Shared>,
+    blocking: bool,
+}
+
+impl EventSender {
+    /// Queue a message. Fails when the receiver is gone. A dropped event
+    /// is not an error, it's counted in the [`QueueStats`].
+    pub(crate) fn send(&self, msg: TapMsg) -> Result<(), SendError<TapMsg>> {
+        let shared = &self.shared;
+        let mut state = shared.lock();
+        if state.closed {
+            return Err(SendError(msg));
+        }
+
+        if let TapMsg::Event(_) = msg {
+            if state.events >= shared.capacity {
+                match shared.policy {
+                    Backpressure::Block if self.blocking => {
+                        while state.events >= shared.capacity && !state.closed {
+                            state = shared
+                                .not_full
+                                .wait(state)
+                                .unwrap_or_else(|e| e.into_inner());
+                        }
+                        if state.closed {
+                            return Err(SendError(msg));
+                        }
+                    }
+                    Backpressure::Block => (),
+                    Backpressure::DropNewest => {
+                        *state.dropped.entry(event_type(&msg)).or_default() += 1;
+                        return Ok(());
+                    }
+                    Backpressure::DropOldest => {
+                        let oldest = state
+                            .messages
+                            .iter()
+                            .position(|m| matches!(m, TapMsg::Event(_)));
+                        if let Some(oldest) = oldest.and_then(|i| state.messages.remove(i)) {
+                            state.events -= 1;
+                            *state.dropped.entry(event_type(&oldest)).or_default() += 1;
+                        }
+                    }
+                }
+            }
+            state.events += 1;
+        }
+
+        state.messages.push_back(msg);
+        shared.not_empty.notify_one();
+        Ok(())
+    }
+
+    /// A snapshot of the queue
+    pub(crate) fn stats(&self) -> QueueStats {
+        self.shared.stats()
+    }
+}
+
+impl Clone for EventSender {
+    fn clone(&self) -> Self {
+        self.shared.lock().senders += 1;
+        EventSender {
+            shared: self.shared.clone(),
+            blocking: self.blocking,
+        }
+    }
+}
+
+impl Drop for EventSender {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.senders -= 1;
+        if state.senders == 0 {
+            self.shared.not_empty.notify_all();
+        }
+    }
+}
+
+/// The receiving half of the event queue. Works like a
+/// [`std::sync::mpsc::Receiver`]: it disconnects once the tap stopped and
+/// all messages were read.
+pub struct EventReceiver {
+    shared: Arc<Shared>,
+}
+
+impl EventReceiver {
+    /// Wait for the next message
+    pub fn recv(&self) -> Result<TapMsg, RecvError> {
+        let mut state = self.shared.lock();
+        loop {
+            if let Some(msg) = self.pop(&mut state) {
+                return Ok(msg);
+            }
+            if state.senders == 0 {
+                return Err(RecvError);
+            }
+            state = self
+                .shared
+                .not_empty
+                .wait(state)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    /// Wait up to `timeout` for the next message
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<TapMsg, RecvTimeoutError> {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.shared.lock();
+        loop {
+            if let Some(msg) = self.pop(&mut state) {
+                return Ok(msg);
+            }
+            if state.senders == 0 {
+                return Err(RecvTimeoutError::Disconnected);
+            }
+            let Some(left) = deadline.checked_duration_since(Instant::now()) else {
+                return Err(RecvTimeoutError::Timeout);
+            };
+            state = self
+                .shared
+                .not_empty
+                .wait_timeout(state, left)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+    }
+
+    /// The next message, if there is one
+    pub fn try_recv(&self) -> Result<TapMsg, TryRecvError> {
+        let mut state = self.shared.lock();
+        match self.pop(&mut state) {
+            Some(msg) => Ok(msg),
+            None if state.senders == 0 => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    /// Iterate over the messages until the tap stopped
+    pub fn iter(&self) -> impl Iterator<Item = TapMsg> + '_ {
+        std::iter::from_fn(|| self.recv().ok())
+    }
+
+    /// A snapshot of the queue, including the dropped events
+    pub fn stats(&self) -> QueueStats {
+        self.shared.stats()
+    }
+
+    /// The number of events that fit into the queue with
+    /// [`Backpressure::Block`]. `None` with the other policies, which make
+    /// room by dropping events.
+    #[cfg(feature = "async")]
+    pub(crate) fn room(&self) -> Option<usize> {
+        let room = self
+            .shared
+            .capacity
+            .saturating_sub(self.shared.lock().events);
+        (self.shared.policy == Backpressure::Block).then_some(room)
+    }
+
+    /// Stop accepting messages and wake up blocked senders
+    pub(crate) fn close(&self) {
+        self.shared.lock().closed = true;
+        self.shared.not_full.notify_all();
+    }
+
+    fn pop(&self, state: &mut State) -> Option<TapMsg> {
+        let msg = state.messages.pop_front()?;
+        if let TapMsg::Event(_) = msg {
+            state.events -= 1;
+            self.shared.not_full.notify_one();
+        }
+        Some(msg)
+    }
+}
+
+impl Drop for EventReceiver {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ConnectionMsg, EventMsg, MempoolMsg, ValidationMsg};
+    use std::thread;
+
+    fn event(event: Event, seq: u64) -> TapMsg {
+        TapMsg::Event(EventMsg::new(event).with_kernel_sequence(seq))
+    }
+
+    fn mempool(seq: u64) -> TapMsg {
+        event(Event::Mempool(MempoolMsg { event: None }), seq)
+    }
+
+    fn conn(seq: u64) -> TapMsg {
+        event(Event::Conn(ConnectionMsg { event: None }), seq)
+    }
+
+    fn validation(seq: u64) -> TapMsg {
+        event(Event::Validation(ValidationMsg { event: None }), seq)
+    }
+
+    /// The kernel sequence numbers of the queued events, `None` for the
+    /// other messages
+    fn drain(rx: &EventReceiver) -> Vec<Option<u64>> {
+        std::iter::from_fn(|| rx.try_recv().ok())
+            .map(|msg| match msg {
+                TapMsg::Event(event_msg) => event_msg.kernel_sequence,
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn drop_newest() {
+        let (tx, rx) = queue(2, Backpressure::DropNewest, true);
+        tx.send(conn(1)).unwrap();
+        tx.send(mempool(2)).unwrap();
+        tx.send(mempool(3)).unwrap();
+        tx.send(validation(4)).unwrap();
+
+        let stats = rx.stats();
+        assert_eq!(stats.queued, 2);
+        assert_eq!(stats.capacity, 2);
+        assert_eq!(
+            stats.dropped,
+            BTreeMap::from([("mempool", 1), ("validation", 1)])
+        );
+        assert_eq!(stats.total_dropped(), 2);
+        assert_eq!(drain(&rx), vec![Some(1), Some(2)]);
+    }
+
+    #[test]
+    fn drop_oldest() {
+        let (tx, rx) = queue(2, Backpressure::DropOldest, true);
+        tx.send(conn(1)).unwrap();
+        tx.send(mempool(2)).unwrap();
+        tx.send(mempool(3)).unwrap();
+        tx.send(validation(4)).unwrap();
+
+        let stats = rx.stats();
+        assert_eq!(stats.queued, 2);
+        assert_eq!(stats.dropped, BTreeMap::from([("conn", 1), ("mempool", 1)]));
+        assert_eq!(drain(&rx), vec![Some(3), Some(4)]);
+    }
+
+    #[test]
+    fn only_events_count_towards_the_capacity() {
+        let (tx, rx) = queue(1, Backpressure::DropOldest, true);
+        tx.send(TapMsg::Detached {
+            label: Some("signet".to_string()),
+        })
+        .unwrap();
+        tx.send(mempool(1)).unwrap();
+        tx.send(TapMsg::Stats(QueueStats::default())).unwrap();
+        tx.send(mempool(2)).unwrap();
+
+        // The oldest event is dropped, the control messages stay queued
+        assert_eq!(rx.stats().queued, 1);
+        assert_eq!(rx.stats().dropped, BTreeMap::from([("mempool", 1)]));
+        assert_eq!(drain(&rx), vec![None, None, Some(2)]);
+    }
+
+    #[test]
+    fn block_waits_for_room() {
+        let (tx, rx) = queue(1, Backpressure::Block, true);
+        tx.send(mempool(1)).unwrap();
+        let sender = thread::spawn(move || tx.send(mempool(2)));
+
+        thread::sleep(Duration::from_millis(50));
+        assert!(!sender.is_finished());
+        assert_eq!(rx.stats().queued, 1);
+
+        assert_eq!(drain(&rx), vec![Some(1)]);
+        sender.join().unwrap().unwrap();
+        assert_eq!(drain(&rx), vec![Some(2)]);
+        assert_eq!(rx.stats().total_dropped(), 0);
+    }
+
+    #[test]
+    fn block_without_blocking_grows() {
+        let (tx, rx) = queue(1, Backpressure::Block, false);
+        tx.send(mempool(1)).unwrap();
+        tx.send(mempool(2)).unwrap();
+        assert_eq!(rx.stats().queued, 2);
+        assert_eq!(drain(&rx), vec![Some(1), Some(2)]);
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn room_of_block_without_blocking() {
+        let (tx, rx) = queue(2, Backpressure::Block, false);
+        assert_eq!(rx.room(), Some(2));
+        tx.send(mempool(1)).unwrap();
+        tx.send(TapMsg::Stats(QueueStats::default())).unwrap();
+        assert_eq!(rx.room(), Some(1));
+
+        // The sender doesn't block, the room is only used up
+        tx.send(mempool(2)).unwrap();
+        tx.send(mempool(3)).unwrap();
+        assert_eq!(rx.room(), Some(0));
+        assert_eq!(rx.stats().total_dropped(), 0);
+
+        assert_eq!(drain(&rx), vec![Some(1), None, Some(2), Some(3)]);
+        assert_eq!(rx.room(), Some(2));
+
+        let (_, rx) = queue(2, Backpressure::DropOldest, false);
+        assert_eq!(rx.room(), None);
+    }
+
+    #[test]
+    fn closing_wakes_blocked_senders() {
+        let (tx, rx) = queue(1, Backpressure::Block, true);
+        tx.send(mempool(1)).unwrap();
+        let sender = thread::spawn(move || tx.send(mempool(2)).is_err());
+
+        thread::sleep(Duration::from_millis(50));
+        drop(rx);
+        assert!(sender.join().unwrap());
+    }
+
+    #[test]
+    fn disconnect_after_the_last_sender() {
+        let (tx, rx) = queue(1, Backpressure::Block, true);
+        let tx2 = tx.clone();
+        tx.send(mempool(1)).unwrap();
+        drop(tx);
+        assert_eq!(rx.try_recv().err(), None);
+        assert_eq!(rx.try_recv().err(), Some(TryRecvError::Empty));
+        drop(tx2);
+        assert_eq!(rx.try_recv().err(), Some(TryRecvError::Disconnected));
+        assert!(rx.recv().is_err());
+    }
+}
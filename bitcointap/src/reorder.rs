@@ -0,0 +1,162 @@
+use crate::TapMsg;
+use shared::log;
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::time::Duration;
+
+/// An event held back by the [`Reorder`] stage
+struct Held {
+    /// The kernel sequence number
+    seq: u64,
+    /// When the tracepoint fired, in nanoseconds since UNIX epoch
+    timestamp_ns: u64,
+    msg: TapMsg,
+}
+
+impl PartialEq for Held {
+    fn eq(&self, other: &Self) -> bool {
+        self.seq == other.seq
+    }
+}
+
+impl Eq for Held {}
+
+impl PartialOrd for Held {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Held {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.seq.cmp(&other.seq)
+    }
+}
+
+/// Holds the events back for a window and hands them out in the order the
+/// kernel recorded them. The ring buffers are drained one after another, so
+/// without this e.g. a closed connection can be handed out before the last
+/// message of the peer.
+pub(crate) struct Reorder {
+    window_ns: u64,
+    held: BinaryHeap<Reverse<Held>>,
+    /// The sequence number of the last event handed out
+    released: Option<u64>,
+}
+
+impl Reorder {
+    pub(crate) fn new(window: Duration) -> Self {
+        Reorder {
+            window_ns: window.as_nanos() as u64,
+            held: BinaryHeap::new(),
+            released: None,
+        }
+    }
+
+    /// Hold the event back. Hands it back if it arrived after the window
+    /// and can't be put in order anymore.
+    pub(crate) fn push(&mut self, seq: u64, timestamp_ns: u64, msg: TapMsg) -> Option<TapMsg> {
+        if self.released.is_some_and(|released| seq < released) {
+            log::debug!(
+                "Event with sequence number {} arrived too late to reorder",
+                seq
+            );
+            return Some(msg);
+        }
+        self.held.push(Reverse(Held {
+            seq,
+            timestamp_ns,
+            msg,
+        }));
+        None
+    }
+
+    /// Take the next event that is older than the window, or any with `all`
+    pub(crate) fn pop(&mut self, now_ns: u64, all: bool) -> Option<TapMsg> {
+        let Reverse(next) = self.held.peek()?;
+        if !all && next.timestamp_ns.saturating_add(self.window_ns) > now_ns {
+            return None;
+        }
+        let Reverse(next) = self.held.pop()?;
+        self.released = Some(next.seq);
+        Some(next.msg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Event, EventMsg, MempoolMsg};
+
+    const WINDOW: Duration = Duration::from_millis(10);
+    const MS: u64 = 1_000_000;
+
+    fn event(seq: u64) -> TapMsg {
+        TapMsg::Event(
+            EventMsg::new(Event::Mempool(MempoolMsg { event: None })).with_kernel_sequence(seq),
+        )
+    }
+
+    fn seq(msg: Option<TapMsg>) -> Option<u64> {
+        match msg? {
+            TapMsg::Event(event_msg) => event_msg.kernel_sequence,
+            _ => None,
+        }
+    }
+
+    /// The sequence numbers of the events released at `now_ns`
+    fn pop_all(reorder: &mut Reorder, now_ns: u64) -> Vec<u64> {
+        std::iter::from_fn(|| seq(reorder.pop(now_ns, false))).collect()
+    }
+
+    #[test]
+    fn reorders_within_the_window() {
+        let mut reorder = Reorder::new(WINDOW);
+        assert!(reorder.push(3, 3 * MS, event(3)).is_none());
+        assert!(reorder.push(1, MS, event(1)).is_none());
+        assert!(reorder.push(2, 2 * MS, event(2)).is_none());
+
+        assert_eq!(pop_all(&mut reorder, 5 * MS), Vec::<u64>::new());
+        assert_eq!(pop_all(&mut reorder, 20 * MS), vec![1, 2, 3]);
+        assert!(reorder.pop(u64::MAX, true).is_none());
+    }
+
+    #[test]
+    fn releases_after_the_window() {
+        let mut reorder = Reorder::new(WINDOW);
+        reorder.push(1, MS, event(1));
+        reorder.push(2, 5 * MS, event(2));
+
+        assert_eq!(pop_all(&mut reorder, 10 * MS), Vec::<u64>::new());
+        assert_eq!(pop_all(&mut reorder, 11 * MS), vec![1]);
+        assert_eq!(pop_all(&mut reorder, 14 * MS), Vec::<u64>::new());
+        assert_eq!(pop_all(&mut reorder, 15 * MS), vec![2]);
+    }
+
+    #[test]
+    fn releases_everything_with_all() {
+        let mut reorder = Reorder::new(WINDOW);
+        reorder.push(2, 2 * MS, event(2));
+        reorder.push(1, MS, event(1));
+
+        assert_eq!(seq(reorder.pop(0, true)), Some(1));
+        assert_eq!(seq(reorder.pop(0, true)), Some(2));
+        assert!(reorder.pop(0, true).is_none());
+    }
+
+    #[test]
+    fn passes_late_events_through() {
+        let mut reorder = Reorder::new(WINDOW);
+        reorder.push(1, MS, event(1));
+        reorder.push(3, 3 * MS, event(3));
+        assert_eq!(pop_all(&mut reorder, 12 * MS), vec![1]);
+
+        // Arrived after 1 was handed out, but still before 3
+        reorder.push(2, 2 * MS, event(2));
+        assert_eq!(pop_all(&mut reorder, 13 * MS), vec![2, 3]);
+
+        // Older than the last event handed out
+        assert_eq!(seq(reorder.push(0, 0, event(0))), Some(0));
+        assert!(reorder.pop(u64::MAX, true).is_none());
+    }
+}
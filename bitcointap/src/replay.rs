@@ -0,0 +1,561 @@
+use crate::capture::{CaptureReader, RecordLayout, record_fingerprint, record_size};
+use crate::filter::NetMsgFilter;
+use crate::queue::EventSender;
+use crate::sink::{EventSink, TargetBuild};
+use crate::tap::{RINGBUFF_CALLBACK_PUBLISH_ERROR, RingBufferHandler, ringbuffer_handler};
+use crate::{RuntimeError, TapMsg};
+use shared::ctypes::{EventHeader, P2PMessageMetadata};
+use shared::log;
+use std::mem;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+/// How long the replay sleeps at most before it checks if it should stop
+const MAX_SLEEP: Duration = Duration::from_millis(100);
+
+/// How fast a capture is replayed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReplaySpeed {
+    /// As fast as the events are read from the queue
+    #[default]
+    Fast,
+    /// With the time between the events as recorded
+    Original,
+}
+
+/// What the tap needs to know to replay a capture
+pub(crate) struct ReplayConfig {
+    pub(crate) path: PathBuf,
+    pub(crate) speed: ReplaySpeed,
+    /// The ring buffers of the selected tracepoints. `None` replays all.
+    pub(crate) ringbuffers: Option<Vec<&'static str>>,
+    pub(crate) stats_interval: Option<Duration>,
+    pub(crate) net_msg_filter: Option<NetMsgFilter>,
+    pub(crate) net_msg_metadata_only: bool,
+}
+
+/// How the records of a ring buffer in the capture are replayed
+#[derive(Clone, Copy)]
+struct ReplayLayout {
+    handler: RingBufferHandler,
+    /// The size of the fixed part of the records
+    size: usize,
+    /// P2P messages go through the filter
+    net_msg: bool,
+}
+
+/// Hand the records of the capture to the ring buffer handlers until the
+/// capture ends or the tap is stopped
+pub(crate) fn replay_thread(
+    config: ReplayConfig,
+    stop: Arc<AtomicBool>,
+    filters: mpsc::Receiver<NetMsgFilter>,
+    tx: EventSender,
+) -> Result<(), RuntimeError> {
+    let mut reader = CaptureReader::open(&config.path)?;
+    let header = reader.header().clone();
+    log::info!(
+        "Replaying '{}', recorded by bitcointap {}..",
+        config.path.display(),
+        header.bitcointap_version
+    );
+
+    let labels = header
+        .targets
+        .iter()
+        .map(|target| target.label.clone())
+        .collect();
+    let builds = header
+        .targets
+        .iter()
+        .map(|target| TargetBuild {
+            connection_time_unit: target.connection_time_unit,
+            version: target.bitcoind_version.clone(),
+        })
+        .collect();
+    // The events are held back by their original timestamps, which have
+    // long passed, so there is nothing to reorder
+    let sink = EventSink::new(tx.clone(), labels, builds, None)
+        .with_realtime_offset_ns(header.realtime_offset_ns);
+    let layouts: Vec<Option<ReplayLayout>> = header
+        .layouts
+        .iter()
+        .map(|layout| replay_layout(layout, &config, &tx))
+        .collect();
+
+    let mut filter = config.net_msg_filter;
+    let mut started: Option<(Instant, u64)> = None;
+    let mut last_stats = Instant::now();
+    let mut replayed = 0u64;
+
+    while let Some(record) = reader.next_record()? {
+        if stop.load(Ordering::Relaxed) {
+            break;
+        }
+        if let Some(new) = filters.try_iter().last() {
+            log::info!("Updated the P2P message filter: {:?}", new);
+            filter = Some(new);
+        }
+        let Some(layout) = layouts[record.layout as usize] else {
+            continue;
+        };
+        let mut data = record.data;
+        // P2P messages are recorded without the padding of their ring buffer
+        // slot. The payload is read up to the end of the record, a record cut
+        // within the metadata is padded back.
+        if layout.net_msg && data.len() < layout.size {
+            data.resize(layout.size, 0);
+        }
+        if data.len() < layout.size {
+            let _ = tx.send(TapMsg::Error(RuntimeError::Capture(
+                config.path.clone(),
+                format!(
+                    "a record of {} has {} bytes, shorter than {}. Skipping it.",
+                    header.layouts[record.layout as usize].ringbuffer,
+                    data.len(),
+                    layout.size
+                ),
+            )));
+            continue;
+        }
+        if layout.net_msg && !passes(filter.as_ref(), &data) {
+            continue;
+        }
+
+        if config.speed == ReplaySpeed::Original {
+            let (start, first_ns) = *started.get_or_insert((Instant::now(), record.ktime_ns));
+            let due = start + Duration::from_nanos(record.ktime_ns.saturating_sub(first_ns));
+            if !sleep_until(due, &stop) {
+                break;
+            }
+        }
+        if (layout.handler)(&data, &sink) == RINGBUFF_CALLBACK_PUBLISH_ERROR {
+            log::warn!("Could not publish to event queue.");
+            break;
+        }
+        replayed += 1;
+
+        if let Some(interval) = config.stats_interval {
+            if last_stats.elapsed() >= interval {
+                last_stats = Instant::now();
+                let _ = tx.send(TapMsg::Stats(tx.stats()));
+            }
+        }
+    }
+
+    log::info!(
+        "Replayed {} events from '{}'",
+        replayed,
+        config.path.display()
+    );
+    Ok(())
+}
+
+/// `None` if the records of the ring buffer are skipped: they were not
+/// selected, or this version can't decode them
+fn replay_layout(
+    layout: &RecordLayout,
+    config: &ReplayConfig,
+    tx: &EventSender,
+) -> Option<ReplayLayout> {
+    let name = layout.ringbuffer.as_str();
+    if let Some(selected) = &config.ringbuffers {
+        if !selected.contains(&name) {
+            return None;
+        }
+    }
+    let net_msg = name.starts_with("net_msg_");
+    let handler = match net_msg && config.net_msg_metadata_only {
+        true => ringbuffer_handler("net_msg_meta"),
+        false => ringbuffer_handler(name),
+    };
+    let Some(handler) = handler else {
+        log::warn!("The ring buffer {} is not supported. Skipping it.", name);
+        return None;
+    };
+    let size = record_size(name);
+    if layout.size as usize != size {
+        let _ = tx.send(TapMsg::Error(RuntimeError::Capture(
+            config.path.clone(),
+            format!(
+                "the records of {} have {} bytes, this version expects {}. Skipping them.",
+                name, layout.size, size
+            ),
+        )));
+        return None;
+    }
+    if layout.fingerprint != record_fingerprint(name) {
+        let _ = tx.send(TapMsg::Error(RuntimeError::Capture(
+            config.path.clone(),
+            format!(
+                "the fields of the {} records differ from what this version expects. Skipping them.",
+                name
+            ),
+        )));
+        return None;
+    }
+    Some(ReplayLayout {
+        handler,
+        size,
+        net_msg,
+    })
+}
+
+/// Does the recorded P2P message pass the filter the BPF programs apply?
+fn passes(filter: Option<&NetMsgFilter>, data: &[u8]) -> bool {
+    let Some(filter) = filter else {
+        return true;
+    };
+    let meta = P2PMessageMetadata::from_bytes(&data[mem::size_of::<EventHeader>()..]);
+    filter.matches(
+        meta.peer_id,
+        &meta.msg_type(),
+        meta.msg_inbound,
+        meta.msg_size,
+    )
+}
+
+/// Sleep until `due`. Returns false if the tap was stopped in the meantime.
+fn sleep_until(due: Instant, stop: &AtomicBool) -> bool {
+    loop {
+        if stop.load(Ordering::Relaxed) {
+            return false;
+        }
+        let now = Instant::now();
+        if now >= due {
+            return true;
+        }
+        std::thread::sleep((due - now).min(MAX_SLEEP));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::{CaptureTarget, CaptureWriter, write_record};
+    use crate::queue::{Backpressure, queue};
+    use crate::{Event, EventMsg};
+    use shared::validation::ConnectionTimeUnit;
+    use std::fs::{self, OpenOptions};
+    use std::path::Path;
+
+    /// The offset of the kernel timestamps to the UNIX epoch in the captures
+    const REALTIME_OFFSET_NS: u64 = 1_700_000_000_000_000_000;
+
+    /// A capture file in the temp dir, removed at the end of the test
+    struct TempCapture(PathBuf);
+
+    impl TempCapture {
+        fn new(name: &str) -> Self {
+            TempCapture(std::env::temp_dir().join(format!(
+                "bitcointap-replay-{}-{}.btap",
+                name,
+                std::process::id()
+            )))
+        }
+
+        fn writer(&self, layouts: Vec<RecordLayout>) -> CaptureWriter {
+            let targets = vec![CaptureTarget {
+                label: "main".to_string(),
+                bitcoind_version: None,
+                connection_time_unit: ConnectionTimeUnit::Microseconds,
+            }];
+            CaptureWriter::with_layouts(&self.0, targets, layouts).unwrap()
+        }
+    }
+
+    impl Drop for TempCapture {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    /// A record of the ring buffer with zeroed fields after the header
+    fn record(ringbuffer: &str, seq: u64) -> Vec<u8> {
+        let mut data = vec![0u8; record_size(ringbuffer)];
+        let ktime_at = mem::offset_of!(EventHeader, ktime_ns);
+        data[ktime_at..][..8].copy_from_slice(&(seq * 1000).to_le_bytes());
+        let seq_at = mem::offset_of!(EventHeader, seq);
+        data[seq_at..][..8].copy_from_slice(&seq.to_le_bytes());
+        data
+    }
+
+    /// A record of a P2P message with its payload
+    fn net_msg(seq: u64, msg_type: &str, payload: &[u8]) -> Vec<u8> {
+        let mut data = record("net_msg_small", seq);
+        let meta = mem::size_of::<EventHeader>();
+        let msg_type_at = meta + mem::offset_of!(P2PMessageMetadata, msg_type);
+        data[msg_type_at..][..msg_type.len()].copy_from_slice(msg_type.as_bytes());
+        let msg_size_at = meta + mem::offset_of!(P2PMessageMetadata, msg_size);
+        data[msg_size_at..][..8].copy_from_slice(&(payload.len() as u64).to_le_bytes());
+        data.extend(payload);
+        data
+    }
+
+    fn ping(seq: u64) -> Vec<u8> {
+        net_msg(seq, "ping", &seq.to_le_bytes())
+    }
+
+    fn config(capture: &TempCapture) -> ReplayConfig {
+        ReplayConfig {
+            path: capture.0.clone(),
+            speed: ReplaySpeed::Fast,
+            ringbuffers: None,
+            stats_interval: None,
+            net_msg_filter: None,
+            net_msg_metadata_only: false,
+        }
+    }
+
+    /// Replay the capture into an event queue. Returns how the replay
+    /// ended and the queued messages.
+    fn replay(config: ReplayConfig) -> (Result<(), RuntimeError>, Vec<TapMsg>) {
+        let (tx, rx) = queue(1024, Backpressure::Block, true);
+        let (_filters_tx, filters) = mpsc::channel();
+        let result = replay_thread(config, Arc::new(AtomicBool::new(false)), filters, tx);
+        (result, std::iter::from_fn(|| rx.try_recv().ok()).collect())
+    }
+
+    fn events(msgs: &[TapMsg]) -> Vec<&EventMsg> {
+        msgs.iter()
+            .filter_map(|msg| match msg {
+                TapMsg::Event(event_msg) => Some(event_msg),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn sequences(msgs: &[TapMsg]) -> Vec<u64> {
+        events(msgs)
+            .iter()
+            .filter_map(|event_msg| event_msg.kernel_sequence)
+            .collect()
+    }
+
+    fn capture_errors(msgs: &[TapMsg]) -> Vec<String> {
+        msgs.iter()
+            .filter_map(|msg| match msg {
+                TapMsg::Error(RuntimeError::Capture(_, e)) => Some(e.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The capture with the realtime offset used by the tests
+    fn write_capture(capture: &TempCapture, records: &[(&str, Vec<u8>)]) {
+        write_capture_with_layouts(capture, RecordLayout::current(), records);
+    }
+
+    fn write_capture_with_layouts(
+        capture: &TempCapture,
+        layouts: Vec<RecordLayout>,
+        records: &[(&str, Vec<u8>)],
+    ) {
+        let mut writer = capture.writer(layouts);
+        for (ringbuffer, data) in records {
+            writer.write(ringbuffer, data);
+        }
+        drop(writer);
+        set_realtime_offset(&capture.0);
+    }
+
+    /// The writer takes the offset of this machine's clocks. Overwrite it
+    /// to get predictable timestamps.
+    fn set_realtime_offset(path: &Path) {
+        let mut bytes = fs::read(path).unwrap();
+        let version_len = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+        let offset_at = 10 + version_len + 8;
+        bytes[offset_at..][..8].copy_from_slice(&REALTIME_OFFSET_NS.to_le_bytes());
+        fs::write(path, bytes).unwrap();
+    }
+
+    #[test]
+    fn replays_the_records() {
+        let capture = TempCapture::new("records");
+        write_capture(
+            &capture,
+            &[
+                ("mempool_added", record("mempool_added", 1)),
+                ("net_msg_small", ping(2)),
+            ],
+        );
+
+        let (result, msgs) = replay(config(&capture));
+        result.unwrap();
+        assert_eq!(msgs.len(), 2);
+        let events = events(&msgs);
+        assert!(matches!(events[0].event, Some(Event::Mempool(_))));
+        assert!(matches!(
+            &events[1].event,
+            Some(Event::Msg(msg)) if msg.meta.command == "ping" && msg.msg.is_some()
+        ));
+        for (event_msg, seq) in events.iter().zip(1..) {
+            assert_eq!(event_msg.kernel_sequence, Some(seq));
+            assert_eq!(
+                event_msg.kernel_timestamp_ns,
+                Some(REALTIME_OFFSET_NS + seq * 1000)
+            );
+            assert_eq!(event_msg.source.as_deref(), Some("main"));
+        }
+    }
+
+    #[test]
+    fn skips_truncated_records() {
+        let capture = TempCapture::new("truncated");
+        let mut short = record("mempool_added", 1);
+        short.pop();
+        write_capture(
+            &capture,
+            &[
+                ("mempool_added", short),
+                ("mempool_added", record("mempool_added", 2)),
+            ],
+        );
+
+        let (result, msgs) = replay(config(&capture));
+        result.unwrap();
+        let errors = capture_errors(&msgs);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("mempool_added"));
+        assert_eq!(sequences(&msgs), vec![2]);
+    }
+
+    #[test]
+    fn pads_short_net_msgs() {
+        let capture = TempCapture::new("short-net-msg");
+        let mut short = ping(1);
+        short.truncate(record_size("net_msg_small") - 1);
+        write_capture(&capture, &[("net_msg_small", short)]);
+
+        let (result, msgs) = replay(config(&capture));
+        result.unwrap();
+        assert!(capture_errors(&msgs).is_empty());
+        assert_eq!(sequences(&msgs), vec![1]);
+    }
+
+    #[test]
+    fn skips_layouts_of_another_size() {
+        let capture = TempCapture::new("size");
+        let mut layouts = RecordLayout::current();
+        let added = layouts
+            .iter_mut()
+            .find(|layout| layout.ringbuffer == "mempool_added")
+            .unwrap();
+        added.size += 8;
+        let mut larger = record("mempool_added", 1);
+        larger.extend([0; 8]);
+        write_capture_with_layouts(
+            &capture,
+            layouts,
+            &[
+                ("mempool_added", larger),
+                ("mempool_removed", record("mempool_removed", 2)),
+            ],
+        );
+
+        let (result, msgs) = replay(config(&capture));
+        result.unwrap();
+        let errors = capture_errors(&msgs);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("mempool_added"));
+        assert_eq!(sequences(&msgs), vec![2]);
+    }
+
+    #[test]
+    fn skips_layouts_with_other_fields() {
+        let capture = TempCapture::new("fingerprint");
+        let mut layouts = RecordLayout::current();
+        let added = layouts
+            .iter_mut()
+            .find(|layout| layout.ringbuffer == "mempool_added")
+            .unwrap();
+        added.fingerprint += 1;
+        write_capture_with_layouts(
+            &capture,
+            layouts,
+            &[
+                ("mempool_added", record("mempool_added", 1)),
+                ("mempool_removed", record("mempool_removed", 2)),
+            ],
+        );
+
+        let (result, msgs) = replay(config(&capture));
+        result.unwrap();
+        let errors = capture_errors(&msgs);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("mempool_added"));
+        assert_eq!(sequences(&msgs), vec![2]);
+    }
+
+    #[test]
+    fn stops_at_an_unknown_layout() {
+        let capture = TempCapture::new("unknown");
+        write_capture(&capture, &[("mempool_added", record("mempool_added", 1))]);
+        let mut file = OpenOptions::new().append(true).open(&capture.0).unwrap();
+        let unknown = RecordLayout::current().len() as u16;
+        write_record(&mut file, unknown, 2000, &record("mempool_added", 2)).unwrap();
+        drop(file);
+
+        let (result, msgs) = replay(config(&capture));
+        assert!(matches!(result, Err(RuntimeError::Capture(_, _))));
+        assert_eq!(sequences(&msgs), vec![1]);
+    }
+
+    #[test]
+    fn filters_net_msgs() {
+        let capture = TempCapture::new("filter");
+        write_capture(
+            &capture,
+            &[
+                ("net_msg_small", ping(1)),
+                ("net_msg_small", net_msg(2, "inv", &[0])),
+                ("mempool_added", record("mempool_added", 3)),
+                ("net_msg_small", ping(4)),
+            ],
+        );
+
+        let mut config = config(&capture);
+        config.net_msg_filter = Some(NetMsgFilter::new().allow_msg_types(["ping"]));
+        let (result, msgs) = replay(config);
+        result.unwrap();
+        assert_eq!(sequences(&msgs), vec![1, 3, 4]);
+    }
+
+    #[test]
+    fn replays_net_msg_metadata_only() {
+        let capture = TempCapture::new("metadata");
+        write_capture(&capture, &[("net_msg_small", ping(1))]);
+
+        let mut config = config(&capture);
+        config.net_msg_metadata_only = true;
+        let (result, msgs) = replay(config);
+        result.unwrap();
+        let events = events(&msgs);
+        assert_eq!(events.len(), 1);
+        assert!(matches!(
+            &events[0].event,
+            Some(Event::Msg(msg)) if msg.meta.command == "ping" && msg.msg.is_none()
+        ));
+    }
+
+    #[test]
+    fn replays_the_selected_ringbuffers() {
+        let capture = TempCapture::new("selected");
+        write_capture(
+            &capture,
+            &[
+                ("net_msg_small", ping(1)),
+                ("mempool_added", record("mempool_added", 2)),
+            ],
+        );
+
+        let mut config = config(&capture);
+        config.ringbuffers = Some(vec!["mempool_added"]);
+        let (result, msgs) = replay(config);
+        result.unwrap();
+        assert_eq!(sequences(&msgs), vec![2]);
+    }
+}
@@ -0,0 +1,167 @@
+use crate::clock::ktime_to_unix_ns;
+use crate::queue::EventSender;
+use crate::reorder::Reorder;
+use crate::version::connection_time_unit;
+use crate::{BitcoindVersion, Event, EventMsg, TapMsg};
+use shared::ctypes::EventHeader;
+use shared::validation::ConnectionTimeUnit;
+use std::path::Path;
+use std::sync::mpsc::SendError;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+/// What the handlers need to know about the bitcoind build of a target
+#[derive(Debug, Clone)]
+pub(crate) struct TargetBuild {
+    pub(crate) connection_time_unit: ConnectionTimeUnit,
+    pub(crate) version: Option<String>,
+}
+
+impl TargetBuild {
+    /// The build of the binary at `path`. A given `unit` wins over the one
+    /// of the version.
+    pub(crate) fn new(
+        unit: Option<ConnectionTimeUnit>,
+        version: Option<&BitcoindVersion>,
+        path: &Path,
+    ) -> Self {
+        TargetBuild {
+            connection_time_unit: connection_time_unit(unit, version, path),
+            version: version.map(|version| version.to_string()),
+        }
+    }
+}
+
+/// Where the ring buffer handlers put the events: the event queue, through
+/// the reordering stage if there is one. Other messages go straight to the
+/// queue.
+#[derive(Clone)]
+pub(crate) struct EventSink {
+    tx: EventSender,
+    /// The labels of the targets, indexed by [`EventHeader::target`]
+    sources: Arc<[String]>,
+    /// The builds of the targets, indexed by [`EventHeader::target`]. A
+    /// restarted target might run another build.
+    builds: Arc<Mutex<Vec<TargetBuild>>>,
+    reorder: Option<Arc<Mutex<Reorder>>>,
+    /// Converts the kernel timestamps of a replayed capture instead of the
+    /// clocks of this machine
+    realtime_offset_ns: Option<u64>,
+}
+
+impl EventSink {
+    /// With a `window`, events are held back that long and then handed out
+    /// ordered by their kernel sequence number
+    pub(crate) fn new(
+        tx: EventSender,
+        sources: Vec<String>,
+        builds: Vec<TargetBuild>,
+        window: Option<Duration>,
+    ) -> Self {
+        EventSink {
+            tx,
+            sources: sources.into(),
+            builds: Arc::new(Mutex::new(builds)),
+            reorder: window.map(|window| Arc::new(Mutex::new(Reorder::new(window)))),
+            realtime_offset_ns: None,
+        }
+    }
+
+    /// Add this to the kernel timestamps to get the time since the UNIX
+    /// epoch, e.g. the [`crate::CaptureHeader::realtime_offset_ns`] of a
+    /// replayed capture
+    pub(crate) fn with_realtime_offset_ns(mut self, offset_ns: u64) -> Self {
+        self.realtime_offset_ns = Some(offset_ns);
+        self
+    }
+
+    /// The bitcoind build of the target with the index
+    pub(crate) fn build(&self, target: u32) -> Option<TargetBuild> {
+        self.builds
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(target as usize)
+            .cloned()
+    }
+
+    /// Replace the build of a target, e.g. after it restarted
+    pub(crate) fn set_build(&self, target: usize, build: TargetBuild) {
+        if let Some(old) = self
+            .builds
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get_mut(target)
+        {
+            *old = build;
+        }
+    }
+
+    /// Queue an event stamped with the time its tracepoint fired and the
+    /// bitcoind process it is from
+    pub(crate) fn send_event(
+        &self,
+        header: EventHeader,
+        event: Event,
+    ) -> Result<(), SendError<TapMsg>> {
+        let mut event_msg = EventMsg::new(event).with_kernel_sequence(header.seq);
+        let timestamp_ns = match self.realtime_offset_ns {
+            Some(offset_ns) => header.ktime_ns.checked_add(offset_ns),
+            None => ktime_to_unix_ns(header.ktime_ns),
+        };
+        if let Some(ns) = timestamp_ns {
+            event_msg = event_msg.with_kernel_timestamp_ns(ns);
+        }
+        if let Some(source) = self.sources.get(header.target as usize) {
+            event_msg = event_msg.with_source(source.clone(), header.pid);
+        }
+        self.send(TapMsg::Event(event_msg))
+    }
+
+    /// Queue a message or hold it back for reordering. Fails when the
+    /// receiver is gone.
+    pub(crate) fn send(&self, msg: TapMsg) -> Result<(), SendError<TapMsg>> {
+        let Some(reorder) = &self.reorder else {
+            return self.tx.send(msg);
+        };
+        let (seq, timestamp_ns) = match &msg {
+            TapMsg::Event(event_msg) => {
+                match (event_msg.kernel_sequence, event_msg.kernel_timestamp_ns) {
+                    (Some(seq), Some(timestamp_ns)) => (seq, timestamp_ns),
+                    _ => return self.tx.send(msg),
+                }
+            }
+            _ => return self.tx.send(msg),
+        };
+
+        let late = reorder
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(seq, timestamp_ns, msg);
+        match late {
+            Some(msg) => self.tx.send(msg),
+            None => Ok(()),
+        }
+    }
+
+    /// Queue the held events that are older than the window. With `all`,
+    /// queue every held event, e.g. when detaching.
+    pub(crate) fn flush(&self, all: bool) -> Result<(), SendError<TapMsg>> {
+        let Some(reorder) = &self.reorder else {
+            return Ok(());
+        };
+        let now_ns = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |now| now.as_nanos() as u64);
+        loop {
+            // Don't hold the lock while a blocking queue waits for room
+            let next = reorder
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .pop(now_ns, all);
+            match next {
+                Some(msg) => self.tx.send(msg)?,
+                None => return Ok(()),
+            }
+        }
+    }
+}
@@ -0,0 +1,152 @@
+use crate::queue::EventReceiver;
+use crate::tap::{PID_CHECK_INTERVAL, Runtime};
+use crate::{BitcoinTap, KernelStats, NetMsgFilter, QueueStats, RuntimeError, TapMsg};
+use futures::Stream;
+use std::os::fd::{AsRawFd, RawFd};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::unix::AsyncFd;
+use tokio::time::{Interval, MissedTickBehavior};
+
+/// The epoll fd of the ring buffers. It's owned by the ring buffers.
+struct EpollFd(RawFd);
+
+impl AsRawFd for EpollFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0
+    }
+}
+
+/// The events of a tap as a [`Stream`]. The ring buffers are consumed when
+/// tokio reports them as readable, no thread is needed. The stream ends after
+/// the final [`TapMsg::Detached`]. Dropping it detaches from bitcoind.
+pub struct TapStream {
+    /// Deregistered from tokio before the runtime closes the fd
+    epoll: AsyncFd<EpollFd>,
+    runtime: Runtime,
+    rx: EventReceiver,
+    housekeeping: Interval,
+    /// Events were left in the ring buffers when the queue was full
+    backlog: bool,
+    detached: bool,
+}
+
+// SAFETY: the libbpf objects in the runtime are not bound to the thread that
+// created them and are only used through `&mut self`.
+unsafe impl Send for TapStream {}
+
+impl BitcoinTap {
+    /// Attach to the process and read the events through a [`TapStream`].
+    /// Must be called from within a tokio runtime. Loading the BPF object
+    /// into the kernel blocks for a moment. The ring buffers are only read
+    /// once the queued events were taken from the stream. With
+    /// [`crate::Backpressure::Block`], the stream never blocks: it reads only
+    /// as many events as fit into the queue and leaves the rest in the ring
+    /// buffers, where the kernel drops new events once they are full. Events
+    /// held back for [`Self::reorder_window`] are handed out on top.
+    pub fn attach_stream(self) -> Result<TapStream, RuntimeError> {
+        let (tx, rx) = self.event_queue(false);
+        let config = self.config(&tx)?;
+        let runtime = Runtime::load(config, tx)?;
+        let epoll = AsyncFd::new(EpollFd(runtime.epoll_fd()))?;
+        let mut housekeeping = tokio::time::interval(PID_CHECK_INTERVAL);
+        housekeeping.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        Ok(TapStream {
+            epoll,
+            runtime,
+            rx,
+            housekeeping,
+            backlog: false,
+            detached: false,
+        })
+    }
+}
+
+impl TapStream {
+    /// Consume the ring buffers, with [`crate::Backpressure::Block`] only up
+    /// to the room in the queue. Returns whether events were left in them.
+    fn consume(&mut self) -> bool {
+        match self.rx.room() {
+            Some(room) => self.runtime.consume_n(room),
+            None => {
+                self.runtime.consume();
+                false
+            }
+        }
+    }
+
+    /// The state of the event queue, including the dropped events
+    pub fn stats(&self) -> QueueStats {
+        self.rx.stats()
+    }
+
+    /// Replace the P2P message filter without re-attaching
+    pub fn set_net_msg_filter(&self, filter: NetMsgFilter) -> Result<(), RuntimeError> {
+        self.runtime.set_net_msg_filter(&filter)
+    }
+
+    /// The events lost in the kernel so far
+    pub fn kernel_stats(&self) -> Result<KernelStats, RuntimeError> {
+        self.runtime.kernel_stats()
+    }
+
+    /// Detach the uprobes and hand out the events left in the ring buffers.
+    /// The stream then ends after a final [`TapMsg::Detached`].
+    pub fn stop(&mut self) {
+        if self.detached {
+            return;
+        }
+        self.detached = true;
+        if let Err(e) = self.runtime.detach() {
+            self.runtime.send(TapMsg::Error(e));
+        }
+        self.runtime.send(TapMsg::Detached { label: None });
+    }
+}
+
+impl Stream for TapStream {
+    type Item = TapMsg;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<TapMsg>> {
+        let this = self.get_mut();
+        loop {
+            if let Ok(msg) = this.rx.try_recv() {
+                return Poll::Ready(Some(msg));
+            }
+            if this.detached {
+                return Poll::Ready(None);
+            }
+
+            if this.housekeeping.poll_tick(cx).is_ready() {
+                this.runtime.housekeeping();
+                continue;
+            }
+
+            // The kernel doesn't wake us up for the events left in the ring
+            // buffers, so they are consumed once the queue was read
+            if this.backlog {
+                this.backlog = this.consume();
+                continue;
+            }
+
+            // Clear the readiness before consuming, so events arriving while
+            // consuming wake us up again.
+            let ready = match this.epoll.poll_read_ready(cx) {
+                Poll::Ready(Ok(mut guard)) => {
+                    guard.clear_ready();
+                    Ok(())
+                }
+                Poll::Ready(Err(e)) => Err(e),
+                Poll::Pending => return Poll::Pending,
+            };
+            match ready {
+                Ok(()) => this.backlog = this.consume(),
+                Err(e) => {
+                    this.runtime.send(TapMsg::Error(e.into()));
+                    this.stop();
+                }
+            }
+        }
+    }
+}
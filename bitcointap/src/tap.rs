@@ -1,57 +1,180 @@
+use crate::capture::{CaptureTarget, CaptureWriter};
+use crate::filter::NetMsgFilter;
+use crate::kernel_stats::{KernelStats, read_kernel_stats};
+use crate::preflight::{self, Preflight};
+use crate::process::{
+    BitcoindProcess, auto_bitcoind, cgroup_bitcoind, container_bitcoind, pids_executing,
+    process_alive, process_cgroup,
+};
+use crate::queue::{
+    self, Backpressure, DEFAULT_QUEUE_CAPACITY, EventReceiver, EventSender, QueueStats,
+};
+use crate::replay::{ReplayConfig, ReplaySpeed, replay_thread};
+use crate::sink::{EventSink, TargetBuild};
+use crate::tracepoint::RINGBUFFERS;
+use crate::usdt::BitcoindBinary;
 use crate::{
-    ConnectionEvent, ConnectionMsg, Event, EventMsg, MempoolEvent, MempoolMsg, RuntimeError,
-    TRACEPOINTS_MEMPOOL, TRACEPOINTS_NET_CONN, TRACEPOINTS_NET_MESSAGE, TRACEPOINTS_VALIDATION,
-    ValidationEvent, ValidationMsg, tracing,
+    AddrmanEvent, AddrmanMsg, CoinSelectionEvent, CoinSelectionMsg, ConnectionEvent, ConnectionMsg,
+    Event, EventMsg, MempoolEvent, MempoolMsg, RuntimeError, Tracepoint, TracepointGroup,
+    UsdtProbe, UtxocacheEvent, UtxocacheMsg, ValidationEvent, ValidationMsg, read_usdt_probes,
+    tracing,
 };
 use libbpf_rs::skel::{OpenSkel, Skel, SkelBuilder};
-use libbpf_rs::{Map, MapCore, Object, ProgramMut, RingBufferBuilder};
+use libbpf_rs::{
+    ErrorKind, Link, Map, MapCore, Object, OpenObject, ProgramMut, RingBuffer, RingBufferBuilder,
+    UsdtOpts,
+};
 use shared::ctypes::{
-    ClosedConnection, InboundConnection, MempoolAdded, MempoolRejected, MempoolRemoved,
-    MempoolReplaced, MisbehavingConnection, OutboundConnection, P2PMessage,
+    AddrmanInsertNew, AddrmanInsertTried, ClosedConnection, CoinSelectionApsCreateTx,
+    CoinSelectionAttemptingApsCreateTx, CoinSelectionNormalCreateTx, CoinSelectionSelectedCoins,
+    InboundConnection, MempoolAdded, MempoolRejected, MempoolRemoved, MempoolReplaced,
+    MisbehavingConnection, OutboundConnection, P2PMessage, UtxocacheCoin, UtxocacheFlush,
     ValidationBlockConnected,
 };
 use shared::log::{self};
 //use shared::simple_logger;
+use shared::validation::{BlockConnected, ConnectionTimeUnit};
 use shared::{mempool, net_msg};
+use std::collections::BTreeMap;
 use std::fs::File;
 use std::io::{BufReader, Read};
-use std::mem::MaybeUninit;
+use std::mem::{ManuallyDrop, MaybeUninit};
 use std::path::{Path, PathBuf};
-use std::sync::mpsc::{self, Receiver, Sender};
-use std::time::Duration;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
 use std::time::SystemTime;
+use std::time::{Duration, Instant};
 
 const RINGBUFF_CALLBACK_OK: i32 = 0;
 const RINGBUFF_CALLBACK_SYSTEM_TIME_ERROR: i32 = -5;
-const RINGBUFF_CALLBACK_PUBLISH_ERROR: i32 = -10;
-const RINGBUFF_CALLBACK_UNABLE_TO_PARSE_P2P_MSG: i32 = -20;
+pub(crate) const RINGBUFF_CALLBACK_PUBLISH_ERROR: i32 = -10;
+
+/// The P2P message ring buffers with a payload. Nothing is written to them
+/// when only the metadata is recorded.
+const NET_MSG_PAYLOAD_RINGBUFFERS: [&str; 4] = [
+    "net_msg_small",
+    "net_msg_medium",
+    "net_msg_large",
+    "net_msg_huge",
+];
+/// A single page, the smallest ring buffer the kernel creates
+const MIN_RINGBUFFER_SIZE: u32 = 4096;
 
 const NO_EVENTS_ERROR_DURATION: Duration = Duration::from_secs(60 * 3);
 const NO_EVENTS_WARN_DURATION: Duration = Duration::from_secs(60 * 1);
-const DEFAULT_PID: i32 = -1;
+pub(crate) const DEFAULT_PID: i32 = -1;
+/// How often a supervised tap checks if the bitcoind process is still running
+pub(crate) const PID_CHECK_INTERVAL: Duration = Duration::from_secs(1);
 
 pub struct BitcoinTap {
-    pid_source: PidSource,
-
-    /// The queue that you read events off of
-    rx: Receiver<TapMsg>,
-
-    /// All tapped events are sent over this channel
-    tx: Sender<TapMsg>,
+    /// The bitcoind processes to attach to. The first one is the target
+    /// passed to [`Self::new`].
+    targets: Vec<Target>,
 
     /// Do we have libbpf debugging enabled ?
     debug: bool,
 
-    /// bitcoind_path
-    path: PathBuf,
+    /// The tracepoints to attach to. Empty means [`TracepointGroup::DEFAULT`].
+    tracepoints: Vec<Tracepoint<'static>>,
+
+    /// Re-attach when the bitcoind process restarts
+    supervise: bool,
+
+    /// The number of events the event queue holds
+    queue_capacity: usize,
+
+    /// What to do with new events when the event queue is full
+    backpressure: Backpressure,
+
+    /// How often to send [`TapMsg::Stats`]
+    stats_interval: Option<Duration>,
+
+    /// Ring buffer sizes in bytes that differ from the ones in tracing.bpf.c
+    ringbuffer_sizes: BTreeMap<String, u32>,
+
+    /// Which P2P messages the BPF programs pass on
+    net_msg_filter: Option<NetMsgFilter>,
+
+    /// Only record the metadata of P2P messages
+    net_msg_metadata_only: bool,
+
+    /// Hold events back this long to hand them out in kernel order
+    reorder_window: Option<Duration>,
+
+    /// Write the raw ring buffer records to this capture file
+    capture: Option<PathBuf>,
+
+    /// Replay this capture file instead of attaching to bitcoind
+    replay: Option<PathBuf>,
+
+    /// How fast to replay the capture
+    replay_speed: ReplaySpeed,
+}
+
+/// A bitcoind process to attach to
+#[derive(Debug, Clone)]
+pub struct Target {
+    /// Tags the events of this process in `EventMsg::source`
+    pub label: String,
+    /// The bitcoind binary
+    pub path: PathBuf,
+    pub pid_source: PidSource,
+    /// The unit `validation:block_connected` passes the connection time in.
+    /// Detected from the version of the binary if `None`.
+    pub connection_time_unit: Option<ConnectionTimeUnit>,
+}
+
+impl Target {
+    /// A target labeled with its path
+    pub fn new(path: impl AsRef<Path>, pid_source: PidSource) -> Self {
+        let path = path.as_ref().to_owned();
+        Target {
+            label: path.display().to_string(),
+            path,
+            pid_source,
+            connection_time_unit: None,
+        }
+    }
+
+    /// Label the events of this target, e.g. with the network
+    pub fn label(mut self, label: impl Into<String>) -> Self {
+        self.label = label.into();
+        self
+    }
+
+    /// Set the unit of the block connection times, e.g. for a build whose
+    /// version can't be read from the binary
+    pub fn connection_time_unit(mut self, unit: ConnectionTimeUnit) -> Self {
+        self.connection_time_unit = Some(unit);
+        self
+    }
 }
 
 /// Used to specify where to source bitcoind's `pid` from
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum PidSource {
     Manual(i32),
     File(PathBuf),
     DefaultPid,
+    /// Find the running bitcoind in `/proc`. It has to be the only one
+    /// executing the binary, or the only one at all if the path is empty.
+    /// With an empty path, the binary path of the process is used.
+    Auto,
+    /// The bitcoind in a container, found by the host PID of any process
+    /// in it, e.g. `docker inspect --format '{{.State.Pid}}'`. The binary is
+    /// read through `/proc/<pid>/root`, the path is ignored. When
+    /// supervised, the restarted bitcoind is looked for in the cgroup v2 of
+    /// the container. Without a cgroup v2, only a bitcoind restarted within
+    /// the still running container is found.
+    Container(i32),
+    /// The bitcoind in a cgroup, e.g. of a container:
+    /// `/sys/fs/cgroup/system.slice/docker-<id>.scope`. The binary is read
+    /// through `/proc/<pid>/root`, the path is ignored. Survives container
+    /// restarts when supervised.
+    Cgroup(PathBuf),
 }
 
 impl Default for PidSource {
@@ -62,48 +185,114 @@ impl Default for PidSource {
 
 #[derive(Debug)]
 pub enum TapMsg {
-    /// The tap thread has been detached. Call attach again to restart.
-    Detached,
+    /// The tap has been detached. Without a label, this is the last message
+    /// of a stopped or failed tap. A supervised tap detaches from a target
+    /// with the `label` when its bitcoind process exits, and sends
+    /// [`TapMsg::Reattached`] once it found the restarted process.
+    Detached { label: Option<String> },
+
+    /// A supervised tap re-attached to the restarted bitcoind process of
+    /// the target with the `label`
+    Reattached { label: String, pid: i32 },
 
     /// There was an error when attaching
     Error(RuntimeError),
 
+    /// The state of the event queue, sent periodically
+    Stats(QueueStats),
+
+    /// The events lost in the kernel, sent together with [`TapMsg::Stats`]
+    KernelStats(KernelStats),
+
     /// An event from the tap thread
     Event(EventMsg),
 }
 
-impl TapMsg {
-    #[inline]
-    fn event(event_msg: EventMsg) -> Self {
-        TapMsg::Event(event_msg)
-    }
-}
-
 impl BitcoinTap {
     /// Create a new bitcoin tap. Attach by calling [`Self::attach`]
     pub fn new(path: impl AsRef<Path>) -> Self {
-        let (tx, rx) = mpsc::channel();
-        let path: PathBuf = path.as_ref().to_owned();
+        let targets = vec![Target::new(path, PidSource::default())];
         let debug = false;
-        let pid_source = PidSource::default();
+        let tracepoints = vec![];
+        let supervise = false;
+        let queue_capacity = DEFAULT_QUEUE_CAPACITY;
+        let backpressure = Backpressure::default();
+        let stats_interval = None;
+        let ringbuffer_sizes = BTreeMap::new();
+        let net_msg_filter = None;
+        let net_msg_metadata_only = false;
+        let reorder_window = None;
+        let capture = None;
+        let replay = None;
+        let replay_speed = ReplaySpeed::default();
 
         Self {
-            tx,
-            rx,
-            path,
+            targets,
             debug,
-            pid_source,
+            tracepoints,
+            supervise,
+            queue_capacity,
+            backpressure,
+            stats_interval,
+            ringbuffer_sizes,
+            net_msg_filter,
+            net_msg_metadata_only,
+            reorder_window,
+            capture,
+            replay,
+            replay_speed,
         }
     }
 
-    /// The event stream
-    pub fn events(&mut self) -> &mut Receiver<TapMsg> {
-        &mut self.rx
+    /// Replay a capture file written with [`Self::capture`] instead of
+    /// attaching to bitcoind. The records go through the same decoding as
+    /// the ones read from the ring buffers, but neither root, BPF nor a
+    /// running bitcoind are needed. The events are labeled with the targets
+    /// of the capture. Without selected tracepoints, all recorded events are
+    /// replayed. The P2P message filter is applied to the recorded messages.
+    /// Start the replay with [`Self::attach`], it detaches at the end of the
+    /// capture.
+    pub fn replay(path: impl AsRef<Path>) -> Self {
+        let mut tap = Self::new("");
+        tap.replay = Some(path.as_ref().to_owned());
+        tap
+    }
+
+    /// How fast a capture is replayed. Defaults to [`ReplaySpeed::Fast`].
+    pub fn replay_speed(mut self, speed: ReplaySpeed) -> Self {
+        self.replay_speed = speed;
+        self
     }
 
     /// Declare the pid source. This is used on [`Self::attach`]
     pub fn pid_source(mut self, source: PidSource) -> Self {
-        self.pid_source = source;
+        self.targets[0].pid_source = source;
+        self
+    }
+
+    /// Label the events of the bitcoind process passed to [`Self::new`].
+    /// Defaults to the path of the binary.
+    pub fn label(mut self, label: impl Into<String>) -> Self {
+        self.targets[0].label = label.into();
+        self
+    }
+
+    /// Set the unit `validation:block_connected` of the bitcoind process
+    /// passed to [`Self::new`] passes the connection time in. By default,
+    /// it's detected from the version of the binary. The
+    /// [`shared::validation::BlockConnected`] events are always in µs.
+    pub fn connection_time_unit(mut self, unit: ConnectionTimeUnit) -> Self {
+        self.targets[0].connection_time_unit = Some(unit);
+        self
+    }
+
+    /// Attach to another bitcoind process as well, e.g. a testnet node on
+    /// the same host. All targets share the BPF programs and ring buffers.
+    /// Their events are told apart by the label and pid in the `EventMsg`.
+    /// Two targets running the same binary need a [`PidSource`] other than
+    /// [`PidSource::DefaultPid`], otherwise both trace all its processes.
+    pub fn target(mut self, target: Target) -> Self {
+        self.targets.push(target);
         self
     }
 
@@ -113,26 +302,404 @@ impl BitcoinTap {
         self
     }
 
-    /// Attach to the process and start reading events
-    pub fn attach(self) -> Result<Self, RuntimeError> {
-        let pid = bitcoind_pid(&self.pid_source)?;
-        let debug = self.debug;
-        let tx = self.tx.clone();
-        let path = self.path.clone();
+    /// Watch the bitcoind process and re-attach to it when it restarts, e.g.
+    /// after an upgrade. The restarted process is found by re-reading the
+    /// [`PidSource::File`], in the cgroup of a [`PidSource::Cgroup`] or
+    /// [`PidSource::Container`], or by looking for a process executing the
+    /// binary. Has no effect with [`PidSource::DefaultPid`], which traces all
+    /// processes of the binary anyway.
+    pub fn supervise(mut self, enable: bool) -> Self {
+        self.supervise = enable;
+        self
+    }
+
+    /// The number of events the event queue holds before [`Self::backpressure`]
+    /// kicks in. Defaults to [`DEFAULT_QUEUE_CAPACITY`].
+    pub fn queue_capacity(mut self, capacity: usize) -> Self {
+        self.queue_capacity = capacity;
+        self
+    }
+
+    /// What to do with new events when the event queue is full. Dropped
+    /// events are counted in the [`QueueStats`].
+    pub fn backpressure(mut self, policy: Backpressure) -> Self {
+        self.backpressure = policy;
+        self
+    }
+
+    /// How often to send a [`TapMsg::Stats`] and [`TapMsg::KernelStats`],
+    /// e.g. every 10 seconds. Off by default, [`TapHandle::stats`] can be
+    /// read any time.
+    pub fn stats_interval(mut self, interval: Option<Duration>) -> Self {
+        self.stats_interval = interval;
+        self
+    }
+
+    /// Set the size of a BPF ring buffer in bytes, e.g. of `net_msg_huge`.
+    /// libbpf rounds it up to a power of two multiple of the page size. The
+    /// ring buffers are locked in memory, so smaller ones save memory while
+    /// larger ones drop fewer events during bursts. See [`RINGBUFFERS`] for
+    /// the names.
+    pub fn ringbuffer_size(mut self, name: impl Into<String>, bytes: u32) -> Self {
+        self.ringbuffer_sizes.insert(name.into(), bytes);
+        self
+    }
+
+    /// Only pass the P2P messages selected by the filter. Filtered messages
+    /// are skipped in the kernel. Can be changed on the running tap with
+    /// [`TapHandle::set_net_msg_filter`].
+    pub fn net_msg_filter(mut self, filter: NetMsgFilter) -> Self {
+        self.net_msg_filter = Some(filter);
+        self
+    }
+
+    /// Only record the metadata of P2P messages: peer, message type,
+    /// direction and size. The payload is not copied out of bitcoind and the
+    /// [`shared::net_msg::Message`]s have no `msg`. Enough to account for
+    /// the number and bytes of messages, at a fraction of the overhead.
+    pub fn net_msg_metadata_only(mut self, enable: bool) -> Self {
+        self.net_msg_metadata_only = enable;
+        self
+    }
+
+    /// Hand out the events in the order their tracepoints fired. Each ring
+    /// buffer is in order, but they are drained one after another. With a
+    /// window, events are held back for that long and then merged by their
+    /// kernel sequence number. Events arriving later than the window are
+    /// handed out as they come. Off by default.
+    pub fn reorder_window(mut self, window: Option<Duration>) -> Self {
+        self.reorder_window = window;
+        self
+    }
+
+    /// Write the raw ring buffer records to a capture file as well, see
+    /// [`crate::CaptureHeader`] for the format. The records are written as
+    /// read from the kernel, so a capture can be decoded again by later
+    /// versions.
+    pub fn capture(mut self, path: impl AsRef<Path>) -> Self {
+        self.capture = Some(path.as_ref().to_owned());
+        self
+    }
+
+    /// Enable all tracepoints of a group. Can be called multiple times.
+    pub fn group(self, group: TracepointGroup) -> Self {
+        self.tracepoints(group.tracepoints().iter().copied())
+    }
+
+    /// Enable all tracepoints of the given groups
+    pub fn groups(mut self, groups: impl IntoIterator<Item = TracepointGroup>) -> Self {
+        for group in groups {
+            self = self.group(group);
+        }
+        self
+    }
+
+    /// Enable a single tracepoint, e.g. one from [`crate::TRACEPOINTS_MEMPOOL`]
+    pub fn tracepoint(self, tracepoint: Tracepoint<'static>) -> Self {
+        self.tracepoints([tracepoint])
+    }
 
-        std::thread::spawn(move || {
-            let tx2 = tx.clone();
-            if let Err(err) = ebpf_thread(pid, debug, tx2, path) {
+    /// Enable the given tracepoints
+    pub fn tracepoints(
+        mut self,
+        tracepoints: impl IntoIterator<Item = Tracepoint<'static>>,
+    ) -> Self {
+        for tracepoint in tracepoints {
+            if !self.tracepoints.contains(&tracepoint) {
+                self.tracepoints.push(tracepoint);
+            }
+        }
+        self
+    }
+
+    /// The tracepoints that will be attached to on [`Self::attach`]. If
+    /// none were selected, these are the ones of [`TracepointGroup::DEFAULT`].
+    pub fn selected_tracepoints(&self) -> Vec<Tracepoint<'static>> {
+        if self.tracepoints.is_empty() {
+            TracepointGroup::DEFAULT
+                .iter()
+                .flat_map(|group| group.tracepoints().iter().copied())
+                .collect()
+        } else {
+            self.tracepoints.clone()
+        }
+    }
+
+    /// The USDT probes of the bitcoind binary at `path`. Use
+    /// [`UsdtProbe::matches`] to check which of our tracepoints it supports.
+    pub fn available_tracepoints(path: impl AsRef<Path>) -> Result<Vec<UsdtProbe>, RuntimeError> {
+        read_usdt_probes(path.as_ref())
+    }
+
+    /// Check if attaching can work: the privileges, the kernel, the memory
+    /// limits, the binaries and the processes of the targets. Unlike
+    /// [`Self::attach`], nothing is loaded into the kernel.
+    pub fn preflight(&self) -> Preflight {
+        let tracepoints = self.selected_tracepoints();
+        let mut preflight = Preflight::default();
+        let ringbuffer_sizes =
+            effective_ringbuffer_sizes(&self.ringbuffer_sizes, self.net_msg_metadata_only);
+        preflight::check_system(&mut preflight, &tracepoints, &ringbuffer_sizes);
+        for target in &self.targets {
+            preflight::check_target(&mut preflight, target, &tracepoints);
+        }
+        preflight
+    }
+
+    /// Attach to the process and start reading events. Selected tracepoints
+    /// the binary has no probe for are skipped and reported as
+    /// [`RuntimeError::ProbeNotFound`]. A tap created with [`Self::replay`]
+    /// starts replaying the capture instead.
+    pub fn attach(mut self) -> Result<TapHandle, RuntimeError> {
+        let (tx, rx) = self.event_queue(true);
+        let source = match self.replay.take() {
+            Some(path) => TapSource::Replay(self.replay_config(path)),
+            None => TapSource::Bpf(self.config(&tx)?),
+        };
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop2 = stop.clone();
+        let (filter_tx, filter_rx) = mpsc::channel();
+
+        let thread = std::thread::spawn(move || {
+            let result = match source {
+                TapSource::Bpf(config) => ebpf_thread(config, stop2, filter_rx, tx.clone()),
+                TapSource::Replay(config) => replay_thread(config, stop2, filter_rx, tx.clone()),
+            };
+            if let Err(err) = result {
                 let _ = tx.send(TapMsg::Error(err));
-                let _ = tx.send(TapMsg::Detached);
             }
+            let _ = tx.send(TapMsg::Detached { label: None });
         });
 
-        Ok(self)
+        Ok(TapHandle {
+            rx,
+            stop,
+            filter_tx,
+            thread: Some(thread),
+        })
+    }
+
+    /// The event queue as configured. See [`queue::queue`] for `blocking`.
+    pub(crate) fn event_queue(&self, blocking: bool) -> (EventSender, EventReceiver) {
+        queue::queue(self.queue_capacity, self.backpressure, blocking)
+    }
+
+    /// Resolve the pid and check the selected tracepoints against the binary
+    pub(crate) fn config(self, tx: &EventSender) -> Result<TapConfig, RuntimeError> {
+        if let Some(path) = self.replay {
+            return Err(RuntimeError::Capture(
+                path,
+                "a capture can only be replayed with attach()".to_string(),
+            ));
+        }
+        if let Some(filter) = &self.net_msg_filter {
+            filter.validate()?;
+        }
+        if let Some(name) = self
+            .ringbuffer_sizes
+            .keys()
+            .find(|name| !RINGBUFFERS.contains(&name.as_str()))
+        {
+            return Err(RuntimeError::NoSuchBPFMap(name.clone()));
+        }
+        for (i, target) in self.targets.iter().enumerate() {
+            if self.targets[..i].iter().any(|t| t.label == target.label) {
+                return Err(RuntimeError::DuplicateTarget(target.label.clone()));
+            }
+        }
+
+        let selected = self.selected_tracepoints();
+        let mut targets = vec![];
+        for mut target in self.targets {
+            let pid = match target.pid_source {
+                PidSource::Auto | PidSource::Container(_) | PidSource::Cgroup(_) => {
+                    let process = find_bitcoind(&target.pid_source, &target.path)?;
+                    log::info!("Found bitcoind {}", process);
+                    if target.label.is_empty() {
+                        target.label = process.chain;
+                    }
+                    target.path = process.path;
+                    process.pid
+                }
+                ref source => bitcoind_pid(source, &target.path)?,
+            };
+            if pid != DEFAULT_PID && !process_alive(pid) {
+                return Err(RuntimeError::PidNotRunning(pid));
+            }
+            let binary = BitcoindBinary::read(&target.path)?;
+            targets.push(TargetConfig {
+                pid,
+                tracepoints: available_tracepoints(
+                    selected.clone(),
+                    &binary.probes,
+                    &target.path,
+                    tx,
+                ),
+                build: TargetBuild::new(
+                    target.connection_time_unit,
+                    binary.version.as_ref(),
+                    &target.path,
+                ),
+                supervisor: match target.pid_source {
+                    _ if !self.supervise || pid == DEFAULT_PID => None,
+                    // The pid is gone when the container restarts, its
+                    // cgroup stays
+                    PidSource::Container(container) => match process_cgroup(pid) {
+                        Ok(cgroup) => Some(PidSource::Cgroup(cgroup)),
+                        Err(e) => {
+                            log::warn!(
+                                "Can't supervise the container of pid {} through its cgroup, a restart of the container is not followed: {}",
+                                container,
+                                e
+                            );
+                            Some(PidSource::Container(container))
+                        }
+                    },
+                    source => Some(source),
+                },
+                label: target.label,
+                path: target.path,
+                connection_time_unit: target.connection_time_unit,
+            });
+        }
+        Ok(TapConfig {
+            targets,
+            debug: self.debug,
+            stats_interval: self.stats_interval,
+            ringbuffer_sizes: self.ringbuffer_sizes,
+            net_msg_filter: self.net_msg_filter,
+            net_msg_metadata_only: self.net_msg_metadata_only,
+            reorder_window: self.reorder_window,
+            capture: self.capture,
+        })
+    }
+
+    /// What to replay from the capture file at `path`
+    fn replay_config(self, path: PathBuf) -> ReplayConfig {
+        // Without a selection, the capture decides
+        let ringbuffers = (!self.tracepoints.is_empty()).then(|| {
+            self.tracepoints
+                .iter()
+                .flat_map(|tracepoint| tracepoint.ringbuffers.iter().copied())
+                .collect()
+        });
+        ReplayConfig {
+            path,
+            speed: self.replay_speed,
+            ringbuffers,
+            stats_interval: self.stats_interval,
+            net_msg_filter: self.net_msg_filter,
+            net_msg_metadata_only: self.net_msg_metadata_only,
+        }
     }
 }
 
-fn bitcoind_pid(method: &PidSource) -> Result<i32, RuntimeError> {
+/// Where the events of an attached tap come from
+enum TapSource {
+    /// The BPF ring buffers of the bitcoind processes
+    Bpf(TapConfig),
+    /// A capture file
+    Replay(ReplayConfig),
+}
+
+/// What the tap needs to know to attach to bitcoind
+pub(crate) struct TapConfig {
+    targets: Vec<TargetConfig>,
+    debug: bool,
+    stats_interval: Option<Duration>,
+    ringbuffer_sizes: BTreeMap<String, u32>,
+    net_msg_filter: Option<NetMsgFilter>,
+    net_msg_metadata_only: bool,
+    reorder_window: Option<Duration>,
+    capture: Option<PathBuf>,
+}
+
+/// A bitcoind process with its pid resolved
+struct TargetConfig {
+    label: String,
+    pid: i32,
+    path: PathBuf,
+    /// The selected tracepoints the binary has a probe for
+    tracepoints: Vec<Tracepoint<'static>>,
+    /// Re-attach to the restarted bitcoind found through this pid source
+    supervisor: Option<PidSource>,
+    /// The given unit of the block connection times
+    connection_time_unit: Option<ConnectionTimeUnit>,
+    /// The build of the binary
+    build: TargetBuild,
+}
+
+/// A running tap returned by [`BitcoinTap::attach`]. Dropping the handle
+/// stops the tap and waits for it to detach.
+pub struct TapHandle {
+    /// The queue that you read events off of
+    rx: EventReceiver,
+
+    /// Tells the tap thread to stop
+    stop: Arc<AtomicBool>,
+
+    /// Hands new P2P message filters to the tap thread
+    filter_tx: mpsc::Sender<NetMsgFilter>,
+
+    thread: Option<JoinHandle<()>>,
+}
+
+impl TapHandle {
+    /// The event stream. It ends after the final [`TapMsg::Detached`]
+    /// without a label.
+    pub fn events(&mut self) -> &mut EventReceiver {
+        &mut self.rx
+    }
+
+    /// Ask the tap to stop. The tap detaches the uprobes, hands out the
+    /// events left in the ring buffers and sends a final [`TapMsg::Detached`].
+    /// This doesn't wait for it, use [`Self::join`] for that.
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+
+    /// Wait for the tap to stop. The remaining events can still be read from
+    /// [`Self::events`] afterwards.
+    pub fn join(&mut self) {
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                log::error!("The tap thread panicked");
+            }
+        }
+    }
+
+    /// The state of the event queue, including the dropped events
+    pub fn stats(&self) -> QueueStats {
+        self.rx.stats()
+    }
+
+    /// Replace the P2P message filter without re-attaching. The tap applies
+    /// it within its next poll, errors are sent as [`TapMsg::Error`].
+    pub fn set_net_msg_filter(&self, filter: NetMsgFilter) -> Result<(), RuntimeError> {
+        filter.validate()?;
+        self.filter_tx
+            .send(filter)
+            .map_err(|_| RuntimeError::Stopped)
+    }
+
+    /// Is the tap still running?
+    pub fn is_running(&self) -> bool {
+        self.thread
+            .as_ref()
+            .is_some_and(|thread| !thread.is_finished())
+    }
+}
+
+impl Drop for TapHandle {
+    fn drop(&mut self) {
+        self.stop();
+        // Nobody reads the events anymore, don't let the tap block on them
+        self.rx.close();
+        self.join();
+    }
+}
+
+pub(crate) fn bitcoind_pid(method: &PidSource, path: &Path) -> Result<i32, RuntimeError> {
     match method {
         PidSource::Manual(pid) => {
             log::info!("Using bitcoind PID={} specified via option", pid);
@@ -146,11 +713,7 @@ fn bitcoind_pid(method: &PidSource) -> Result<i32, RuntimeError> {
                 path.display()
             );
 
-            let file = File::open(&path)?;
-            let mut reader = BufReader::new(file);
-            let mut content = String::new();
-            reader.read_to_string(&mut content)?;
-            let pid: i32 = content.trim().parse()?;
+            let pid = read_pid_file(path)?;
 
             log::info!("Using bitcoind PID={} read from {}", pid, path.display());
 
@@ -158,131 +721,319 @@ fn bitcoind_pid(method: &PidSource) -> Result<i32, RuntimeError> {
         }
 
         PidSource::DefaultPid => Ok(DEFAULT_PID),
+
+        PidSource::Auto | PidSource::Container(_) | PidSource::Cgroup(_) => {
+            Ok(find_bitcoind(method, path)?.pid)
+        }
     }
 }
 
-fn handle_net_conn_closed(data: &[u8], tx: &mut mpsc::Sender<TapMsg>) -> i32 {
+/// Look up the bitcoind process of the pid sources that find it in `/proc`
+pub(crate) fn find_bitcoind(
+    method: &PidSource,
+    path: &Path,
+) -> Result<BitcoindProcess, RuntimeError> {
+    match method {
+        PidSource::Container(pid) => container_bitcoind(*pid),
+        PidSource::Cgroup(cgroup) => cgroup_bitcoind(cgroup),
+        _ => auto_bitcoind(path),
+    }
+}
+
+fn read_pid_file(path: &Path) -> Result<i32, RuntimeError> {
+    let file = File::open(path)?;
+    let mut reader = BufReader::new(file);
+    let mut content = String::new();
+    reader.read_to_string(&mut content)?;
+    Ok(content.trim().parse()?)
+}
+
+/// Look for a restarted bitcoind process and its binary. The
+/// [`PidSource::File`] is re-read and the [`PidSource::Cgroup`] or
+/// [`PidSource::Container`] searched again, otherwise we look for a new
+/// process executing the binary. `taken` are the old pid and the pids of the
+/// other targets.
+fn restarted_bitcoind(source: &PidSource, path: &Path, taken: &[i32]) -> Option<(i32, PathBuf)> {
+    let (pid, path) = match source {
+        PidSource::File(pid_file) => (read_pid_file(pid_file).ok()?, path.to_owned()),
+        PidSource::Cgroup(cgroup) => {
+            let process = cgroup_bitcoind(cgroup).ok()?;
+            (process.pid, process.path)
+        }
+        // Only without a cgroup v2, see BitcoinTap::config. Finds a bitcoind
+        // restarted within the container, as long as the container runs.
+        PidSource::Container(container) => {
+            let process = container_bitcoind(*container).ok()?;
+            (process.pid, process.path)
+        }
+        PidSource::Manual(_) | PidSource::DefaultPid | PidSource::Auto => {
+            let pid = pids_executing(path)
+                .into_iter()
+                .find(|pid| !taken.contains(pid))?;
+            (pid, path.to_owned())
+        }
+    };
+    (!taken.contains(&pid) && process_alive(pid)).then_some((pid, path))
+}
+
+fn handle_net_conn_closed(data: &[u8], tx: &EventSink) -> i32 {
     let closed = ClosedConnection::from_bytes(data);
-    tx.send(TapMsg::event(EventMsg::new(Event::Conn(ConnectionMsg {
-        event: Some(ConnectionEvent::Closed(closed.into())),
-    }))))
+    tx.send_event(
+        closed.header,
+        Event::Conn(ConnectionMsg {
+            event: Some(ConnectionEvent::Closed(closed.into())),
+        }),
+    )
     .map_or(RINGBUFF_CALLBACK_PUBLISH_ERROR, |_| RINGBUFF_CALLBACK_OK)
 }
 
-fn handle_net_conn_outbound(data: &[u8], tx: &mut mpsc::Sender<TapMsg>) -> i32 {
+fn handle_net_conn_outbound(data: &[u8], tx: &EventSink) -> i32 {
     log::info!("outbound conn!!");
     let outbound = OutboundConnection::from_bytes(data);
-    tx.send(TapMsg::event(EventMsg::new(Event::Conn(ConnectionMsg {
-        event: Some(ConnectionEvent::Outbound(outbound.into())),
-    }))))
+    tx.send_event(
+        outbound.header,
+        Event::Conn(ConnectionMsg {
+            event: Some(ConnectionEvent::Outbound(outbound.into())),
+        }),
+    )
     .map_or(RINGBUFF_CALLBACK_PUBLISH_ERROR, |_| RINGBUFF_CALLBACK_OK)
 }
 
-fn handle_net_conn_inbound(data: &[u8], tx: &mut mpsc::Sender<TapMsg>) -> i32 {
+fn handle_net_conn_inbound(data: &[u8], tx: &EventSink) -> i32 {
     let inbound = InboundConnection::from_bytes(data);
-    tx.send(TapMsg::event(EventMsg::new(Event::Conn(ConnectionMsg {
-        event: Some(ConnectionEvent::Inbound(inbound.into())),
-    }))))
+    tx.send_event(
+        inbound.header,
+        Event::Conn(ConnectionMsg {
+            event: Some(ConnectionEvent::Inbound(inbound.into())),
+        }),
+    )
     .map_or(RINGBUFF_CALLBACK_PUBLISH_ERROR, |_| RINGBUFF_CALLBACK_OK)
 }
 
-fn handle_net_conn_inbound_evicted(data: &[u8], tx: &mut mpsc::Sender<TapMsg>) -> i32 {
+fn handle_net_conn_inbound_evicted(data: &[u8], tx: &EventSink) -> i32 {
     let evicted = ClosedConnection::from_bytes(data);
-    tx.send(TapMsg::event(EventMsg::new(Event::Conn(ConnectionMsg {
-        event: Some(ConnectionEvent::InboundEvicted(evicted.into())),
-    }))))
+    tx.send_event(
+        evicted.header,
+        Event::Conn(ConnectionMsg {
+            event: Some(ConnectionEvent::InboundEvicted(evicted.into())),
+        }),
+    )
     .map_or(RINGBUFF_CALLBACK_PUBLISH_ERROR, |_| RINGBUFF_CALLBACK_OK)
 }
 
-fn handle_net_conn_misbehaving(data: &[u8], tx: &mpsc::Sender<TapMsg>) -> i32 {
+fn handle_net_conn_misbehaving(data: &[u8], tx: &EventSink) -> i32 {
     let misbehaving = MisbehavingConnection::from_bytes(data);
-    tx.send(TapMsg::event(EventMsg::new(Event::Conn(ConnectionMsg {
-        event: Some(ConnectionEvent::Misbehaving(misbehaving.into())),
-    }))))
+    tx.send_event(
+        misbehaving.header,
+        Event::Conn(ConnectionMsg {
+            event: Some(ConnectionEvent::Misbehaving(misbehaving.into())),
+        }),
+    )
     .map_or(RINGBUFF_CALLBACK_PUBLISH_ERROR, |_| RINGBUFF_CALLBACK_OK)
 }
 
-fn handle_net_message(data: &[u8], tx: &mpsc::Sender<TapMsg>) -> i32 {
+fn handle_net_message(data: &[u8], tx: &EventSink) -> i32 {
     let message = P2PMessage::from_bytes(data);
     let protobuf_message = match message.decode_to_protobuf_network_message() {
         Ok(msg) => msg.into(),
         Err(e) => {
-            log::warn!("Could not parse P2P msg with size={}: {}", data.len(), e);
-            return RINGBUFF_CALLBACK_UNABLE_TO_PARSE_P2P_MSG;
+            log::debug!("Could not parse P2P msg with size={}: {}", data.len(), e);
+            net_msg::message::Msg::DecodeFailure(e.to_protobuf_decode_failure(&message.payload))
         }
     };
-    tx.send(TapMsg::event(EventMsg::new(Event::Msg(net_msg::Message {
-        meta: message.meta.create_protobuf_metadata(),
-        msg: Some(protobuf_message),
-    }))))
+    tx.send_event(
+        message.header,
+        Event::Msg(net_msg::Message {
+            meta: message.meta.create_protobuf_metadata(),
+            msg: Some(protobuf_message),
+        }),
+    )
     .map_or(RINGBUFF_CALLBACK_PUBLISH_ERROR, |_| RINGBUFF_CALLBACK_OK)
 }
 
-/*
-fn handle_addrman_new(data: &[u8], tx: &mut mpsc::Sender<EventMsg>) -> i32 {
+fn handle_net_message_meta(data: &[u8], tx: &EventSink) -> i32 {
+    let message = P2PMessage::from_bytes(data);
+    tx.send_event(
+        message.header,
+        Event::Msg(net_msg::Message {
+            meta: message.meta.create_protobuf_metadata(),
+            msg: None,
+        }),
+    )
+    .map_or(RINGBUFF_CALLBACK_PUBLISH_ERROR, |_| RINGBUFF_CALLBACK_OK)
+}
+
+fn handle_addrman_new(data: &[u8], tx: &EventSink) -> i32 {
     let new = AddrmanInsertNew::from_bytes(data);
-    tx.send(EventMsg::new(Event::Addrman(AddrmanMsg {
-        event: Some(AddrmanEvent::New(new.into())),
-    })))
-    .map_or(
-        RINGBUFF_CALLBACK_PUBLISH_ERROR,
-        |_| RINGBUFF_CALLBACK_OK,
+    tx.send_event(
+        new.header,
+        Event::Addrman(AddrmanMsg {
+            event: Some(AddrmanEvent::New(new.into())),
+        }),
     )
+    .map_or(RINGBUFF_CALLBACK_PUBLISH_ERROR, |_| RINGBUFF_CALLBACK_OK)
 }
 
-fn handle_addrman_tried(data: &[u8], tx: &mut mpsc::Sender<EventMsg>) -> i32 {
+fn handle_addrman_tried(data: &[u8], tx: &EventSink) -> i32 {
     let tried = AddrmanInsertTried::from_bytes(data);
-    tx.send(EventMsg::new(Event::Addrman(addrman::AddrmanEvent {
-        event: Some(addrman::addrman_event::Event::Tried(tried.into())),
-    })))
-    .map_or(
-        RINGBUFF_CALLBACK_PUBLISH_ERROR,
-        |_| RINGBUFF_CALLBACK_OK,
+    tx.send_event(
+        tried.header,
+        Event::Addrman(AddrmanMsg {
+            event: Some(AddrmanEvent::Tried(tried.into())),
+        }),
     )
+    .map_or(RINGBUFF_CALLBACK_PUBLISH_ERROR, |_| RINGBUFF_CALLBACK_OK)
 }
-*/
 
-fn handle_mempool_added(data: &[u8], tx: &mut mpsc::Sender<TapMsg>) -> i32 {
+fn handle_mempool_added(data: &[u8], tx: &EventSink) -> i32 {
     let added = MempoolAdded::from_bytes(data);
-    tx.send(TapMsg::event(EventMsg::new(Event::Mempool(MempoolMsg {
-        event: Some(MempoolEvent::Added(added.into())),
-    }))))
+    tx.send_event(
+        added.header,
+        Event::Mempool(MempoolMsg {
+            event: Some(MempoolEvent::Added(added.into())),
+        }),
+    )
     .map_or(RINGBUFF_CALLBACK_PUBLISH_ERROR, |_| RINGBUFF_CALLBACK_OK)
 }
 
-fn handle_mempool_removed(data: &[u8], tx: &mut mpsc::Sender<TapMsg>) -> i32 {
+fn handle_mempool_removed(data: &[u8], tx: &EventSink) -> i32 {
     let removed = MempoolRemoved::from_bytes(data);
-    tx.send(TapMsg::event(EventMsg::new(Event::Mempool(MempoolMsg {
-        event: Some(MempoolEvent::Removed(removed.into())),
-    }))))
+    tx.send_event(
+        removed.header,
+        Event::Mempool(MempoolMsg {
+            event: Some(MempoolEvent::Removed(removed.into())),
+        }),
+    )
     .map_or(RINGBUFF_CALLBACK_PUBLISH_ERROR, |_| RINGBUFF_CALLBACK_OK)
 }
 
-fn handle_mempool_replaced(data: &[u8], tx: &mut mpsc::Sender<TapMsg>) -> i32 {
+fn handle_mempool_replaced(data: &[u8], tx: &EventSink) -> i32 {
     let replaced = MempoolReplaced::from_bytes(data);
-    tx.send(TapMsg::event(EventMsg::new(Event::Mempool(
-        mempool::MempoolEvent {
+    tx.send_event(
+        replaced.header,
+        Event::Mempool(mempool::MempoolEvent {
             event: Some(mempool::mempool_event::Event::Replaced(replaced.into())),
-        },
-    ))))
+        }),
+    )
     .map_or(RINGBUFF_CALLBACK_PUBLISH_ERROR, |_| RINGBUFF_CALLBACK_OK)
 }
 
-fn handle_mempool_rejected(data: &[u8], tx: &mut Sender<TapMsg>) -> i32 {
+fn handle_mempool_rejected(data: &[u8], tx: &EventSink) -> i32 {
     let rejected = MempoolRejected::from_bytes(data);
-    tx.send(TapMsg::event(EventMsg::new(Event::Mempool(MempoolMsg {
-        event: Some(MempoolEvent::Rejected(rejected.into())),
-    }))))
+    tx.send_event(
+        rejected.header,
+        Event::Mempool(MempoolMsg {
+            event: Some(MempoolEvent::Rejected(rejected.into())),
+        }),
+    )
     .map_or(RINGBUFF_CALLBACK_PUBLISH_ERROR, |_| RINGBUFF_CALLBACK_OK)
 }
 
-fn handle_validation_block_connected(data: &[u8], tx: &mut mpsc::Sender<TapMsg>) -> i32 {
+fn handle_validation_block_connected(data: &[u8], tx: &EventSink) -> i32 {
     let connected = ValidationBlockConnected::from_bytes(data);
-    tx.send(TapMsg::event(EventMsg::new(Event::Validation(
-        ValidationMsg {
-            event: Some(ValidationEvent::BlockConnected(connected.into())),
-        },
-    ))))
+    let header = connected.header;
+    let mut block_connected = BlockConnected::from(connected);
+    // Depending on its version, bitcoind passes µs or ns
+    if let Some(build) = tx.build(header.target) {
+        block_connected.connection_time = build
+            .connection_time_unit
+            .to_micros(block_connected.connection_time);
+        block_connected.bitcoind_version = build.version;
+    }
+    tx.send_event(
+        header,
+        Event::Validation(ValidationMsg {
+            event: Some(ValidationEvent::BlockConnected(block_connected)),
+        }),
+    )
+    .map_or(RINGBUFF_CALLBACK_PUBLISH_ERROR, |_| RINGBUFF_CALLBACK_OK)
+}
+
+fn handle_utxocache_flush(data: &[u8], tx: &EventSink) -> i32 {
+    let flush = UtxocacheFlush::from_bytes(data);
+    tx.send_event(
+        flush.header,
+        Event::Utxocache(UtxocacheMsg {
+            event: Some(UtxocacheEvent::Flush(flush.into())),
+        }),
+    )
+    .map_or(RINGBUFF_CALLBACK_PUBLISH_ERROR, |_| RINGBUFF_CALLBACK_OK)
+}
+
+fn handle_utxocache_add(data: &[u8], tx: &EventSink) -> i32 {
+    let coin = UtxocacheCoin::from_bytes(data);
+    tx.send_event(
+        coin.header,
+        Event::Utxocache(UtxocacheMsg {
+            event: Some(UtxocacheEvent::Add(coin.into())),
+        }),
+    )
+    .map_or(RINGBUFF_CALLBACK_PUBLISH_ERROR, |_| RINGBUFF_CALLBACK_OK)
+}
+
+fn handle_utxocache_spent(data: &[u8], tx: &EventSink) -> i32 {
+    let coin = UtxocacheCoin::from_bytes(data);
+    tx.send_event(
+        coin.header,
+        Event::Utxocache(UtxocacheMsg {
+            event: Some(UtxocacheEvent::Spent(coin.into())),
+        }),
+    )
+    .map_or(RINGBUFF_CALLBACK_PUBLISH_ERROR, |_| RINGBUFF_CALLBACK_OK)
+}
+
+fn handle_utxocache_uncache(data: &[u8], tx: &EventSink) -> i32 {
+    let coin = UtxocacheCoin::from_bytes(data);
+    tx.send_event(
+        coin.header,
+        Event::Utxocache(UtxocacheMsg {
+            event: Some(UtxocacheEvent::Uncache(coin.into())),
+        }),
+    )
+    .map_or(RINGBUFF_CALLBACK_PUBLISH_ERROR, |_| RINGBUFF_CALLBACK_OK)
+}
+
+fn handle_coin_selection_selected_coins(data: &[u8], tx: &EventSink) -> i32 {
+    let selected = CoinSelectionSelectedCoins::from_bytes(data);
+    tx.send_event(
+        selected.header,
+        Event::CoinSelection(CoinSelectionMsg {
+            event: Some(CoinSelectionEvent::SelectedCoins(selected.into())),
+        }),
+    )
+    .map_or(RINGBUFF_CALLBACK_PUBLISH_ERROR, |_| RINGBUFF_CALLBACK_OK)
+}
+
+fn handle_coin_selection_normal_create_tx(data: &[u8], tx: &EventSink) -> i32 {
+    let created = CoinSelectionNormalCreateTx::from_bytes(data);
+    tx.send_event(
+        created.header,
+        Event::CoinSelection(CoinSelectionMsg {
+            event: Some(CoinSelectionEvent::NormalCreateTx(created.into())),
+        }),
+    )
+    .map_or(RINGBUFF_CALLBACK_PUBLISH_ERROR, |_| RINGBUFF_CALLBACK_OK)
+}
+
+fn handle_coin_selection_attempting_aps_create_tx(data: &[u8], tx: &EventSink) -> i32 {
+    let attempting = CoinSelectionAttemptingApsCreateTx::from_bytes(data);
+    tx.send_event(
+        attempting.header,
+        Event::CoinSelection(CoinSelectionMsg {
+            event: Some(CoinSelectionEvent::AttemptingApsCreateTx(attempting.into())),
+        }),
+    )
+    .map_or(RINGBUFF_CALLBACK_PUBLISH_ERROR, |_| RINGBUFF_CALLBACK_OK)
+}
+
+fn handle_coin_selection_aps_create_tx(data: &[u8], tx: &EventSink) -> i32 {
+    let created = CoinSelectionApsCreateTx::from_bytes(data);
+    tx.send_event(
+        created.header,
+        Event::CoinSelection(CoinSelectionMsg {
+            event: Some(CoinSelectionEvent::ApsCreateTx(created.into())),
+        }),
+    )
     .map_or(RINGBUFF_CALLBACK_PUBLISH_ERROR, |_| RINGBUFF_CALLBACK_OK)
 }
 
@@ -305,203 +1056,483 @@ pub fn find_map<'obj>(object: &'obj Object, name: &str) -> Result<Map<'obj>, Run
     }
 }
 
-fn ebpf_thread(
-    pid: i32,
-    debug: bool,
-    tx: mpsc::Sender<TapMsg>,
-    path: PathBuf,
+/// Filter out the tracepoints the binary has no USDT probe for. These are
+/// reported on the event queue instead of failing the whole attach.
+fn available_tracepoints(
+    tracepoints: Vec<Tracepoint<'static>>,
+    probes: &[UsdtProbe],
+    path: &Path,
+    tx: &EventSender,
+) -> Vec<Tracepoint<'static>> {
+    tracepoints
+        .into_iter()
+        .filter(|tracepoint| {
+            if probes.iter().any(|probe| probe.matches(tracepoint)) {
+                return true;
+            }
+            log::warn!(
+                "'{}' has no tracepoint {}. Skipping it.",
+                path.display(),
+                tracepoint
+            );
+            let _ = tx.send(TapMsg::Error(RuntimeError::ProbeNotFound {
+                provider: tracepoint.context.to_string(),
+                name: tracepoint.name.to_string(),
+                path: path.to_owned(),
+            }));
+            false
+        })
+        .collect()
+}
+
+/// The ring buffer sizes set on load: the configured ones, but the unused
+/// payload ring buffers get a single page when only the metadata of the P2P
+/// messages is recorded
+fn effective_ringbuffer_sizes(
+    sizes: &BTreeMap<String, u32>,
+    net_msg_metadata_only: bool,
+) -> BTreeMap<String, u32> {
+    let mut sizes = sizes.clone();
+    if net_msg_metadata_only {
+        for name in NET_MSG_PAYLOAD_RINGBUFFERS {
+            sizes.insert(name.to_string(), MIN_RINGBUFFER_SIZE);
+        }
+    }
+    sizes
+}
+
+/// Resize the ring buffers before they are created on load
+fn resize_ringbuffers(
+    object: &mut OpenObject,
+    sizes: &BTreeMap<String, u32>,
 ) -> Result<(), RuntimeError> {
-    let mut skel_builder = tracing::TracingSkelBuilder::default();
-    skel_builder.obj_builder.debug(debug);
-
-    let mut uninit = MaybeUninit::uninit();
-    log::info!("Opening BPF skeleton with debug={}..", debug);
-    let open_skel: tracing::OpenTracingSkel = skel_builder.open(&mut uninit).unwrap();
-    log::info!("Loading BPF functions and maps into kernel..");
-    let skel: tracing::TracingSkel = open_skel.load()?;
-    let obj = skel.object();
-
-    let mut active_tracepoints = vec![];
-    let mut ringbuff_builder = RingBufferBuilder::new();
-
-    // P2P net msgs tracepoints
-    let map_net_msg_small = find_map(&obj, "net_msg_small")?;
-    let map_net_msg_medium = find_map(&obj, "net_msg_medium")?;
-    let map_net_msg_large = find_map(&obj, "net_msg_large")?;
-    let map_net_msg_huge = find_map(&obj, "net_msg_huge")?;
-
-    // net tracepoints
-    {
-        // TODO: selectively enable these
-        active_tracepoints.extend(&TRACEPOINTS_NET_MESSAGE);
-
-        let mut tx2 = tx.clone();
-        ringbuff_builder.add(&map_net_msg_small, move |data| {
-            handle_net_message(data, &mut tx2)
-        })?;
+    for (name, bytes) in sizes {
+        let mut map = object
+            .maps_mut()
+            .find(|map| map.name() == name.as_str())
+            .ok_or_else(|| RuntimeError::NoSuchBPFMap(name.clone()))?;
+        map.set_max_entries(*bytes)?;
+        log::info!(
+            "Set the size of the ring buffer {} to {} bytes",
+            name,
+            bytes
+        );
+    }
+    Ok(())
+}
 
-        let mut tx2 = tx.clone();
-        ringbuff_builder.add(&map_net_msg_medium, move |data| {
-            handle_net_message(data, &mut tx2)
-        })?;
+/// Handles the raw bytes of an event read from a BPF ring buffer
+pub(crate) type RingBufferHandler = fn(&[u8], &EventSink) -> i32;
+
+/// The handler for the events in the BPF ring buffer with the given name
+pub(crate) fn ringbuffer_handler(name: &str) -> Option<RingBufferHandler> {
+    let handler: RingBufferHandler = match name {
+        "net_msg_small" | "net_msg_medium" | "net_msg_large" | "net_msg_huge" => handle_net_message,
+        "net_msg_meta" => handle_net_message_meta,
+        "net_conn_inbound" => handle_net_conn_inbound,
+        "net_conn_outbound" => handle_net_conn_outbound,
+        "net_conn_closed" => handle_net_conn_closed,
+        "net_conn_inbound_evicted" => handle_net_conn_inbound_evicted,
+        "net_conn_misbehaving" => handle_net_conn_misbehaving,
+        "mempool_added" => handle_mempool_added,
+        "mempool_removed" => handle_mempool_removed,
+        "mempool_replaced" => handle_mempool_replaced,
+        "mempool_rejected" => handle_mempool_rejected,
+        "validation_block_connected" => handle_validation_block_connected,
+        "addrman_insert_new" => handle_addrman_new,
+        "addrman_insert_tried" => handle_addrman_tried,
+        "utxocache_flush" => handle_utxocache_flush,
+        "utxocache_add" => handle_utxocache_add,
+        "utxocache_spent" => handle_utxocache_spent,
+        "utxocache_uncache" => handle_utxocache_uncache,
+        "coin_selection_selected_coins" => handle_coin_selection_selected_coins,
+        "coin_selection_normal_create_tx" => handle_coin_selection_normal_create_tx,
+        "coin_selection_attempting_aps_create_tx" => handle_coin_selection_attempting_aps_create_tx,
+        "coin_selection_aps_create_tx" => handle_coin_selection_aps_create_tx,
+        _ => return None,
+    };
+    Some(handler)
+}
 
-        let mut tx2 = tx.clone();
-        ringbuff_builder.add(&map_net_msg_large, move |data| {
-            handle_net_message(data, &mut tx2)
-        })?;
+/// Attach the BPF programs of the tracepoints to the bitcoind process. The
+/// uprobes are detached again when the returned links are dropped.
+/// The `target` index is passed to the BPF programs as USDT cookie.
+fn attach_tracepoints(
+    obj: &Object,
+    tracepoints: &[Tracepoint],
+    pid: i32,
+    path: &Path,
+    target: usize,
+) -> Result<Vec<Link>, RuntimeError> {
+    let mut links = Vec::new();
+    for tracepoint in tracepoints {
+        let prog = find_prog_mut(obj, tracepoint.function)?;
+        let opts = UsdtOpts {
+            cookie: target as u64,
+            ..Default::default()
+        };
+        let link = prog
+            .attach_usdt_with_opts(pid, path, tracepoint.context, tracepoint.name, opts)
+            .map_err(|error| RuntimeError::AttachFailed {
+                tracepoint: tracepoint.to_string(),
+                pid,
+                error,
+            })?;
+        links.push(link);
+        log::info!(
+            "hooked the BPF script function {} up to the tracepoint {}:{} of '{}' with PID={}",
+            tracepoint.function,
+            tracepoint.context,
+            tracepoint.name,
+            path.display(),
+            pid
+        );
+    }
+    Ok(links)
+}
 
-        let mut tx2 = tx.clone();
-        ringbuff_builder.add(&map_net_msg_huge, move |data| {
-            handle_net_message(data, &mut tx2)
-        })?;
+fn ebpf_thread(
+    config: TapConfig,
+    stop: Arc<AtomicBool>,
+    filters: mpsc::Receiver<NetMsgFilter>,
+    tx: EventSender,
+) -> Result<(), RuntimeError> {
+    let mut runtime = Runtime::load(config, tx)?;
+
+    while !stop.load(Ordering::Relaxed) {
+        for filter in filters.try_iter() {
+            if let Err(e) = runtime.set_net_msg_filter(&filter) {
+                runtime.send(TapMsg::Error(e));
+            }
+        }
+        runtime.poll(Duration::from_millis(100))?;
     }
 
-    // P2P connection tracepoints
-    let map_net_conn_inbound = find_map(&obj, "net_conn_inbound")?;
-    let map_net_conn_outbound = find_map(&obj, "net_conn_outbound")?;
-    let map_net_conn_closed = find_map(&obj, "net_conn_closed")?;
-    let map_net_conn_inbound_evicted = find_map(&obj, "net_conn_inbound_evicted")?;
-    let map_net_conn_misbehaving = find_map(&obj, "net_conn_misbehaving")?;
+    log::info!("Stopping. Detaching from {}..", runtime.target_labels());
+    runtime.detach()
+}
 
-    // connection tracepoints
-    {
-        // TODO: select individual connection tracepoints
-        active_tracepoints.extend(&TRACEPOINTS_NET_CONN);
+/// A bitcoind process the tap is attached to. Its index in
+/// [`Runtime::targets`] is the USDT cookie of its uprobes.
+struct AttachedTarget {
+    label: String,
+    pid: i32,
+    path: PathBuf,
+    /// The tracepoints with an active ring buffer the binary has a probe for
+    tracepoints: Vec<Tracepoint<'static>>,
+    supervisor: Option<PidSource>,
+    /// The given unit of the block connection times
+    connection_time_unit: Option<ConnectionTimeUnit>,
+    /// Empty while a supervised target waits for bitcoind to restart
+    links: Vec<Link>,
+}
 
-        let mut tx2 = tx.clone();
-        ringbuff_builder.add(&map_net_conn_inbound, move |data| {
-            handle_net_conn_inbound(data, &mut tx2)
-        })?;
+/// The BPF object loaded into the kernel with the tracepoints attached to
+/// bitcoind. The uprobes are detached when this is dropped.
+pub(crate) struct Runtime {
+    targets: Vec<AttachedTarget>,
+    ring_buffers: ManuallyDrop<RingBuffer<'static>>,
+    skel: ManuallyDrop<tracing::TracingSkel<'static>>,
+    /// The storage the skeleton lives in. Freed on drop.
+    object: *mut MaybeUninit<OpenObject>,
+
+    /// The names of the active ring buffers
+    ringbuffers: Vec<&'static str>,
+    stats_interval: Option<Duration>,
+    tx: EventSender,
+    /// The ring buffer handlers' way into `tx`
+    sink: EventSink,
+    /// Where the ring buffer records are recorded to
+    capture: Option<Arc<Mutex<CaptureWriter>>>,
+
+    last_event_timestamp: SystemTime,
+    has_warned_about_no_events: bool,
+    has_errored_about_no_events: bool,
+    last_pid_check: Instant,
+    last_stats: Instant,
+}
 
-        let mut tx2 = tx.clone();
-        ringbuff_builder.add(&map_net_conn_outbound, move |data| {
-            handle_net_conn_outbound(data, &mut tx2)
-        })?;
+impl Runtime {
+    /// Load the BPF object into the kernel and attach to bitcoind
+    pub(crate) fn load(config: TapConfig, tx: EventSender) -> Result<Self, RuntimeError> {
+        let object = Box::into_raw(Box::new(MaybeUninit::uninit()));
+        // SAFETY: `object` is only freed after the skeleton borrowing it is
+        // dropped, either below or in `Drop`.
+        let storage: &'static mut MaybeUninit<OpenObject> = unsafe { &mut *object };
+
+        match Self::load_skel(storage, config, tx) {
+            Ok(mut runtime) => {
+                runtime.object = object;
+                Ok(runtime)
+            }
+            Err(e) => {
+                // SAFETY: the skeleton was dropped in `load_skel`
+                drop(unsafe { Box::from_raw(object) });
+                Err(e)
+            }
+        }
+    }
 
-        let mut tx2 = tx.clone();
-        ringbuff_builder.add(&map_net_conn_closed, move |data| {
-            handle_net_conn_closed(data, &mut tx2)
+    fn load_skel(
+        storage: &'static mut MaybeUninit<OpenObject>,
+        config: TapConfig,
+        tx: EventSender,
+    ) -> Result<Self, RuntimeError> {
+        let TapConfig {
+            targets,
+            debug,
+            stats_interval,
+            ringbuffer_sizes,
+            net_msg_filter,
+            net_msg_metadata_only,
+            reorder_window,
+            capture,
+        } = config;
+
+        let mut skel_builder = tracing::TracingSkelBuilder::default();
+        skel_builder.obj_builder.debug(debug);
+
+        log::info!("Opening BPF skeleton with debug={}..", debug);
+        let mut open_skel: tracing::OpenTracingSkel = skel_builder.open(storage)?;
+        resize_ringbuffers(
+            open_skel.open_object_mut(),
+            &effective_ringbuffer_sizes(&ringbuffer_sizes, net_msg_metadata_only),
+        )?;
+        open_skel.maps.rodata_data.net_msg_metadata_only = net_msg_metadata_only;
+        log::info!("Loading BPF functions and maps into kernel..");
+        let skel: tracing::TracingSkel = open_skel.load().map_err(|e| match e.kind() {
+            ErrorKind::PermissionDenied => RuntimeError::PermissionDenied(e),
+            _ => RuntimeError::LibBpf(e),
         })?;
+        let obj = skel.object();
+        if let Some(filter) = net_msg_filter {
+            filter.write_to(obj)?;
+        }
 
-        let mut tx2 = tx.clone();
-        ringbuff_builder.add(&map_net_conn_inbound_evicted, move |data| {
-            handle_net_conn_inbound_evicted(data, &mut tx2)
-        })?;
+        // The tracepoints any of the targets has a probe for
+        let mut tracepoints: Vec<Tracepoint<'static>> = vec![];
+        for tracepoint in targets.iter().flat_map(|target| &target.tracepoints) {
+            if !tracepoints.contains(tracepoint) {
+                tracepoints.push(*tracepoint);
+            }
+        }
 
-        let mut tx2 = tx.clone();
-        ringbuff_builder.add(&map_net_conn_misbehaving, move |data| {
-            handle_net_conn_misbehaving(data, &mut tx2)
-        })?;
-    }
+        // Look up the ring buffers of the selected tracepoints. Tracepoints
+        // can share ring buffers, so each of them is only added once.
+        let mut active_tracepoints = vec![];
+        let mut maps: Vec<(&'static str, Map, RingBufferHandler)> = vec![];
+        for tracepoint in tracepoints {
+            let handlers: Option<Vec<_>> = tracepoint
+                .ringbuffers
+                .iter()
+                .map(|name| ringbuffer_handler(name).map(|handler| (*name, handler)))
+                .collect();
+            let Some(handlers) = handlers else {
+                log::warn!(
+                    "Tracepoint {} is not supported yet. Skipping it.",
+                    tracepoint
+                );
+                continue;
+            };
+            for (name, handler) in handlers {
+                if maps.iter().any(|(active, _, _)| *active == name) {
+                    continue;
+                }
+                if net_msg_metadata_only && NET_MSG_PAYLOAD_RINGBUFFERS.contains(&name) {
+                    continue;
+                }
+                maps.push((name, find_map(obj, name)?, handler));
+            }
+            active_tracepoints.push(tracepoint);
+        }
 
-    // validation tracepoints
-    let map_validation_block_connected = find_map(&obj, "validation_block_connected")?;
-    {
-        // TODO: select validation tracepoints
-        active_tracepoints.extend(&TRACEPOINTS_VALIDATION);
-        let mut tx2 = tx.clone();
-        ringbuff_builder.add(&map_validation_block_connected, move |data| {
-            handle_validation_block_connected(data, &mut tx2)
-        })?;
-    }
+        let ringbuffers: Vec<&'static str> = maps.iter().map(|(name, _, _)| *name).collect();
+
+        let labels: Vec<String> = targets.iter().map(|target| target.label.clone()).collect();
+        let builds: Vec<TargetBuild> = targets.iter().map(|target| target.build.clone()).collect();
+        let capture = match capture {
+            Some(path) => {
+                let targets = labels
+                    .iter()
+                    .zip(&builds)
+                    .map(|(label, build)| CaptureTarget {
+                        label: label.clone(),
+                        bitcoind_version: build.version.clone(),
+                        connection_time_unit: build.connection_time_unit,
+                    })
+                    .collect();
+                let writer = CaptureWriter::create(&path, targets)?;
+                Some(Arc::new(Mutex::new(writer)))
+            }
+            None => None,
+        };
+        let sink = EventSink::new(tx.clone(), labels, builds, reorder_window);
+        let mut ringbuff_builder = RingBufferBuilder::new();
+        for (name, map, handler) in maps.iter() {
+            let name = *name;
+            let handler = *handler;
+            let sink = sink.clone();
+            let capture = capture.clone();
+            ringbuff_builder
+                .add(map, move |data| {
+                    if let Some(capture) = &capture {
+                        capture
+                            .lock()
+                            .unwrap_or_else(|e| e.into_inner())
+                            .write(name, data);
+                    }
+                    handler(data, &sink)
+                })
+                .map_err(RuntimeError::RingBufferBuild)?;
+        }
 
-    // mempool tracepoints
-    let map_mempool_added = find_map(&obj, "mempool_added")?;
-    let map_mempool_removed = find_map(&obj, "mempool_removed")?;
-    let map_mempool_rejected = find_map(&obj, "mempool_rejected")?;
-    let map_mempool_replaced = find_map(&obj, "mempool_replaced")?;
-    {
-        // TODO: select mempool tracepoints
-        active_tracepoints.extend(&TRACEPOINTS_MEMPOOL);
+        log::info!("active tracepoints: {:?}", &active_tracepoints);
+        if active_tracepoints.is_empty() {
+            return Err(RuntimeError::NoTracepoints);
+        }
 
-        let mut tx2 = tx.clone();
-        ringbuff_builder.add(&map_mempool_added, move |data| {
-            handle_mempool_added(data, &mut tx2)
-        })?;
+        let mut attached = vec![];
+        for (index, target) in targets.into_iter().enumerate() {
+            let mut tracepoints = target.tracepoints;
+            tracepoints.retain(|tracepoint| active_tracepoints.contains(tracepoint));
+            let links = attach_tracepoints(obj, &tracepoints, target.pid, &target.path, index)?;
+            attached.push(AttachedTarget {
+                label: target.label,
+                pid: target.pid,
+                path: target.path,
+                tracepoints,
+                supervisor: target.supervisor,
+                connection_time_unit: target.connection_time_unit,
+                links,
+            });
+        }
+        let ring_buffers = ringbuff_builder
+            .build()
+            .map_err(RuntimeError::RingBufferBuild)?;
+
+        let runtime = Runtime {
+            targets: attached,
+            ring_buffers: ManuallyDrop::new(ring_buffers),
+            skel: ManuallyDrop::new(skel),
+            object: ptr::null_mut(),
+            ringbuffers,
+            stats_interval,
+            tx,
+            sink,
+            capture,
+            last_event_timestamp: SystemTime::now(),
+            has_warned_about_no_events: false,
+            has_errored_about_no_events: false,
+            last_pid_check: Instant::now(),
+            last_stats: Instant::now(),
+        };
+        log::info!(
+            "Startup successful. Starting to extract events from {}..",
+            runtime.target_labels()
+        );
+        Ok(runtime)
+    }
 
-        let mut tx2 = tx.clone();
-        ringbuff_builder.add(&map_mempool_removed, move |data| {
-            handle_mempool_removed(data, &mut tx2)
-        })?;
+    /// The labels of the targets for log messages
+    fn target_labels(&self) -> String {
+        let labels: Vec<_> = self
+            .targets
+            .iter()
+            .map(|target| format!("'{}'", target.label))
+            .collect();
+        labels.join(", ")
+    }
 
-        let mut tx2 = tx.clone();
-        ringbuff_builder.add(&map_mempool_rejected, move |data| {
-            handle_mempool_rejected(data, &mut tx2)
-        })?;
+    /// Wait up to `timeout` for events and hand them out
+    pub(crate) fn poll(&mut self, timeout: Duration) -> Result<(), RuntimeError> {
+        let result = self.ring_buffers.poll_raw(timeout);
+        self.handle_ringbuffer_result(result);
+        self.housekeeping();
+        Ok(())
+    }
 
-        let mut tx2 = tx.clone();
-        ringbuff_builder.add(&map_mempool_replaced, move |data| {
-            handle_mempool_replaced(data, &mut tx2)
-        })?;
+    /// Hand out the events available right now without waiting
+    pub(crate) fn consume(&mut self) {
+        let result = self.ring_buffers.consume_raw();
+        self.handle_ringbuffer_result(result);
+        self.flush_reordered(false);
     }
 
-    // addrman tracepoints
-    /*
-    let map_addrman_insert_new = find_map(&obj, "addrman_insert_new")?;
-    let map_addrman_insert_tried = find_map(&obj, "addrman_insert_tried")?;
-    {
-        // TODO: select addrman tracepoints
-        active_tracepoints.extend(&TRACEPOINTS_ADDRMAN);
+    /// Hand out at most `max` of the events available right now. Returns
+    /// whether events might be left in the ring buffers.
+    #[cfg(feature = "async")]
+    pub(crate) fn consume_n(&mut self, max: usize) -> bool {
+        use libbpf_rs::AsRawLibbpf;
+
+        let max = max.max(1);
+        // SAFETY: the ring buffers are alive, libbpf-rs has no wrapper for
+        // ring_buffer__consume_n
+        let result = unsafe {
+            libbpf_rs::libbpf_sys::ring_buffer__consume_n(
+                self.ring_buffers.as_libbpf_object().as_ptr(),
+                max as libbpf_rs::libbpf_sys::size_t,
+            )
+        };
+        self.handle_ringbuffer_result(result);
+        self.flush_reordered(false);
+        usize::try_from(result).is_ok_and(|consumed| consumed >= max)
+    }
 
-        let mut tx2 = tx.clone();
-        ringbuff_builder.add(&map_addrman_insert_new, move |data| {
-            handle_addrman_new(data, &mut tx2)
-        })?;
+    /// Fires when any of the ring buffers has events to consume
+    #[cfg(feature = "async")]
+    pub(crate) fn epoll_fd(&self) -> i32 {
+        self.ring_buffers.epoll_fd()
+    }
 
-        let mut tx2 = tx.clone();
-        ringbuff_builder.add(&map_addrman_insert_tried, move |data| {
-            handle_addrman_tried(data, &mut tx2)
-        })?;
+    /// Replace the P2P message filter in the BPF maps
+    pub(crate) fn set_net_msg_filter(&self, filter: &NetMsgFilter) -> Result<(), RuntimeError> {
+        filter.write_to(self.skel.object())?;
+        log::info!("Updated the P2P message filter: {:?}", filter);
+        Ok(())
     }
-    */
 
-    log::info!("active tracepoints: {:?}", &active_tracepoints);
-    if active_tracepoints.is_empty() {
-        log::error!("No tracepoints enabled.");
-        return Ok(());
+    /// The events lost in the kernel so far
+    pub(crate) fn kernel_stats(&self) -> Result<KernelStats, RuntimeError> {
+        read_kernel_stats(self.skel.object(), &self.ringbuffers)
     }
 
-    // attach tracepoints
-    let mut _links = Vec::new();
-    for tracepoint in active_tracepoints {
-        let prog = find_prog_mut(&obj, tracepoint.function)?;
-        _links.push(prog.attach_usdt(pid, &path, tracepoint.context, tracepoint.name)?);
-        log::info!(
-            "hooked the BPF script function {} up to the tracepoint {}:{} of '{}' with PID={}",
-            tracepoint.function,
-            tracepoint.context,
-            tracepoint.name,
-            &path.display(),
-            pid
-        );
+    /// Send a message to the tap's event queue
+    pub(crate) fn send(&self, msg: TapMsg) {
+        let _ = self.tx.send(msg);
+    }
+
+    /// Detach the uprobes and hand out what is left in the ring buffers
+    pub(crate) fn detach(&mut self) -> Result<(), RuntimeError> {
+        for target in self.targets.iter_mut() {
+            target.links.clear();
+        }
+        self.ring_buffers.consume()?;
+        self.flush_reordered(true);
+        Ok(())
     }
 
-    log::info!(
-        "Startup successful. Starting to extract events from '{}'..",
-        path.display()
-    );
-    let mut last_event_timestamp = SystemTime::now();
-    let mut has_warned_about_no_events = false;
-    let ring_buffers = ringbuff_builder.build()?;
+    /// Queue the events the reordering stage held back long enough
+    fn flush_reordered(&self, all: bool) {
+        if self.sink.flush(all).is_err() {
+            log::warn!("Could not publish to event queue.");
+        }
+    }
 
-    // TODO: epoll async somehow ?
-    loop {
-        match ring_buffers.poll_raw(Duration::from_millis(100)) {
+    fn handle_ringbuffer_result(&mut self, result: i32) {
+        match result {
             RINGBUFF_CALLBACK_OK => (),
             RINGBUFF_CALLBACK_PUBLISH_ERROR => {
                 log::warn!("Could not publish to event queue.")
             }
-            RINGBUFF_CALLBACK_UNABLE_TO_PARSE_P2P_MSG => {
-                log::warn!("Could not parse P2P message.")
-            }
             RINGBUFF_CALLBACK_SYSTEM_TIME_ERROR => log::warn!("SystemTimeError"),
             _other => {
                 // values >0 are the number of handled events
                 if _other <= 0 {
                     log::warn!("Unhandled ringbuffer callback error: {}", _other)
                 } else {
-                    last_event_timestamp = SystemTime::now();
-                    has_warned_about_no_events = false;
+                    self.last_event_timestamp = SystemTime::now();
+                    self.has_warned_about_no_events = false;
+                    self.has_errored_about_no_events = false;
                     log::trace!(
                         "Extracted {} events from ring buffers and published them",
                         _other
@@ -509,26 +1540,160 @@ fn ebpf_thread(
                 }
             }
         };
+    }
+
+    /// Warn about missing events and look after the bitcoind process.
+    /// Should be called regularly.
+    pub(crate) fn housekeeping(&mut self) {
+        self.flush_reordered(false);
+        if let Some(capture) = &self.capture {
+            capture.lock().unwrap_or_else(|e| e.into_inner()).flush();
+        }
+
+        let supervised = self
+            .targets
+            .iter()
+            .any(|target| target.supervisor.is_some());
         let duration_since_last_event = SystemTime::now()
-            .duration_since(last_event_timestamp)
+            .duration_since(self.last_event_timestamp)
             .expect("time went backwards");
+        // A supervised tap re-attaches to a restarted bitcoind by itself
         if duration_since_last_event >= NO_EVENTS_ERROR_DURATION {
-            log::error!(
-                "No events received in the last {:?}.",
-                NO_EVENTS_ERROR_DURATION
-            );
-            log::warn!(
-                "The bitcoind process might be down, has restarted and changed PIDs, or the network might be down."
-            );
-            log::warn!("The extractor will exit. Please restart it");
+            if !supervised && !self.has_errored_about_no_events {
+                self.has_errored_about_no_events = true;
+                log::error!(
+                    "No events received in the last {:?}.",
+                    NO_EVENTS_ERROR_DURATION
+                );
+                log::warn!(
+                    "The bitcoind process might be down, has restarted and changed PIDs, or the network might be down."
+                );
+                log::warn!(
+                    "If bitcoind restarted, restart the tap, or supervise it (--reattach) to re-attach automatically."
+                );
+            }
         } else if duration_since_last_event >= NO_EVENTS_WARN_DURATION
-            && !has_warned_about_no_events
+            && !self.has_warned_about_no_events
         {
-            has_warned_about_no_events = true;
+            self.has_warned_about_no_events = true;
             log::warn!(
                 "No events received in the last {:?}. Is bitcoind or the network down?",
                 NO_EVENTS_WARN_DURATION
             );
         }
+
+        if supervised && self.last_pid_check.elapsed() >= PID_CHECK_INTERVAL {
+            self.last_pid_check = Instant::now();
+            self.supervise();
+        }
+
+        if let Some(interval) = self.stats_interval {
+            if self.last_stats.elapsed() >= interval {
+                self.last_stats = Instant::now();
+                self.send(TapMsg::Stats(self.tx.stats()));
+                match self.kernel_stats() {
+                    Ok(stats) => self.send(TapMsg::KernelStats(stats)),
+                    Err(e) => self.send(TapMsg::Error(e)),
+                }
+            }
+        }
+    }
+
+    /// Detach when bitcoind exits and re-attach once it restarted
+    fn supervise(&mut self) {
+        for index in 0..self.targets.len() {
+            self.supervise_target(index);
+        }
+    }
+
+    fn supervise_target(&mut self, index: usize) {
+        let pids: Vec<i32> = self.targets.iter().map(|target| target.pid).collect();
+        let obj = self.skel.object();
+        let target = &mut self.targets[index];
+        let Some(pid_source) = &target.supervisor else {
+            return;
+        };
+
+        if target.links.is_empty() {
+            let Some((new_pid, path)) = restarted_bitcoind(pid_source, &target.path, &pids) else {
+                return;
+            };
+            // A restarted container has a new root
+            target.path = path;
+            // The restarted bitcoind might be a different build
+            let reattached = BitcoindBinary::read(&target.path).and_then(|binary| {
+                let tracepoints = available_tracepoints(
+                    target.tracepoints.clone(),
+                    &binary.probes,
+                    &target.path,
+                    &self.tx,
+                );
+                let links = attach_tracepoints(obj, &tracepoints, new_pid, &target.path, index)?;
+                Ok((links, binary.version))
+            });
+            match reattached {
+                Ok((links, version)) => {
+                    log::info!(
+                        "Re-attached to the restarted bitcoind '{}' with PID={}",
+                        target.label,
+                        new_pid
+                    );
+                    self.sink.set_build(
+                        index,
+                        TargetBuild::new(
+                            target.connection_time_unit,
+                            version.as_ref(),
+                            &target.path,
+                        ),
+                    );
+                    target.links = links;
+                    target.pid = new_pid;
+                    self.last_event_timestamp = SystemTime::now();
+                    let _ = self.tx.send(TapMsg::Reattached {
+                        label: target.label.clone(),
+                        pid: new_pid,
+                    });
+                }
+                Err(e) => {
+                    log::warn!(
+                        "Could not re-attach to bitcoind '{}' with PID={}: {}",
+                        target.label,
+                        new_pid,
+                        e
+                    );
+                    let _ = self.tx.send(TapMsg::Error(e));
+                }
+            }
+        } else if !process_alive(target.pid) {
+            log::warn!(
+                "bitcoind '{}' with PID={} exited. Waiting for it to restart..",
+                target.label,
+                target.pid
+            );
+            target.links.clear();
+            let label = target.label.clone();
+            // The other targets keep going
+            if self.targets.iter().all(|target| target.links.is_empty()) {
+                if let Err(e) = self.detach() {
+                    let _ = self.tx.send(TapMsg::Error(e));
+                }
+            }
+            let _ = self.tx.send(TapMsg::Detached { label: Some(label) });
+        }
+    }
+}
+
+impl Drop for Runtime {
+    fn drop(&mut self) {
+        for target in self.targets.iter_mut() {
+            target.links.clear();
+        }
+        // SAFETY: neither is used after this. The skeleton borrows `object`,
+        // so it has to be dropped before `object` is freed.
+        unsafe {
+            ManuallyDrop::drop(&mut self.ring_buffers);
+            ManuallyDrop::drop(&mut self.skel);
+            drop(Box::from_raw(self.object));
+        }
     }
 }
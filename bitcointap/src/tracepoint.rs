@@ -1,22 +1,141 @@
+use std::fmt;
 
 /// Tracepoint entry information
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Tracepoint<'a> {
     pub context: &'a str,
     pub name: &'a str,
     pub function: &'a str,
+    /// The BPF ring buffers `function` writes its events into
+    pub ringbuffers: &'a [&'a str],
 }
 
+impl fmt::Display for Tracepoint<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.context, self.name)
+    }
+}
+
+/// A group of related tracepoints that are usually enabled together
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TracepointGroup {
+    NetMessage,
+    NetConn,
+    Mempool,
+    Validation,
+    Addrman,
+    Utxocache,
+    CoinSelection,
+}
+
+impl TracepointGroup {
+    /// All tracepoint groups
+    pub const ALL: [TracepointGroup; 7] = [
+        TracepointGroup::NetMessage,
+        TracepointGroup::NetConn,
+        TracepointGroup::Mempool,
+        TracepointGroup::Validation,
+        TracepointGroup::Addrman,
+        TracepointGroup::Utxocache,
+        TracepointGroup::CoinSelection,
+    ];
+
+    /// The groups that are enabled when nothing was selected
+    pub const DEFAULT: [TracepointGroup; 4] = [
+        TracepointGroup::NetMessage,
+        TracepointGroup::NetConn,
+        TracepointGroup::Mempool,
+        TracepointGroup::Validation,
+    ];
+
+    /// The tracepoints belonging to this group
+    pub fn tracepoints(&self) -> &'static [Tracepoint<'static>] {
+        match self {
+            TracepointGroup::NetMessage => &TRACEPOINTS_NET_MESSAGE,
+            TracepointGroup::NetConn => &TRACEPOINTS_NET_CONN,
+            TracepointGroup::Mempool => &TRACEPOINTS_MEMPOOL,
+            TracepointGroup::Validation => &TRACEPOINTS_VALIDATION,
+            TracepointGroup::Addrman => &TRACEPOINTS_ADDRMAN,
+            TracepointGroup::Utxocache => &TRACEPOINTS_UTXOCACHE,
+            TracepointGroup::CoinSelection => &TRACEPOINTS_COIN_SELECTION,
+        }
+    }
+}
+
+impl fmt::Display for TracepointGroup {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TracepointGroup::NetMessage => write!(f, "net-msg"),
+            TracepointGroup::NetConn => write!(f, "net-conn"),
+            TracepointGroup::Mempool => write!(f, "mempool"),
+            TracepointGroup::Validation => write!(f, "validation"),
+            TracepointGroup::Addrman => write!(f, "addrman"),
+            TracepointGroup::Utxocache => write!(f, "utxocache"),
+            TracepointGroup::CoinSelection => write!(f, "coin-selection"),
+        }
+    }
+}
+
+/// Find one of our tracepoints by its `context` and `name`
+pub fn find_tracepoint(context: &str, name: &str) -> Option<&'static Tracepoint<'static>> {
+    TracepointGroup::ALL
+        .iter()
+        .flat_map(|group| group.tracepoints())
+        .find(|tp| tp.context == context && tp.name == name)
+}
+
+/// All BPF ring buffers in the order of their ids in the `ringbuf_stats` BPF
+/// map. Keep in sync with `enum RingbufId` in tracing.bpf.c.
+pub const RINGBUFFERS: [&str; 25] = [
+    "net_msg_small",
+    "net_msg_medium",
+    "net_msg_large",
+    "net_msg_huge",
+    "net_msg_meta",
+    "net_conn_inbound",
+    "net_conn_outbound",
+    "net_conn_closed",
+    "net_conn_inbound_evicted",
+    "net_conn_misbehaving",
+    "addrman_insert_new",
+    "addrman_insert_tried",
+    "mempool_added",
+    "mempool_removed",
+    "mempool_replaced",
+    "mempool_rejected",
+    "validation_block_connected",
+    "utxocache_flush",
+    "utxocache_add",
+    "utxocache_spent",
+    "utxocache_uncache",
+    "coin_selection_selected_coins",
+    "coin_selection_normal_create_tx",
+    "coin_selection_attempting_aps_create_tx",
+    "coin_selection_aps_create_tx",
+];
+
+/// The P2P message handlers pick the smallest ring buffer the message fits
+/// into, or `net_msg_meta` when only the metadata is recorded
+const NET_MSG_RINGBUFFERS: [&str; 5] = [
+    "net_msg_small",
+    "net_msg_medium",
+    "net_msg_large",
+    "net_msg_huge",
+    "net_msg_meta",
+];
+
 pub const TRACEPOINTS_NET_MESSAGE: [Tracepoint; 2] = [
     Tracepoint {
         context: "net",
         name: "inbound_message",
         function: "handle_net_msg_inbound",
+        ringbuffers: &NET_MSG_RINGBUFFERS,
     },
     Tracepoint {
         context: "net",
         name: "outbound_message",
         function: "handle_net_msg_outbound",
+        ringbuffers: &NET_MSG_RINGBUFFERS,
     },
 ];
 
@@ -25,26 +144,31 @@ pub const TRACEPOINTS_NET_CONN: [Tracepoint; 5] = [
         context: "net",
         name: "inbound_connection",
         function: "handle_net_conn_inbound",
+        ringbuffers: &["net_conn_inbound"],
     },
     Tracepoint {
         context: "net",
         name: "outbound_connection",
         function: "handle_net_conn_outbound",
+        ringbuffers: &["net_conn_outbound"],
     },
     Tracepoint {
         context: "net",
         name: "closed_connection",
         function: "handle_net_conn_closed",
+        ringbuffers: &["net_conn_closed"],
     },
     Tracepoint {
         context: "net",
         name: "evicted_inbound_connection",
         function: "handle_net_conn_inbound_evicted",
+        ringbuffers: &["net_conn_inbound_evicted"],
     },
     Tracepoint {
         context: "net",
         name: "misbehaving_connection",
         function: "handle_net_conn_misbehaving",
+        ringbuffers: &["net_conn_misbehaving"],
     },
 ];
 
@@ -53,21 +177,25 @@ pub const TRACEPOINTS_MEMPOOL: [Tracepoint; 4] = [
         context: "mempool",
         name: "added",
         function: "handle_mempool_added",
+        ringbuffers: &["mempool_added"],
     },
     Tracepoint {
         context: "mempool",
         name: "removed",
         function: "handle_mempool_removed",
+        ringbuffers: &["mempool_removed"],
     },
     Tracepoint {
         context: "mempool",
         name: "replaced",
         function: "handle_mempool_replaced",
+        ringbuffers: &["mempool_replaced"],
     },
     Tracepoint {
         context: "mempool",
         name: "rejected",
         function: "handle_mempool_rejected",
+        ringbuffers: &["mempool_rejected"],
     },
 ];
 
@@ -76,11 +204,13 @@ pub const TRACEPOINTS_ADDRMAN: [Tracepoint; 2] = [
         context: "addrman",
         name: "attempt_add",
         function: "handle_addrman_new",
+        ringbuffers: &["addrman_insert_new"],
     },
     Tracepoint {
         context: "addrman",
         name: "move_to_good",
         function: "handle_addrman_tried",
+        ringbuffers: &["addrman_insert_tried"],
     },
 ];
 
@@ -88,4 +218,59 @@ pub const TRACEPOINTS_VALIDATION: [Tracepoint; 1] = [Tracepoint {
     context: "validation",
     name: "block_connected",
     function: "handle_validation_block_connected",
+    ringbuffers: &["validation_block_connected"],
 }];
+
+pub const TRACEPOINTS_UTXOCACHE: [Tracepoint; 4] = [
+    Tracepoint {
+        context: "utxocache",
+        name: "flush",
+        function: "handle_utxocache_flush",
+        ringbuffers: &["utxocache_flush"],
+    },
+    Tracepoint {
+        context: "utxocache",
+        name: "add",
+        function: "handle_utxocache_add",
+        ringbuffers: &["utxocache_add"],
+    },
+    Tracepoint {
+        context: "utxocache",
+        name: "spent",
+        function: "handle_utxocache_spent",
+        ringbuffers: &["utxocache_spent"],
+    },
+    Tracepoint {
+        context: "utxocache",
+        name: "uncache",
+        function: "handle_utxocache_uncache",
+        ringbuffers: &["utxocache_uncache"],
+    },
+];
+
+pub const TRACEPOINTS_COIN_SELECTION: [Tracepoint; 4] = [
+    Tracepoint {
+        context: "coin_selection",
+        name: "selected_coins",
+        function: "handle_coin_selection_selected_coins",
+        ringbuffers: &["coin_selection_selected_coins"],
+    },
+    Tracepoint {
+        context: "coin_selection",
+        name: "normal_create_tx_internal",
+        function: "handle_coin_selection_normal_create_tx",
+        ringbuffers: &["coin_selection_normal_create_tx"],
+    },
+    Tracepoint {
+        context: "coin_selection",
+        name: "attempting_aps_create_tx",
+        function: "handle_coin_selection_attempting_aps_create_tx",
+        ringbuffers: &["coin_selection_attempting_aps_create_tx"],
+    },
+    Tracepoint {
+        context: "coin_selection",
+        name: "aps_create_tx_internal",
+        function: "handle_coin_selection_aps_create_tx",
+        ringbuffers: &["coin_selection_aps_create_tx"],
+    },
+];
@@ -0,0 +1,417 @@
+use crate::{BitcoindVersion, RuntimeError, Tracepoint, parse_bitcoind_version};
+use shared::log;
+use std::fmt;
+use std::path::Path;
+
+const ELF_MAGIC: &[u8] = b"\x7fELF";
+const ELF_CLASS_32: u8 = 1;
+const ELF_CLASS_64: u8 = 2;
+const ELF_DATA_LSB: u8 = 1;
+const ELF_DATA_MSB: u8 = 2;
+
+const STAPSDT_SECTION: &str = ".note.stapsdt";
+const STAPSDT_NOTE_OWNER: &[u8] = b"stapsdt";
+const STAPSDT_NOTE_TYPE: u32 = 3;
+
+/// A USDT probe found in the `.note.stapsdt` ELF section of a binary
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UsdtProbe {
+    pub provider: String,
+    pub name: String,
+    /// The argument specs of the probe, e.g. `-8@%rax` for a signed 8 byte
+    /// argument in the `rax` register.
+    pub args: Vec<String>,
+}
+
+impl UsdtProbe {
+    /// Does this probe belong to the tracepoint?
+    pub fn matches(&self, tracepoint: &Tracepoint) -> bool {
+        self.provider == tracepoint.context && self.name == tracepoint.name
+    }
+}
+
+impl fmt::Display for UsdtProbe {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}({})",
+            self.provider,
+            self.name,
+            self.args.join(" ")
+        )
+    }
+}
+
+/// What the tap needs to know about a bitcoind binary. The binary is read
+/// once for all of it.
+pub(crate) struct BitcoindBinary {
+    pub(crate) probes: Vec<UsdtProbe>,
+    /// `None` if the binary has no version string
+    pub(crate) version: Option<BitcoindVersion>,
+}
+
+impl BitcoindBinary {
+    pub(crate) fn read(path: &Path) -> Result<Self, RuntimeError> {
+        let binary = read_binary(path)?;
+        let probes = parse_usdt_probes(&binary)
+            .map_err(|e| RuntimeError::Elf(path.to_owned(), e.to_string()))?;
+        let version = match parse_bitcoind_version(&binary) {
+            Ok(version) => version,
+            Err(e) => {
+                log::warn!("Could not read the version of '{}': {}", path.display(), e);
+                None
+            }
+        };
+        Ok(BitcoindBinary { probes, version })
+    }
+}
+
+/// Read the whole binary at `path`
+pub(crate) fn read_binary(path: &Path) -> Result<Vec<u8>, RuntimeError> {
+    std::fs::read(path).map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => RuntimeError::BinaryNotFound(path.to_owned()),
+        _ => e.into(),
+    })
+}
+
+/// Read the USDT probes of the ELF binary at `path`
+pub fn read_usdt_probes(path: &Path) -> Result<Vec<UsdtProbe>, RuntimeError> {
+    let binary = read_binary(path)?;
+    parse_usdt_probes(&binary).map_err(|e| RuntimeError::Elf(path.to_owned(), e.to_string()))
+}
+
+/// Parse the USDT probes from the raw bytes of an ELF binary. A binary
+/// without a `.note.stapsdt` section has no probes.
+pub fn parse_usdt_probes(elf: &[u8]) -> Result<Vec<UsdtProbe>, ElfError> {
+    let elf = Elf::parse(elf)?;
+    let Some(notes) = elf.section(STAPSDT_SECTION)? else {
+        return Ok(vec![]);
+    };
+
+    let mut probes = vec![];
+    let mut offset = 0;
+    // The sizes are read from the file, so the offsets may overflow
+    while notes.len().saturating_sub(offset) >= 12 {
+        let name_size = elf.u32(notes, offset)? as usize;
+        let desc_size = elf.u32(notes, offset + 4)? as usize;
+        let note_type = elf.u32(notes, offset + 8)?;
+        let name_start = offset + 12;
+        let desc_start = add(name_start, align4(name_size)?)?;
+        let owner = slice(notes, name_start, name_size)?;
+        let desc = slice(notes, desc_start, desc_size)?;
+        offset = add(desc_start, align4(desc_size)?)?;
+
+        // the owner name includes its NUL terminator
+        if note_type != STAPSDT_NOTE_TYPE || owner.strip_suffix(b"\0") != Some(STAPSDT_NOTE_OWNER) {
+            continue;
+        }
+
+        // The description starts with the probe, base and semaphore
+        // addresses followed by the provider, name and argument strings.
+        let addresses = 3 * elf.address_size();
+        let mut strings = desc
+            .get(addresses..)
+            .ok_or(ElfError::Truncated)?
+            .split(|b| *b == 0)
+            .map(|s| String::from_utf8_lossy(s).into_owned());
+        let provider = strings.next().ok_or(ElfError::Truncated)?;
+        let name = strings.next().ok_or(ElfError::Truncated)?;
+        let args = strings
+            .next()
+            .map(|args| args.split_whitespace().map(String::from).collect())
+            .unwrap_or_default();
+
+        probes.push(UsdtProbe {
+            provider,
+            name,
+            args,
+        });
+    }
+
+    Ok(probes)
+}
+
+/// An error while parsing an ELF binary
+#[derive(Debug)]
+pub enum ElfError {
+    NotElf,
+    UnsupportedClass(u8),
+    UnsupportedEncoding(u8),
+    Truncated,
+}
+
+impl fmt::Display for ElfError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ElfError::NotElf => write!(f, "not an ELF binary"),
+            ElfError::UnsupportedClass(class) => write!(f, "unsupported ELF class {}", class),
+            ElfError::UnsupportedEncoding(data) => {
+                write!(f, "unsupported ELF data encoding {}", data)
+            }
+            ElfError::Truncated => write!(f, "truncated ELF binary"),
+        }
+    }
+}
+
+impl std::error::Error for ElfError {}
+
+/// Just enough of an ELF parser to find sections by name
+pub(crate) struct Elf<'a> {
+    data: &'a [u8],
+    is_64: bool,
+    is_le: bool,
+}
+
+impl<'a> Elf<'a> {
+    pub(crate) fn parse(data: &'a [u8]) -> Result<Self, ElfError> {
+        if data.len() < 16 || &data[..4] != ELF_MAGIC {
+            return Err(ElfError::NotElf);
+        }
+        let is_64 = match data[4] {
+            ELF_CLASS_32 => false,
+            ELF_CLASS_64 => true,
+            class => return Err(ElfError::UnsupportedClass(class)),
+        };
+        let is_le = match data[5] {
+            ELF_DATA_LSB => true,
+            ELF_DATA_MSB => false,
+            encoding => return Err(ElfError::UnsupportedEncoding(encoding)),
+        };
+        Ok(Elf { data, is_64, is_le })
+    }
+
+    fn address_size(&self) -> usize {
+        if self.is_64 { 8 } else { 4 }
+    }
+
+    fn u16(&self, data: &[u8], offset: usize) -> Result<u16, ElfError> {
+        let bytes: [u8; 2] = slice(data, offset, 2)?.try_into().unwrap();
+        Ok(if self.is_le {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        })
+    }
+
+    fn u32(&self, data: &[u8], offset: usize) -> Result<u32, ElfError> {
+        let bytes: [u8; 4] = slice(data, offset, 4)?.try_into().unwrap();
+        Ok(if self.is_le {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    }
+
+    fn u64(&self, data: &[u8], offset: usize) -> Result<u64, ElfError> {
+        let bytes: [u8; 8] = slice(data, offset, 8)?.try_into().unwrap();
+        Ok(if self.is_le {
+            u64::from_le_bytes(bytes)
+        } else {
+            u64::from_be_bytes(bytes)
+        })
+    }
+
+    /// Reads an address or offset sized field
+    fn addr(&self, data: &[u8], offset: usize) -> Result<usize, ElfError> {
+        if self.is_64 {
+            Ok(self.u64(data, offset)? as usize)
+        } else {
+            Ok(self.u32(data, offset)? as usize)
+        }
+    }
+
+    /// The section header at `index` as (name offset, file offset, size)
+    fn section_header(&self, index: usize) -> Result<(usize, usize, usize), ElfError> {
+        let (shoff, shentsize) = if self.is_64 {
+            (self.addr(self.data, 0x28)?, self.u16(self.data, 0x3a)?)
+        } else {
+            (self.addr(self.data, 0x20)?, self.u16(self.data, 0x2e)?)
+        };
+        let header = index
+            .checked_mul(shentsize as usize)
+            .ok_or(ElfError::Truncated)
+            .and_then(|offset| add(shoff, offset))?;
+        let name = self.u32(self.data, header)? as usize;
+        let (offset, size) = if self.is_64 {
+            (
+                self.addr(self.data, add(header, 0x18)?)?,
+                self.addr(self.data, add(header, 0x20)?)?,
+            )
+        } else {
+            (
+                self.addr(self.data, add(header, 0x10)?)?,
+                self.addr(self.data, add(header, 0x14)?)?,
+            )
+        };
+        Ok((name, offset, size))
+    }
+
+    /// The contents of the section with the given name, if there is one
+    pub(crate) fn section(&self, name: &str) -> Result<Option<&'a [u8]>, ElfError> {
+        let (shnum, shstrndx) = if self.is_64 {
+            (self.u16(self.data, 0x3c)?, self.u16(self.data, 0x3e)?)
+        } else {
+            (self.u16(self.data, 0x30)?, self.u16(self.data, 0x32)?)
+        };
+        let (_, strtab_offset, strtab_size) = self.section_header(shstrndx as usize)?;
+        let strtab = slice(self.data, strtab_offset, strtab_size)?;
+
+        for index in 0..shnum as usize {
+            let (name_offset, offset, size) = self.section_header(index)?;
+            let section_name = strtab
+                .get(name_offset..)
+                .and_then(|s| s.split(|b| *b == 0).next())
+                .ok_or(ElfError::Truncated)?;
+            if section_name == name.as_bytes() {
+                return Ok(Some(slice(self.data, offset, size)?));
+            }
+        }
+        Ok(None)
+    }
+}
+
+fn slice(data: &[u8], offset: usize, len: usize) -> Result<&[u8], ElfError> {
+    offset
+        .checked_add(len)
+        .and_then(|end| data.get(offset..end))
+        .ok_or(ElfError::Truncated)
+}
+
+/// Offsets past the end of the address space are past the end of the file
+fn add(a: usize, b: usize) -> Result<usize, ElfError> {
+    a.checked_add(b).ok_or(ElfError::Truncated)
+}
+
+fn align4(n: usize) -> Result<usize, ElfError> {
+    Ok(add(n, 3)? & !3)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// A stapsdt note of a probe on x86_64
+    fn note(provider: &str, name: &str, args: &str) -> Vec<u8> {
+        // The probe, base and semaphore addresses
+        let mut desc = vec![0u8; 24];
+        for s in [provider, name, args] {
+            desc.extend_from_slice(s.as_bytes());
+            desc.push(0);
+        }
+        let mut note = vec![];
+        note.extend_from_slice(&8u32.to_le_bytes());
+        note.extend_from_slice(&(desc.len() as u32).to_le_bytes());
+        note.extend_from_slice(&STAPSDT_NOTE_TYPE.to_le_bytes());
+        note.extend_from_slice(b"stapsdt\0");
+        note.extend_from_slice(&desc);
+        note.resize(note.len().next_multiple_of(4), 0);
+        note
+    }
+
+    /// A 64 bit little-endian ELF with a `.shstrtab` and a `.note.stapsdt`
+    /// section holding `notes`
+    fn elf(notes: &[u8]) -> Vec<u8> {
+        elf_with_section(".note.stapsdt", notes)
+    }
+
+    /// A 64 bit little-endian ELF with a `.shstrtab` and the section `name`
+    /// holding `data`
+    pub(crate) fn elf_with_section(name: &str, data: &[u8]) -> Vec<u8> {
+        let shstrtab = [b"\0.shstrtab\0", name.as_bytes(), b"\0"].concat();
+        let mut elf = vec![0u8; 64];
+        elf[..4].copy_from_slice(ELF_MAGIC);
+        elf[4] = ELF_CLASS_64;
+        elf[5] = ELF_DATA_LSB;
+        let shstrtab_offset = elf.len();
+        elf.extend_from_slice(&shstrtab);
+        let data_offset = elf.len();
+        elf.extend_from_slice(data);
+
+        let shoff = elf.len();
+        let sections = [
+            (0, 0, 0),
+            (1, shstrtab_offset, shstrtab.len()),
+            (11, data_offset, data.len()),
+        ];
+        for (name, offset, size) in sections {
+            let mut header = [0u8; 64];
+            header[..4].copy_from_slice(&(name as u32).to_le_bytes());
+            header[0x18..0x20].copy_from_slice(&(offset as u64).to_le_bytes());
+            header[0x20..0x28].copy_from_slice(&(size as u64).to_le_bytes());
+            elf.extend_from_slice(&header);
+        }
+        elf[0x28..0x30].copy_from_slice(&(shoff as u64).to_le_bytes());
+        elf[0x3a..0x3c].copy_from_slice(&64u16.to_le_bytes());
+        elf[0x3c..0x3e].copy_from_slice(&3u16.to_le_bytes());
+        elf[0x3e..0x40].copy_from_slice(&1u16.to_le_bytes());
+        elf
+    }
+
+    #[test]
+    fn parse_stapsdt_notes() {
+        let mut notes = note("net", "inbound_message", "-8@%rax 8@%rdx");
+        notes.extend(note("validation", "block_connected", ""));
+        let probes = parse_usdt_probes(&elf(&notes)).unwrap();
+        assert_eq!(
+            probes,
+            vec![
+                UsdtProbe {
+                    provider: "net".to_string(),
+                    name: "inbound_message".to_string(),
+                    args: vec!["-8@%rax".to_string(), "8@%rdx".to_string()],
+                },
+                UsdtProbe {
+                    provider: "validation".to_string(),
+                    name: "block_connected".to_string(),
+                    args: vec![],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn skip_other_notes() {
+        let mut notes = note("net", "inbound_message", "");
+        notes[8..12].copy_from_slice(&1u32.to_le_bytes());
+        assert_eq!(parse_usdt_probes(&elf(&notes)).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn reject_non_elf() {
+        assert!(matches!(
+            parse_usdt_probes(b"#!/bin/sh\nexec bitcoind"),
+            Err(ElfError::NotElf)
+        ));
+    }
+
+    #[test]
+    fn reject_truncated_note() {
+        let notes = note("net", "inbound_message", "");
+        let result = parse_usdt_probes(&elf(&notes[..notes.len() - 8]));
+        assert!(matches!(result, Err(ElfError::Truncated)));
+    }
+
+    #[test]
+    fn reject_overflowing_note_sizes() {
+        let mut notes = note("net", "inbound_message", "");
+        notes[..4].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(
+            parse_usdt_probes(&elf(&notes)),
+            Err(ElfError::Truncated)
+        ));
+
+        let mut notes = note("net", "inbound_message", "");
+        notes[4..8].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(
+            parse_usdt_probes(&elf(&notes)),
+            Err(ElfError::Truncated)
+        ));
+    }
+
+    #[test]
+    fn reject_overflowing_section_headers() {
+        let mut elf = elf(&note("net", "inbound_message", ""));
+        elf[0x28..0x30].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(matches!(parse_usdt_probes(&elf), Err(ElfError::Truncated)));
+    }
+}
@@ -0,0 +1,226 @@
+use crate::RuntimeError;
+use crate::usdt::{Elf, ElfError, read_binary};
+use shared::log;
+use shared::validation::ConnectionTimeUnit;
+use std::fmt;
+use std::path::Path;
+
+/// The section with the string literals of a binary
+const RODATA_SECTION: &str = ".rodata";
+
+/// `PACKAGE_STRING` starts with the `PACKAGE_NAME`
+const PACKAGE_NAME_PREFIX: &str = "Bitcoin Core ";
+
+/// The first release with tracepoints. Skips version strings of libraries
+/// linked into bitcoind.
+const MIN_MAJOR_VERSION: u32 = 22;
+
+/// The version of a bitcoind binary, from the string `bitcoind -version`
+/// prints, e.g. `v27.0.0` or `v27.99.0-0b4aa31c5a`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BitcoindVersion {
+    pub major: u32,
+    pub minor: u32,
+    /// The version string as found in the binary
+    pub build: String,
+}
+
+impl BitcoindVersion {
+    /// The unit of the connection time `validation:block_connected` passes
+    pub fn connection_time_unit(&self) -> ConnectionTimeUnit {
+        ConnectionTimeUnit::for_major_version(self.major)
+    }
+}
+
+impl fmt::Display for BitcoindVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.build)
+    }
+}
+
+/// Read the version of the bitcoind binary at `path`. `None` if it has no
+/// version string, e.g. when it is not a Bitcoin Core build.
+pub fn read_bitcoind_version(path: &Path) -> Result<Option<BitcoindVersion>, RuntimeError> {
+    let binary = read_binary(path)?;
+    parse_bitcoind_version(&binary).map_err(|e| RuntimeError::Elf(path.to_owned(), e.to_string()))
+}
+
+/// Find the version string in the raw bytes of a bitcoind ELF binary.
+/// Bitcoin Core compiles it in as string literals: `PACKAGE_STRING`, e.g.
+/// `Bitcoin Core 27.0.0`, and `CLIENT_BUILD`, the git tag like `v27.0` or
+/// `v` followed by the package version and a commit suffix for untagged
+/// builds. Other literals can look like a `v` version as well, so the
+/// `PACKAGE_STRING` decides and `CLIENT_BUILD` only adds the commit. Without
+/// a `PACKAGE_STRING`, the first `v` version is taken.
+pub fn parse_bitcoind_version(elf: &[u8]) -> Result<Option<BitcoindVersion>, ElfError> {
+    let elf = Elf::parse(elf)?;
+    let Some(rodata) = elf.section(RODATA_SECTION)? else {
+        return Ok(None);
+    };
+    let literals: Vec<&str> = rodata
+        .split(|b| *b == 0)
+        .filter_map(|literal| std::str::from_utf8(literal).ok())
+        .collect();
+    let versions = |prefix: &'static str| {
+        literals.iter().filter_map(move |literal| {
+            let version = literal.strip_prefix(prefix)?;
+            Some((version, parse_version(version)?))
+        })
+    };
+
+    let (version, (major, minor)) = match versions(PACKAGE_NAME_PREFIX).next() {
+        Some((package, numbers)) => versions("v")
+            .find(|(_, build)| *build == numbers)
+            .unwrap_or((package, numbers)),
+        None => {
+            let Some(found) = versions("v").next() else {
+                return Ok(None);
+            };
+            log::warn!(
+                "No '{}' version string found, guessing v{} from the string literals",
+                PACKAGE_NAME_PREFIX.trim_end(),
+                found.0
+            );
+            found
+        }
+    };
+    Ok(Some(BitcoindVersion {
+        major,
+        minor,
+        build: format!("v{}", version),
+    }))
+}
+
+/// Parse `<major>.<minor>[.<patch>][rc<n>][-<suffix>]` into the major and
+/// minor version
+fn parse_version(version: &str) -> Option<(u32, u32)> {
+    let (numbers, suffix) = match version.split_once('-') {
+        Some((numbers, suffix)) => (numbers, Some(suffix)),
+        None => (version, None),
+    };
+    let suffix_valid = |suffix: &str| {
+        !suffix.is_empty()
+            && suffix
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-')
+    };
+    if suffix.is_some_and(|suffix| !suffix_valid(suffix)) {
+        return None;
+    }
+    let numbers = match numbers.split_once("rc") {
+        Some((numbers, rc)) if !rc.is_empty() && rc.chars().all(|c| c.is_ascii_digit()) => numbers,
+        Some(_) => return None,
+        None => numbers,
+    };
+
+    let parts: Vec<&str> = numbers.split('.').collect();
+    let number = |part: &&str| {
+        !part.is_empty() && part.len() <= 3 && part.chars().all(|c| c.is_ascii_digit())
+    };
+    if !(2..=3).contains(&parts.len()) || !parts.iter().all(number) {
+        return None;
+    }
+    let major: u32 = parts[0].parse().ok()?;
+    if major < MIN_MAJOR_VERSION {
+        return None;
+    }
+    Some((major, parts[1].parse().ok()?))
+}
+
+/// The unit of the connection times of the binary at `path`: the given
+/// one, or else the one of its version. Falls back to nanoseconds, the
+/// unit of current releases, if the version is unknown.
+pub(crate) fn connection_time_unit(
+    unit: Option<ConnectionTimeUnit>,
+    version: Option<&BitcoindVersion>,
+    path: &Path,
+) -> ConnectionTimeUnit {
+    let unit = match (unit, version) {
+        (Some(unit), _) => unit,
+        (None, Some(version)) => version.connection_time_unit(),
+        (None, None) => {
+            log::warn!(
+                "Unknown version of '{}'. Assuming validation:block_connected passes nanoseconds.",
+                path.display()
+            );
+            ConnectionTimeUnit::Nanoseconds
+        }
+    };
+    if let Some(version) = version {
+        log::info!(
+            "'{}' is bitcoind {}, its block connection times are in {}",
+            path.display(),
+            version,
+            unit
+        );
+    }
+    unit
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::usdt::tests::elf_with_section;
+
+    /// A binary with the string literals in its `.rodata`
+    fn binary(literals: &[&str]) -> Vec<u8> {
+        let mut rodata = vec![0u8];
+        for literal in literals {
+            rodata.extend_from_slice(literal.as_bytes());
+            rodata.push(0);
+        }
+        elf_with_section(RODATA_SECTION, &rodata)
+    }
+
+    fn version(literals: &[&str]) -> Option<BitcoindVersion> {
+        parse_bitcoind_version(&binary(literals)).unwrap()
+    }
+
+    #[test]
+    fn parse_versions() {
+        assert_eq!(parse_version("27.0"), Some((27, 0)));
+        assert_eq!(parse_version("27.1.0rc2"), Some((27, 1)));
+        assert_eq!(parse_version("28.99.0-0b4aa31c5a"), Some((28, 99)));
+        assert_eq!(parse_version("0.21.1"), None);
+        assert_eq!(parse_version("27"), None);
+        assert_eq!(parse_version("27.0 is required"), None);
+    }
+
+    #[test]
+    fn package_string_and_client_build() {
+        let found = version(&["%s version", "Bitcoin Core 28.99.0", "v28.99.0-0b4aa31c5a"]);
+        assert_eq!(
+            found,
+            Some(BitcoindVersion {
+                major: 28,
+                minor: 99,
+                build: "v28.99.0-0b4aa31c5a".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn package_string_wins_over_decoys() {
+        let found = version(&["v30.1", "v22.0", "Bitcoin Core 27.0.0", "v27.0"]).unwrap();
+        assert_eq!((found.major, found.minor), (27, 0));
+        assert_eq!(found.build, "v27.0");
+
+        // Without a matching CLIENT_BUILD, the package version is used
+        let found = version(&["v30.1", "Bitcoin Core 27.0.0"]).unwrap();
+        assert_eq!((found.major, found.minor), (27, 0));
+        assert_eq!(found.build, "v27.0.0");
+    }
+
+    #[test]
+    fn fall_back_to_client_build() {
+        let found = version(&["v1.2.3", "v26.1"]).unwrap();
+        assert_eq!((found.major, found.minor), (26, 1));
+    }
+
+    #[test]
+    fn no_version() {
+        assert_eq!(version(&["v1.2.3", "Bitcoin Core"]), None);
+        let elf = elf_with_section(".data", b"\0Bitcoin Core 27.0.0\0");
+        assert_eq!(parse_bitcoind_version(&elf).unwrap(), None);
+    }
+}
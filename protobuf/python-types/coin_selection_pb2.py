@@ -0,0 +1,44 @@
+# -*- coding: utf-8 -*-
+# Generated by the protocol buffer compiler.  DO NOT EDIT!
+# NO CHECKED-IN PROTOBUF GENCODE
+# source: coin_selection.proto
+# Protobuf Python Version: 7.36.2
+"""Generated protocol buffer code."""
+from google.protobuf import descriptor as _descriptor
+from google.protobuf import descriptor_pool as _descriptor_pool
+from google.protobuf import runtime_version as _runtime_version
+from google.protobuf import symbol_database as _symbol_database
+from google.protobuf.internal import builder as _builder
+_runtime_version.ValidateProtobufRuntimeVersion(
+    _runtime_version.Domain.PUBLIC,
+    7,
+    36,
+    2,
+    '',
+    'coin_selection.proto'
+)
+# @@protoc_insertion_point(imports)
+
+_sym_db = _symbol_database.Default()
+
+
+
+
+DESCRIPTOR = _descriptor_pool.Default().AddSerializedFile(b'\n\x14\x63oin_selection.proto\x12\x0e\x63oin_selection\"\x93\x02\n\x12\x43oinSelectionEvent\x12\x37\n\x0eselected_coins\x18\x01 \x01(\x0b\x32\x1d.coin_selection.SelectedCoinsH\x00\x12:\n\x10normal_create_tx\x18\x02 \x01(\x0b\x32\x1e.coin_selection.NormalCreateTxH\x00\x12I\n\x18\x61ttempting_aps_create_tx\x18\x03 \x01(\x0b\x32%.coin_selection.AttemptingApsCreateTxH\x00\x12\x34\n\raps_create_tx\x18\x04 \x01(\x0b\x32\x1b.coin_selection.ApsCreateTxH\x00\x42\x07\n\x05\x65vent\"n\n\rSelectedCoins\x12\x13\n\x0bwallet_name\x18\x01 \x02(\t\x12\x11\n\talgorithm\x18\x02 \x02(\t\x12\x0e\n\x06target\x18\x03 \x02(\x03\x12\r\n\x05waste\x18\x04 \x02(\x03\x12\x16\n\x0eselected_value\x18\x05 \x02(\x03\"W\n\x0eNormalCreateTx\x12\x13\n\x0bwallet_name\x18\x01 \x02(\t\x12\x0f\n\x07success\x18\x02 \x02(\x08\x12\x0b\n\x03\x66\x65\x65\x18\x03 \x02(\x03\x12\x12\n\nchange_pos\x18\x04 \x02(\x05\",\n\x15\x41ttemptingApsCreateTx\x12\x13\n\x0bwallet_name\x18\x01 \x02(\t\"e\n\x0b\x41psCreateTx\x12\x13\n\x0bwallet_name\x18\x01 \x02(\t\x12\x0f\n\x07use_aps\x18\x02 \x02(\x08\x12\x0f\n\x07success\x18\x03 \x02(\x08\x12\x0b\n\x03\x66\x65\x65\x18\x04 \x02(\x03\x12\x12\n\nchange_pos\x18\x05 \x02(\x05')
+
+_globals = globals()
+_builder.BuildMessageAndEnumDescriptors(DESCRIPTOR, _globals)
+_builder.BuildTopDescriptorsAndMessages(DESCRIPTOR, 'coin_selection_pb2', _globals)
+if not _descriptor._USE_C_DESCRIPTORS:
+  DESCRIPTOR._loaded_options = None
+  _globals['_COINSELECTIONEVENT']._serialized_start=41
+  _globals['_COINSELECTIONEVENT']._serialized_end=316
+  _globals['_SELECTEDCOINS']._serialized_start=318
+  _globals['_SELECTEDCOINS']._serialized_end=428
+  _globals['_NORMALCREATETX']._serialized_start=430
+  _globals['_NORMALCREATETX']._serialized_end=517
+  _globals['_ATTEMPTINGAPSCREATETX']._serialized_start=519
+  _globals['_ATTEMPTINGAPSCREATETX']._serialized_end=563
+  _globals['_APSCREATETX']._serialized_start=565
+  _globals['_APSCREATETX']._serialized_end=666
+# @@protoc_insertion_point(module_scope)
@@ -2,7 +2,7 @@
 # Generated by the protocol buffer compiler.  DO NOT EDIT!
 # NO CHECKED-IN PROTOBUF GENCODE
 # source: event_msg.proto
-# Protobuf Python Version: 5.28.3
+# Protobuf Python Version: 7.36.2
 """Generated protocol buffer code."""
 from google.protobuf import descriptor as _descriptor
 from google.protobuf import descriptor_pool as _descriptor_pool
@@ -11,9 +11,9 @@ from google.protobuf import symbol_database as _symbol_database
 from google.protobuf.internal import builder as _builder
 _runtime_version.ValidateProtobufRuntimeVersion(
     _runtime_version.Domain.PUBLIC,
-    5,
-    28,
-    3,
+    7,
+    36,
+    2,
     '',
     'event_msg.proto'
 )
@@ -27,15 +27,17 @@ import net_conn_pb2 as net__conn__pb2
 import addrman_pb2 as addrman__pb2
 import mempool_pb2 as mempool__pb2
 import validation_pb2 as validation__pb2
+import utxocache_pb2 as utxocache__pb2
+import coin_selection_pb2 as coin__selection__pb2
 
 
-DESCRIPTOR = _descriptor_pool.Default().AddSerializedFile(b'\n\x0f\x65vent_msg.proto\x12\tevent_msg\x1a\rnet_msg.proto\x1a\x0enet_conn.proto\x1a\raddrman.proto\x1a\rmempool.proto\x1a\x10validation.proto\"\x9a\x02\n\x08\x45ventMsg\x12\x11\n\ttimestamp\x18\n \x02(\x04\x12\x1f\n\x17timestamp_subsec_micros\x18\x0b \x02(\r\x12\x1f\n\x03msg\x18\x01 \x01(\x0b\x32\x10.net_msg.MessageH\x00\x12)\n\x04\x63onn\x18\x02 \x01(\x0b\x32\x19.net_conn.ConnectionEventH\x00\x12(\n\x07\x61\x64\x64rman\x18\x03 \x01(\x0b\x32\x15.addrman.AddrmanEventH\x00\x12(\n\x07mempool\x18\x04 \x01(\x0b\x32\x15.mempool.MempoolEventH\x00\x12\x31\n\nvalidation\x18\x05 \x01(\x0b\x32\x1b.validation.ValidationEventH\x00\x42\x07\n\x05\x65vent')
+DESCRIPTOR = _descriptor_pool.Default().AddSerializedFile(b'\n\x0f\x65vent_msg.proto\x12\tevent_msg\x1a\rnet_msg.proto\x1a\x0enet_conn.proto\x1a\raddrman.proto\x1a\rmempool.proto\x1a\x10validation.proto\x1a\x0futxocache.proto\x1a\x14\x63oin_selection.proto\"\xdb\x03\n\x08\x45ventMsg\x12\x11\n\ttimestamp\x18\n \x02(\x04\x12\x1f\n\x17timestamp_subsec_micros\x18\x0b \x02(\r\x12\x1b\n\x13kernel_timestamp_ns\x18\x0c \x01(\x04\x12\x17\n\x0fkernel_sequence\x18\r \x01(\x04\x12\x0b\n\x03pid\x18\x0e \x01(\r\x12\x0e\n\x06source\x18\x0f \x01(\t\x12\x1f\n\x03msg\x18\x01 \x01(\x0b\x32\x10.net_msg.MessageH\x00\x12)\n\x04\x63onn\x18\x02 \x01(\x0b\x32\x19.net_conn.ConnectionEventH\x00\x12(\n\x07\x61\x64\x64rman\x18\x03 \x01(\x0b\x32\x15.addrman.AddrmanEventH\x00\x12(\n\x07mempool\x18\x04 \x01(\x0b\x32\x15.mempool.MempoolEventH\x00\x12\x31\n\nvalidation\x18\x05 \x01(\x0b\x32\x1b.validation.ValidationEventH\x00\x12.\n\tutxocache\x18\x06 \x01(\x0b\x32\x19.utxocache.UtxocacheEventH\x00\x12<\n\x0e\x63oin_selection\x18\x07 \x01(\x0b\x32\".coin_selection.CoinSelectionEventH\x00\x42\x07\n\x05\x65vent')
 
 _globals = globals()
 _builder.BuildMessageAndEnumDescriptors(DESCRIPTOR, _globals)
 _builder.BuildTopDescriptorsAndMessages(DESCRIPTOR, 'event_msg_pb2', _globals)
 if not _descriptor._USE_C_DESCRIPTORS:
   DESCRIPTOR._loaded_options = None
-  _globals['_EVENTMSG']._serialized_start=110
-  _globals['_EVENTMSG']._serialized_end=392
+  _globals['_EVENTMSG']._serialized_start=149
+  _globals['_EVENTMSG']._serialized_end=624
 # @@protoc_insertion_point(module_scope)
@@ -2,7 +2,7 @@
 # Generated by the protocol buffer compiler.  DO NOT EDIT!
 # NO CHECKED-IN PROTOBUF GENCODE
 # source: net_msg.proto
-# Protobuf Python Version: 5.28.3
+# Protobuf Python Version: 7.36.2
 """Generated protocol buffer code."""
 from google.protobuf import descriptor as _descriptor
 from google.protobuf import descriptor_pool as _descriptor_pool
@@ -11,9 +11,9 @@ from google.protobuf import symbol_database as _symbol_database
 from google.protobuf.internal import builder as _builder
 _runtime_version.ValidateProtobufRuntimeVersion(
     _runtime_version.Domain.PUBLIC,
-    5,
-    28,
-    3,
+    7,
+    36,
+    2,
     '',
     'net_msg.proto'
 )
@@ -25,7 +25,7 @@ _sym_db = _symbol_database.Default()
 import primitive_pb2 as primitive__pb2
 
 
-DESCRIPTOR = _descriptor_pool.Default().AddSerializedFile(b'\n\rnet_msg.proto\x12\x07net_msg\x1a\x0fprimitive.proto\"\x81\x01\n\x08Metadata\x12\x0f\n\x07peer_id\x18\x01 \x02(\x04\x12\x0c\n\x04\x61\x64\x64r\x18\x02 \x02(\t\x12&\n\tconn_type\x18\x03 \x02(\x0e\x32\x13.primitive.ConnType\x12\x0f\n\x07\x63ommand\x18\x04 \x02(\t\x12\x0f\n\x07inbound\x18\x05 \x02(\x08\x12\x0c\n\x04size\x18\x06 \x02(\x04\"\xf3\n\n\x07Message\x12\x1f\n\x04meta\x18\x01 \x02(\x0b\x32\x11.net_msg.Metadata\x12\x1d\n\x04ping\x18\x02 \x01(\x0b\x32\r.net_msg.PingH\x00\x12\x1d\n\x04pong\x18\x03 \x01(\x0b\x32\r.net_msg.PongH\x00\x12\x1b\n\x03inv\x18\x04 \x01(\x0b\x32\x0c.net_msg.InvH\x00\x12#\n\x07getdata\x18\x05 \x01(\x0b\x32\x10.net_msg.GetDataH\x00\x12\x19\n\x02tx\x18\x06 \x01(\x0b\x32\x0b.net_msg.TxH\x00\x12#\n\x07headers\x18\x07 \x01(\x0b\x32\x10.net_msg.HeadersH\x00\x12\x1d\n\x04\x61\x64\x64r\x18\x08 \x01(\x0b\x32\r.net_msg.AddrH\x00\x12!\n\x06\x61\x64\x64rv2\x18\t \x01(\x0b\x32\x0f.net_msg.AddrV2H\x00\x12\'\n\tfeefilter\x18\n \x01(\x0b\x32\x12.net_msg.FeeFilterH\x00\x12)\n\ngetheaders\x18\x0b \x01(\x0b\x32\x13.net_msg.GetHeadersH\x00\x12\'\n\tgetblocks\x18\x0c \x01(\x0b\x32\x12.net_msg.GetBlocksH\x00\x12#\n\x07version\x18\r \x01(\x0b\x32\x10.net_msg.VersionH\x00\x12%\n\x08notfound\x18\x0e \x01(\x0b\x32\x11.net_msg.NotFoundH\x00\x12!\n\x06reject\x18\' \x01(\x0b\x32\x0f.net_msg.RejectH\x00\x12-\n\x0c\x63ompactblock\x18\x10 \x01(\x0b\x32\x15.net_msg.CompactBlockH\x00\x12+\n\x0bsendcompact\x18\x11 \x01(\x0b\x32\x14.net_msg.SendCompactH\x00\x12\x1f\n\x05\x62lock\x18\x12 \x01(\x0b\x32\x0e.net_msg.BlockH\x00\x12+\n\x0bgetblocktxn\x18\x13 \x01(\x0b\x32\x14.net_msg.GetBlockTxnH\x00\x12%\n\x08\x62locktxn\x18\x14 \x01(\x0b\x32\x11.net_msg.BlockTxnH\x00\x12\x1f\n\x05\x61lert\x18\x15 \x01(\x0b\x32\x0e.net_msg.AlertH\x00\x12\'\n\tfilteradd\x18\x16 \x01(\x0b\x32\x12.net_msg.FilterAddH\x00\x12)\n\nfilterload\x18\x17 \x01(\x0b\x32\x13.net_msg.FilterLoadH\x00\x12-\n\x0cgetcfcheckpt\x18\x18 \x01(\x0b\x32\x15.net_msg.GetCFCheckptH\x00\x12\'\n\tcfheaders\x18\x19 \x01(\x0b\x32\x12.net_msg.CFHeadersH\x00\x12#\n\x07\x63\x66ilter\x18\x1a \x01(\x0b\x32\x10.net_msg.CFilterH\x00\x12\'\n\tcfcheckpt\x18\x1b \x01(\x0b\x32\x12.net_msg.CFCheckptH\x00\x12-\n\x0cgetcfheaders\x18\x1c \x01(\x0b\x32\x15.net_msg.GetCFHeadersH\x00\x12)\n\ngetcfilter\x18\x1d \x01(\x0b\x32\x13.net_msg.GetCFilterH\x00\x12+\n\x0bmerkleblock\x18\x1e \x01(\x0b\x32\x14.net_msg.MerkleBlockH\x00\x12#\n\x07unknown\x18\x1f \x01(\x0b\x32\x10.net_msg.UnknownH\x00\x12\x10\n\x06verack\x18  \x01(\x08H\x00\x12\x15\n\x0bsendheaders\x18! \x01(\x08H\x00\x12\x11\n\x07getaddr\x18\" \x01(\x08H\x00\x12\x11\n\x07mempool\x18# \x01(\x08H\x00\x12\x14\n\nwtxidrelay\x18$ \x01(\x08H\x00\x12\x14\n\nsendaddrv2\x18% \x01(\x08H\x00\x12\x15\n\x0b\x66ilterclear\x18& \x01(\x08H\x00\x12\x15\n\x0b\x65mptyaddrv2\x18( \x01(\x08H\x00\x12\x11\n\x07oldping\x18) \x01(\x08H\x00\x42\x05\n\x03msg\"\x15\n\x04Ping\x12\r\n\x05value\x18\x01 \x02(\x06\"\x15\n\x04Pong\x12\r\n\x05value\x18\x01 \x02(\x06\".\n\x03Inv\x12\'\n\x05items\x18\x01 \x03(\x0b\x32\x18.primitive.InventoryItem\"3\n\x08NotFound\x12\'\n\x05items\x18\x01 \x03(\x0b\x32\x18.primitive.InventoryItem\"\x8f\x01\n\x0c\x43ompactBlock\x12&\n\x06header\x18\x01 \x02(\x0b\x32\x16.primitive.BlockHeader\x12\r\n\x05nonce\x18\x02 \x02(\x04\x12\x11\n\tshort_ids\x18\x03 \x03(\x0c\x12\x35\n\x0ctransactions\x18\x04 \x03(\x0b\x32\x1f.primitive.PrefilledTransaction\"(\n\x02Tx\x12\"\n\x02tx\x18\x01 \x02(\x0b\x32\x16.primitive.Transaction\"2\n\x07GetData\x12\'\n\x05items\x18\x01 \x03(\x0b\x32\x18.primitive.InventoryItem\"2\n\x07Headers\x12\'\n\x07headers\x18\x01 \x03(\x0b\x32\x16.primitive.BlockHeader\"5\n\x0bGetBlockTxn\x12\x12\n\nblock_hash\x18\x01 \x02(\x0c\x12\x12\n\ntx_indexes\x18\x02 \x03(\x04\"L\n\x08\x42lockTxn\x12\x12\n\nblock_hash\x18\x01 \x02(\x0c\x12,\n\x0ctransactions\x18\x02 \x03(\x0b\x32\x16.primitive.Transaction\"\x16\n\x05\x41lert\x12\r\n\x05\x61lert\x18\x01 \x02(\x0c\"-\n\x04\x41\x64\x64r\x12%\n\taddresses\x18\x01 \x03(\x0b\x32\x12.primitive.Address\"/\n\x06\x41\x64\x64rV2\x12%\n\taddresses\x18\x01 \x03(\x0b\x32\x12.primitive.Address\"\xf3\x01\n\x06Reject\x12\x18\n\x10rejected_command\x18\x01 \x02(\t\x12,\n\x06reason\x18\x02 \x02(\x0e\x32\x1c.net_msg.Reject.RejectReason\x12\x16\n\x0ereason_details\x18\x03 \x02(\t\x12\x0c\n\x04hash\x18\x04 \x02(\x0c\"{\n\x0cRejectReason\x12\r\n\tMALFORMED\x10\x00\x12\x0b\n\x07INVALID\x10\x01\x12\x0c\n\x08OBSOLETE\x10\x02\x12\r\n\tDUPLICATE\x10\x03\x12\x0f\n\x0bNONSTANDARD\x10\x04\x12\x08\n\x04\x44UST\x10\x05\x12\x07\n\x03\x46\x45\x45\x10\x06\x12\x0e\n\nCHECKPOINT\x10\x07\"4\n\x0bSendCompact\x12\x14\n\x0csend_compact\x18\x01 \x02(\x08\x12\x0f\n\x07version\x18\x02 \x02(\x04\"\x18\n\tFeeFilter\x12\x0b\n\x03\x66\x65\x65\x18\x01 \x02(\x12\"H\n\nGetHeaders\x12\x0f\n\x07version\x18\x01 \x02(\r\x12\x16\n\x0elocator_hashes\x18\x02 \x03(\x0c\x12\x11\n\tstop_hash\x18\x03 \x02(\x0c\"G\n\tGetBlocks\x12\x0f\n\x07version\x18\x01 \x02(\r\x12\x16\n\x0elocator_hashes\x18\x02 \x03(\x0c\x12\x11\n\tstop_hash\x18\x03 \x02(\x0c\"\xd1\x01\n\x07Version\x12\x0f\n\x07version\x18\x01 \x02(\r\x12\x10\n\x08services\x18\x02 \x02(\x04\x12\x11\n\ttimestamp\x18\x03 \x02(\x12\x12$\n\x08receiver\x18\x04 \x02(\x0b\x32\x12.primitive.Address\x12\"\n\x06sender\x18\x05 \x02(\x0b\x32\x12.primitive.Address\x12\r\n\x05nonce\x18\x06 \x02(\x04\x12\x12\n\nuser_agent\x18\x07 \x02(\t\x12\x14\n\x0cstart_height\x18\x08 \x02(\x11\x12\r\n\x05relay\x18\t \x02(\x08\"]\n\x05\x42lock\x12&\n\x06header\x18\x01 \x02(\x0b\x32\x16.primitive.BlockHeader\x12,\n\x0ctransactions\x18\x02 \x03(\x0b\x32\x16.primitive.Transaction\"\x1b\n\tFilterAdd\x12\x0e\n\x06\x66ilter\x18\x01 \x02(\x0c\"\x9f\x01\n\nFilterLoad\x12\x0e\n\x06\x66ilter\x18\x01 \x02(\x0c\x12\x12\n\nhash_funcs\x18\x02 \x02(\r\x12\r\n\x05tweak\x18\x03 \x02(\r\x12-\n\x05\x66lags\x18\x04 \x02(\x0e\x32\x1e.net_msg.FilterLoad.BloomFlags\"/\n\nBloomFlags\x12\x08\n\x04None\x10\x00\x12\x07\n\x03\x41ll\x10\x01\x12\x0e\n\nPubkeyOnly\x10\x02\"6\n\x0cGetCFCheckpt\x12\x13\n\x0b\x66ilter_type\x18\x01 \x02(\r\x12\x11\n\tstop_hash\x18\x02 \x02(\x0c\"K\n\tCFCheckpt\x12\x13\n\x0b\x66ilter_type\x18\x01 \x02(\r\x12\x11\n\tstop_hash\x18\x02 \x02(\x0c\x12\x16\n\x0e\x66ilter_headers\x18\x03 \x03(\x0c\"L\n\x0cGetCFHeaders\x12\x13\n\x0b\x66ilter_type\x18\x01 \x02(\r\x12\x14\n\x0cstart_height\x18\x02 \x02(\r\x12\x11\n\tstop_hash\x18\x03 \x02(\x0c\"j\n\tCFHeaders\x12\x13\n\x0b\x66ilter_type\x18\x01 \x02(\r\x12\x11\n\tstop_hash\x18\x02 \x02(\x0c\x12\x1e\n\x16previous_filter_header\x18\x03 \x02(\x0c\x12\x15\n\rfilter_hashes\x18\x04 \x03(\x0c\"J\n\nGetCFilter\x12\x13\n\x0b\x66ilter_type\x18\x01 \x02(\r\x12\x14\n\x0cstart_height\x18\x02 \x02(\r\x12\x11\n\tstop_hash\x18\x03 \x02(\x0c\"B\n\x07\x43\x46ilter\x12\x13\n\x0b\x66ilter_type\x18\x01 \x02(\r\x12\x12\n\nblock_hash\x18\x02 \x02(\x0c\x12\x0e\n\x06\x66ilter\x18\x03 \x02(\x0c\"m\n\x0bMerkleBlock\x12&\n\x06header\x18\x01 \x02(\x0b\x32\x16.primitive.BlockHeader\x12\x18\n\x10num_transactions\x18\x02 \x02(\r\x12\x0c\n\x04\x62its\x18\x03 \x03(\x08\x12\x0e\n\x06hashes\x18\x04 \x03(\x0c\"+\n\x07Unknown\x12\x0f\n\x07\x63ommand\x18\x01 \x02(\t\x12\x0f\n\x07payload\x18\x02 \x02(\x0c')
+DESCRIPTOR = _descriptor_pool.Default().AddSerializedFile(b'\n\rnet_msg.proto\x12\x07net_msg\x1a\x0fprimitive.proto\"\x81\x01\n\x08Metadata\x12\x0f\n\x07peer_id\x18\x01 \x02(\x04\x12\x0c\n\x04\x61\x64\x64r\x18\x02 \x02(\t\x12&\n\tconn_type\x18\x03 \x02(\x0e\x32\x13.primitive.ConnType\x12\x0f\n\x07\x63ommand\x18\x04 \x02(\t\x12\x0f\n\x07inbound\x18\x05 \x02(\x08\x12\x0c\n\x04size\x18\x06 \x02(\x04\"\xa5\x0b\n\x07Message\x12\x1f\n\x04meta\x18\x01 \x02(\x0b\x32\x11.net_msg.Metadata\x12\x1d\n\x04ping\x18\x02 \x01(\x0b\x32\r.net_msg.PingH\x00\x12\x1d\n\x04pong\x18\x03 \x01(\x0b\x32\r.net_msg.PongH\x00\x12\x1b\n\x03inv\x18\x04 \x01(\x0b\x32\x0c.net_msg.InvH\x00\x12#\n\x07getdata\x18\x05 \x01(\x0b\x32\x10.net_msg.GetDataH\x00\x12\x19\n\x02tx\x18\x06 \x01(\x0b\x32\x0b.net_msg.TxH\x00\x12#\n\x07headers\x18\x07 \x01(\x0b\x32\x10.net_msg.HeadersH\x00\x12\x1d\n\x04\x61\x64\x64r\x18\x08 \x01(\x0b\x32\r.net_msg.AddrH\x00\x12!\n\x06\x61\x64\x64rv2\x18\t \x01(\x0b\x32\x0f.net_msg.AddrV2H\x00\x12\'\n\tfeefilter\x18\n \x01(\x0b\x32\x12.net_msg.FeeFilterH\x00\x12)\n\ngetheaders\x18\x0b \x01(\x0b\x32\x13.net_msg.GetHeadersH\x00\x12\'\n\tgetblocks\x18\x0c \x01(\x0b\x32\x12.net_msg.GetBlocksH\x00\x12#\n\x07version\x18\r \x01(\x0b\x32\x10.net_msg.VersionH\x00\x12%\n\x08notfound\x18\x0e \x01(\x0b\x32\x11.net_msg.NotFoundH\x00\x12!\n\x06reject\x18\' \x01(\x0b\x32\x0f.net_msg.RejectH\x00\x12-\n\x0c\x63ompactblock\x18\x10 \x01(\x0b\x32\x15.net_msg.CompactBlockH\x00\x12+\n\x0bsendcompact\x18\x11 \x01(\x0b\x32\x14.net_msg.SendCompactH\x00\x12\x1f\n\x05\x62lock\x18\x12 \x01(\x0b\x32\x0e.net_msg.BlockH\x00\x12+\n\x0bgetblocktxn\x18\x13 \x01(\x0b\x32\x14.net_msg.GetBlockTxnH\x00\x12%\n\x08\x62locktxn\x18\x14 \x01(\x0b\x32\x11.net_msg.BlockTxnH\x00\x12\x1f\n\x05\x61lert\x18\x15 \x01(\x0b\x32\x0e.net_msg.AlertH\x00\x12\'\n\tfilteradd\x18\x16 \x01(\x0b\x32\x12.net_msg.FilterAddH\x00\x12)\n\nfilterload\x18\x17 \x01(\x0b\x32\x13.net_msg.FilterLoadH\x00\x12-\n\x0cgetcfcheckpt\x18\x18 \x01(\x0b\x32\x15.net_msg.GetCFCheckptH\x00\x12\'\n\tcfheaders\x18\x19 \x01(\x0b\x32\x12.net_msg.CFHeadersH\x00\x12#\n\x07\x63\x66ilter\x18\x1a \x01(\x0b\x32\x10.net_msg.CFilterH\x00\x12\'\n\tcfcheckpt\x18\x1b \x01(\x0b\x32\x12.net_msg.CFCheckptH\x00\x12-\n\x0cgetcfheaders\x18\x1c \x01(\x0b\x32\x15.net_msg.GetCFHeadersH\x00\x12)\n\ngetcfilter\x18\x1d \x01(\x0b\x32\x13.net_msg.GetCFilterH\x00\x12+\n\x0bmerkleblock\x18\x1e \x01(\x0b\x32\x14.net_msg.MerkleBlockH\x00\x12#\n\x07unknown\x18\x1f \x01(\x0b\x32\x10.net_msg.UnknownH\x00\x12\x10\n\x06verack\x18  \x01(\x08H\x00\x12\x15\n\x0bsendheaders\x18! \x01(\x08H\x00\x12\x11\n\x07getaddr\x18\" \x01(\x08H\x00\x12\x11\n\x07mempool\x18# \x01(\x08H\x00\x12\x14\n\nwtxidrelay\x18$ \x01(\x08H\x00\x12\x14\n\nsendaddrv2\x18% \x01(\x08H\x00\x12\x15\n\x0b\x66ilterclear\x18& \x01(\x08H\x00\x12\x15\n\x0b\x65mptyaddrv2\x18( \x01(\x08H\x00\x12\x11\n\x07oldping\x18) \x01(\x08H\x00\x12\x30\n\x0e\x64\x65\x63ode_failure\x18* \x01(\x0b\x32\x16.net_msg.DecodeFailureH\x00\x42\x05\n\x03msg\"\x15\n\x04Ping\x12\r\n\x05value\x18\x01 \x02(\x06\"\x15\n\x04Pong\x12\r\n\x05value\x18\x01 \x02(\x06\".\n\x03Inv\x12\'\n\x05items\x18\x01 \x03(\x0b\x32\x18.primitive.InventoryItem\"3\n\x08NotFound\x12\'\n\x05items\x18\x01 \x03(\x0b\x32\x18.primitive.InventoryItem\"\x8f\x01\n\x0c\x43ompactBlock\x12&\n\x06header\x18\x01 \x02(\x0b\x32\x16.primitive.BlockHeader\x12\r\n\x05nonce\x18\x02 \x02(\x04\x12\x11\n\tshort_ids\x18\x03 \x03(\x0c\x12\x35\n\x0ctransactions\x18\x04 \x03(\x0b\x32\x1f.primitive.PrefilledTransaction\"(\n\x02Tx\x12\"\n\x02tx\x18\x01 \x02(\x0b\x32\x16.primitive.Transaction\"2\n\x07GetData\x12\'\n\x05items\x18\x01 \x03(\x0b\x32\x18.primitive.InventoryItem\"2\n\x07Headers\x12\'\n\x07headers\x18\x01 \x03(\x0b\x32\x16.primitive.BlockHeader\"5\n\x0bGetBlockTxn\x12\x12\n\nblock_hash\x18\x01 \x02(\x0c\x12\x12\n\ntx_indexes\x18\x02 \x03(\x04\"L\n\x08\x42lockTxn\x12\x12\n\nblock_hash\x18\x01 \x02(\x0c\x12,\n\x0ctransactions\x18\x02 \x03(\x0b\x32\x16.primitive.Transaction\"\x16\n\x05\x41lert\x12\r\n\x05\x61lert\x18\x01 \x02(\x0c\"-\n\x04\x41\x64\x64r\x12%\n\taddresses\x18\x01 \x03(\x0b\x32\x12.primitive.Address\"/\n\x06\x41\x64\x64rV2\x12%\n\taddresses\x18\x01 \x03(\x0b\x32\x12.primitive.Address\"\xf3\x01\n\x06Reject\x12\x18\n\x10rejected_command\x18\x01 \x02(\t\x12,\n\x06reason\x18\x02 \x02(\x0e\x32\x1c.net_msg.Reject.RejectReason\x12\x16\n\x0ereason_details\x18\x03 \x02(\t\x12\x0c\n\x04hash\x18\x04 \x02(\x0c\"{\n\x0cRejectReason\x12\r\n\tMALFORMED\x10\x00\x12\x0b\n\x07INVALID\x10\x01\x12\x0c\n\x08OBSOLETE\x10\x02\x12\r\n\tDUPLICATE\x10\x03\x12\x0f\n\x0bNONSTANDARD\x10\x04\x12\x08\n\x04\x44UST\x10\x05\x12\x07\n\x03\x46\x45\x45\x10\x06\x12\x0e\n\nCHECKPOINT\x10\x07\"4\n\x0bSendCompact\x12\x14\n\x0csend_compact\x18\x01 \x02(\x08\x12\x0f\n\x07version\x18\x02 \x02(\x04\"\x18\n\tFeeFilter\x12\x0b\n\x03\x66\x65\x65\x18\x01 \x02(\x12\"H\n\nGetHeaders\x12\x0f\n\x07version\x18\x01 \x02(\r\x12\x16\n\x0elocator_hashes\x18\x02 \x03(\x0c\x12\x11\n\tstop_hash\x18\x03 \x02(\x0c\"G\n\tGetBlocks\x12\x0f\n\x07version\x18\x01 \x02(\r\x12\x16\n\x0elocator_hashes\x18\x02 \x03(\x0c\x12\x11\n\tstop_hash\x18\x03 \x02(\x0c\"\xd1\x01\n\x07Version\x12\x0f\n\x07version\x18\x01 \x02(\r\x12\x10\n\x08services\x18\x02 \x02(\x04\x12\x11\n\ttimestamp\x18\x03 \x02(\x12\x12$\n\x08receiver\x18\x04 \x02(\x0b\x32\x12.primitive.Address\x12\"\n\x06sender\x18\x05 \x02(\x0b\x32\x12.primitive.Address\x12\r\n\x05nonce\x18\x06 \x02(\x04\x12\x12\n\nuser_agent\x18\x07 \x02(\t\x12\x14\n\x0cstart_height\x18\x08 \x02(\x11\x12\r\n\x05relay\x18\t \x02(\x08\"]\n\x05\x42lock\x12&\n\x06header\x18\x01 \x02(\x0b\x32\x16.primitive.BlockHeader\x12,\n\x0ctransactions\x18\x02 \x03(\x0b\x32\x16.primitive.Transaction\"\x1b\n\tFilterAdd\x12\x0e\n\x06\x66ilter\x18\x01 \x02(\x0c\"\x9f\x01\n\nFilterLoad\x12\x0e\n\x06\x66ilter\x18\x01 \x02(\x0c\x12\x12\n\nhash_funcs\x18\x02 \x02(\r\x12\r\n\x05tweak\x18\x03 \x02(\r\x12-\n\x05\x66lags\x18\x04 \x02(\x0e\x32\x1e.net_msg.FilterLoad.BloomFlags\"/\n\nBloomFlags\x12\x08\n\x04None\x10\x00\x12\x07\n\x03\x41ll\x10\x01\x12\x0e\n\nPubkeyOnly\x10\x02\"6\n\x0cGetCFCheckpt\x12\x13\n\x0b\x66ilter_type\x18\x01 \x02(\r\x12\x11\n\tstop_hash\x18\x02 \x02(\x0c\"K\n\tCFCheckpt\x12\x13\n\x0b\x66ilter_type\x18\x01 \x02(\r\x12\x11\n\tstop_hash\x18\x02 \x02(\x0c\x12\x16\n\x0e\x66ilter_headers\x18\x03 \x03(\x0c\"L\n\x0cGetCFHeaders\x12\x13\n\x0b\x66ilter_type\x18\x01 \x02(\r\x12\x14\n\x0cstart_height\x18\x02 \x02(\r\x12\x11\n\tstop_hash\x18\x03 \x02(\x0c\"j\n\tCFHeaders\x12\x13\n\x0b\x66ilter_type\x18\x01 \x02(\r\x12\x11\n\tstop_hash\x18\x02 \x02(\x0c\x12\x1e\n\x16previous_filter_header\x18\x03 \x02(\x0c\x12\x15\n\rfilter_hashes\x18\x04 \x03(\x0c\"J\n\nGetCFilter\x12\x13\n\x0b\x66ilter_type\x18\x01 \x02(\r\x12\x14\n\x0cstart_height\x18\x02 \x02(\r\x12\x11\n\tstop_hash\x18\x03 \x02(\x0c\"B\n\x07\x43\x46ilter\x12\x13\n\x0b\x66ilter_type\x18\x01 \x02(\r\x12\x12\n\nblock_hash\x18\x02 \x02(\x0c\x12\x0e\n\x06\x66ilter\x18\x03 \x02(\x0c\"m\n\x0bMerkleBlock\x12&\n\x06header\x18\x01 \x02(\x0b\x32\x16.primitive.BlockHeader\x12\x18\n\x10num_transactions\x18\x02 \x02(\r\x12\x0c\n\x04\x62its\x18\x03 \x03(\x08\x12\x0e\n\x06hashes\x18\x04 \x03(\x0c\"+\n\x07Unknown\x12\x0f\n\x07\x63ommand\x18\x01 \x02(\t\x12\x0f\n\x07payload\x18\x02 \x02(\x0c\"V\n\rDecodeFailure\x12\x0f\n\x07\x63ommand\x18\x01 \x02(\t\x12\x0f\n\x07payload\x18\x02 \x02(\x0c\x12\r\n\x05\x65rror\x18\x03 \x02(\t\x12\x14\n\x0cpayload_size\x18\x04 \x02(\x04')
 
 _globals = globals()
 _builder.BuildMessageAndEnumDescriptors(DESCRIPTOR, _globals)
@@ -35,69 +35,71 @@ if not _descriptor._USE_C_DESCRIPTORS:
   _globals['_METADATA']._serialized_start=44
   _globals['_METADATA']._serialized_end=173
   _globals['_MESSAGE']._serialized_start=176
-  _globals['_MESSAGE']._serialized_end=1571
-  _globals['_PING']._serialized_start=1573
-  _globals['_PING']._serialized_end=1594
-  _globals['_PONG']._serialized_start=1596
-  _globals['_PONG']._serialized_end=1617
-  _globals['_INV']._serialized_start=1619
-  _globals['_INV']._serialized_end=1665
-  _globals['_NOTFOUND']._serialized_start=1667
-  _globals['_NOTFOUND']._serialized_end=1718
-  _globals['_COMPACTBLOCK']._serialized_start=1721
-  _globals['_COMPACTBLOCK']._serialized_end=1864
-  _globals['_TX']._serialized_start=1866
-  _globals['_TX']._serialized_end=1906
-  _globals['_GETDATA']._serialized_start=1908
-  _globals['_GETDATA']._serialized_end=1958
-  _globals['_HEADERS']._serialized_start=1960
-  _globals['_HEADERS']._serialized_end=2010
-  _globals['_GETBLOCKTXN']._serialized_start=2012
-  _globals['_GETBLOCKTXN']._serialized_end=2065
-  _globals['_BLOCKTXN']._serialized_start=2067
-  _globals['_BLOCKTXN']._serialized_end=2143
-  _globals['_ALERT']._serialized_start=2145
-  _globals['_ALERT']._serialized_end=2167
-  _globals['_ADDR']._serialized_start=2169
-  _globals['_ADDR']._serialized_end=2214
-  _globals['_ADDRV2']._serialized_start=2216
-  _globals['_ADDRV2']._serialized_end=2263
-  _globals['_REJECT']._serialized_start=2266
-  _globals['_REJECT']._serialized_end=2509
-  _globals['_REJECT_REJECTREASON']._serialized_start=2386
-  _globals['_REJECT_REJECTREASON']._serialized_end=2509
-  _globals['_SENDCOMPACT']._serialized_start=2511
-  _globals['_SENDCOMPACT']._serialized_end=2563
-  _globals['_FEEFILTER']._serialized_start=2565
-  _globals['_FEEFILTER']._serialized_end=2589
-  _globals['_GETHEADERS']._serialized_start=2591
-  _globals['_GETHEADERS']._serialized_end=2663
-  _globals['_GETBLOCKS']._serialized_start=2665
-  _globals['_GETBLOCKS']._serialized_end=2736
-  _globals['_VERSION']._serialized_start=2739
-  _globals['_VERSION']._serialized_end=2948
-  _globals['_BLOCK']._serialized_start=2950
-  _globals['_BLOCK']._serialized_end=3043
-  _globals['_FILTERADD']._serialized_start=3045
-  _globals['_FILTERADD']._serialized_end=3072
-  _globals['_FILTERLOAD']._serialized_start=3075
-  _globals['_FILTERLOAD']._serialized_end=3234
-  _globals['_FILTERLOAD_BLOOMFLAGS']._serialized_start=3187
-  _globals['_FILTERLOAD_BLOOMFLAGS']._serialized_end=3234
-  _globals['_GETCFCHECKPT']._serialized_start=3236
-  _globals['_GETCFCHECKPT']._serialized_end=3290
-  _globals['_CFCHECKPT']._serialized_start=3292
-  _globals['_CFCHECKPT']._serialized_end=3367
-  _globals['_GETCFHEADERS']._serialized_start=3369
-  _globals['_GETCFHEADERS']._serialized_end=3445
-  _globals['_CFHEADERS']._serialized_start=3447
-  _globals['_CFHEADERS']._serialized_end=3553
-  _globals['_GETCFILTER']._serialized_start=3555
-  _globals['_GETCFILTER']._serialized_end=3629
-  _globals['_CFILTER']._serialized_start=3631
-  _globals['_CFILTER']._serialized_end=3697
-  _globals['_MERKLEBLOCK']._serialized_start=3699
-  _globals['_MERKLEBLOCK']._serialized_end=3808
-  _globals['_UNKNOWN']._serialized_start=3810
-  _globals['_UNKNOWN']._serialized_end=3853
+  _globals['_MESSAGE']._serialized_end=1621
+  _globals['_PING']._serialized_start=1623
+  _globals['_PING']._serialized_end=1644
+  _globals['_PONG']._serialized_start=1646
+  _globals['_PONG']._serialized_end=1667
+  _globals['_INV']._serialized_start=1669
+  _globals['_INV']._serialized_end=1715
+  _globals['_NOTFOUND']._serialized_start=1717
+  _globals['_NOTFOUND']._serialized_end=1768
+  _globals['_COMPACTBLOCK']._serialized_start=1771
+  _globals['_COMPACTBLOCK']._serialized_end=1914
+  _globals['_TX']._serialized_start=1916
+  _globals['_TX']._serialized_end=1956
+  _globals['_GETDATA']._serialized_start=1958
+  _globals['_GETDATA']._serialized_end=2008
+  _globals['_HEADERS']._serialized_start=2010
+  _globals['_HEADERS']._serialized_end=2060
+  _globals['_GETBLOCKTXN']._serialized_start=2062
+  _globals['_GETBLOCKTXN']._serialized_end=2115
+  _globals['_BLOCKTXN']._serialized_start=2117
+  _globals['_BLOCKTXN']._serialized_end=2193
+  _globals['_ALERT']._serialized_start=2195
+  _globals['_ALERT']._serialized_end=2217
+  _globals['_ADDR']._serialized_start=2219
+  _globals['_ADDR']._serialized_end=2264
+  _globals['_ADDRV2']._serialized_start=2266
+  _globals['_ADDRV2']._serialized_end=2313
+  _globals['_REJECT']._serialized_start=2316
+  _globals['_REJECT']._serialized_end=2559
+  _globals['_REJECT_REJECTREASON']._serialized_start=2436
+  _globals['_REJECT_REJECTREASON']._serialized_end=2559
+  _globals['_SENDCOMPACT']._serialized_start=2561
+  _globals['_SENDCOMPACT']._serialized_end=2613
+  _globals['_FEEFILTER']._serialized_start=2615
+  _globals['_FEEFILTER']._serialized_end=2639
+  _globals['_GETHEADERS']._serialized_start=2641
+  _globals['_GETHEADERS']._serialized_end=2713
+  _globals['_GETBLOCKS']._serialized_start=2715
+  _globals['_GETBLOCKS']._serialized_end=2786
+  _globals['_VERSION']._serialized_start=2789
+  _globals['_VERSION']._serialized_end=2998
+  _globals['_BLOCK']._serialized_start=3000
+  _globals['_BLOCK']._serialized_end=3093
+  _globals['_FILTERADD']._serialized_start=3095
+  _globals['_FILTERADD']._serialized_end=3122
+  _globals['_FILTERLOAD']._serialized_start=3125
+  _globals['_FILTERLOAD']._serialized_end=3284
+  _globals['_FILTERLOAD_BLOOMFLAGS']._serialized_start=3237
+  _globals['_FILTERLOAD_BLOOMFLAGS']._serialized_end=3284
+  _globals['_GETCFCHECKPT']._serialized_start=3286
+  _globals['_GETCFCHECKPT']._serialized_end=3340
+  _globals['_CFCHECKPT']._serialized_start=3342
+  _globals['_CFCHECKPT']._serialized_end=3417
+  _globals['_GETCFHEADERS']._serialized_start=3419
+  _globals['_GETCFHEADERS']._serialized_end=3495
+  _globals['_CFHEADERS']._serialized_start=3497
+  _globals['_CFHEADERS']._serialized_end=3603
+  _globals['_GETCFILTER']._serialized_start=3605
+  _globals['_GETCFILTER']._serialized_end=3679
+  _globals['_CFILTER']._serialized_start=3681
+  _globals['_CFILTER']._serialized_end=3747
+  _globals['_MERKLEBLOCK']._serialized_start=3749
+  _globals['_MERKLEBLOCK']._serialized_end=3858
+  _globals['_UNKNOWN']._serialized_start=3860
+  _globals['_UNKNOWN']._serialized_end=3903
+  _globals['_DECODEFAILURE']._serialized_start=3905
+  _globals['_DECODEFAILURE']._serialized_end=3991
 # @@protoc_insertion_point(module_scope)
@@ -0,0 +1,42 @@
+# -*- coding: utf-8 -*-
+# Generated by the protocol buffer compiler.  DO NOT EDIT!
+# NO CHECKED-IN PROTOBUF GENCODE
+# source: utxocache.proto
+# Protobuf Python Version: 7.36.2
+"""Generated protocol buffer code."""
+from google.protobuf import descriptor as _descriptor
+from google.protobuf import descriptor_pool as _descriptor_pool
+from google.protobuf import runtime_version as _runtime_version
+from google.protobuf import symbol_database as _symbol_database
+from google.protobuf.internal import builder as _builder
+_runtime_version.ValidateProtobufRuntimeVersion(
+    _runtime_version.Domain.PUBLIC,
+    7,
+    36,
+    2,
+    '',
+    'utxocache.proto'
+)
+# @@protoc_insertion_point(imports)
+
+_sym_db = _symbol_database.Default()
+
+
+
+
+DESCRIPTOR = _descriptor_pool.Default().AddSerializedFile(b'\n\x0futxocache.proto\x12\tutxocache\"\xa2\x01\n\x0eUtxocacheEvent\x12!\n\x05\x66lush\x18\x01 \x01(\x0b\x32\x10.utxocache.FlushH\x00\x12\x1e\n\x03\x61\x64\x64\x18\x02 \x01(\x0b\x32\x0f.utxocache.CoinH\x00\x12 \n\x05spent\x18\x03 \x01(\x0b\x32\x0f.utxocache.CoinH\x00\x12\"\n\x07uncache\x18\x04 \x01(\x0b\x32\x0f.utxocache.CoinH\x00\x42\x07\n\x05\x65vent\"\x87\x01\n\x05\x46lush\x12\x10\n\x08\x64uration\x18\x01 \x02(\x03\x12\"\n\x04mode\x18\x02 \x02(\x0e\x32\x14.utxocache.FlushMode\x12\x13\n\x0b\x63oins_count\x18\x03 \x02(\x04\x12\x17\n\x0f\x63oins_mem_usage\x18\x04 \x02(\x04\x12\x1a\n\x12is_flush_for_prune\x18\x05 \x02(\x08\"W\n\x04\x43oin\x12\x0c\n\x04txid\x18\x01 \x02(\x0c\x12\r\n\x05index\x18\x02 \x02(\r\x12\x0e\n\x06height\x18\x03 \x02(\r\x12\r\n\x05value\x18\x04 \x02(\x03\x12\x13\n\x0bis_coinbase\x18\x05 \x02(\x08*=\n\tFlushMode\x12\x08\n\x04None\x10\x00\x12\x0c\n\x08IfNeeded\x10\x01\x12\x0c\n\x08Periodic\x10\x02\x12\n\n\x06\x41lways\x10\x03')
+
+_globals = globals()
+_builder.BuildMessageAndEnumDescriptors(DESCRIPTOR, _globals)
+_builder.BuildTopDescriptorsAndMessages(DESCRIPTOR, 'utxocache_pb2', _globals)
+if not _descriptor._USE_C_DESCRIPTORS:
+  DESCRIPTOR._loaded_options = None
+  _globals['_FLUSHMODE']._serialized_start=422
+  _globals['_FLUSHMODE']._serialized_end=483
+  _globals['_UTXOCACHEEVENT']._serialized_start=31
+  _globals['_UTXOCACHEEVENT']._serialized_end=193
+  _globals['_FLUSH']._serialized_start=196
+  _globals['_FLUSH']._serialized_end=331
+  _globals['_COIN']._serialized_start=333
+  _globals['_COIN']._serialized_end=420
+# @@protoc_insertion_point(module_scope)
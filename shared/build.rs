@@ -17,7 +17,8 @@ fn main() {
         println!("Error while compiling protos: {}", e);
         panic!("Failed to code-gen the Rust structs from the Protobuf definitions");
     }
-    println!("cargo:rerun-if-changed=../protobuf/proto-types/event_msg.proto");
+    // The directory, as event_msg.proto imports the others
+    println!("cargo:rerun-if-changed=../protobuf/proto-types/");
 
     // Generate check functions for IP addresses
     gen_ip_match_fn(
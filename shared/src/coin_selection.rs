@@ -0,0 +1,97 @@
+use crate::ctypes;
+use std::fmt;
+
+// structs are generated via the coin_selection.proto file
+include!(concat!(env!("OUT_DIR"), "/coin_selection.rs"));
+
+impl From<ctypes::CoinSelectionSelectedCoins> for SelectedCoins {
+    fn from(selected: ctypes::CoinSelectionSelectedCoins) -> Self {
+        SelectedCoins {
+            wallet_name: selected.wallet_name(),
+            algorithm: selected.algorithm(),
+            target: selected.target,
+            waste: selected.waste,
+            selected_value: selected.selected_value,
+        }
+    }
+}
+
+impl fmt::Display for SelectedCoins {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "SelectedCoins(wallet={}, algorithm={}, target={}, waste={}, selected_value={})",
+            self.wallet_name, self.algorithm, self.target, self.waste, self.selected_value,
+        )
+    }
+}
+
+impl From<ctypes::CoinSelectionNormalCreateTx> for NormalCreateTx {
+    fn from(created: ctypes::CoinSelectionNormalCreateTx) -> Self {
+        NormalCreateTx {
+            wallet_name: created.wallet_name(),
+            success: created.success,
+            fee: created.fee,
+            change_pos: created.change_pos,
+        }
+    }
+}
+
+impl fmt::Display for NormalCreateTx {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "NormalCreateTx(wallet={}, success={}, fee={}, change_pos={})",
+            self.wallet_name, self.success, self.fee, self.change_pos,
+        )
+    }
+}
+
+impl From<ctypes::CoinSelectionAttemptingApsCreateTx> for AttemptingApsCreateTx {
+    fn from(attempting: ctypes::CoinSelectionAttemptingApsCreateTx) -> Self {
+        AttemptingApsCreateTx {
+            wallet_name: attempting.wallet_name(),
+        }
+    }
+}
+
+impl fmt::Display for AttemptingApsCreateTx {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "AttemptingApsCreateTx(wallet={})", self.wallet_name)
+    }
+}
+
+impl From<ctypes::CoinSelectionApsCreateTx> for ApsCreateTx {
+    fn from(created: ctypes::CoinSelectionApsCreateTx) -> Self {
+        ApsCreateTx {
+            wallet_name: created.wallet_name(),
+            use_aps: created.use_aps,
+            success: created.success,
+            fee: created.fee,
+            change_pos: created.change_pos,
+        }
+    }
+}
+
+impl fmt::Display for ApsCreateTx {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "ApsCreateTx(wallet={}, use_aps={}, success={}, fee={}, change_pos={})",
+            self.wallet_name, self.use_aps, self.success, self.fee, self.change_pos,
+        )
+    }
+}
+
+impl fmt::Display for coin_selection_event::Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            coin_selection_event::Event::SelectedCoins(selected) => write!(f, "{}", selected),
+            coin_selection_event::Event::NormalCreateTx(created) => write!(f, "{}", created),
+            coin_selection_event::Event::AttemptingApsCreateTx(attempting) => {
+                write!(f, "{}", attempting)
+            }
+            coin_selection_event::Event::ApsCreateTx(created) => write!(f, "{}", created),
+        }
+    }
+}
@@ -16,11 +16,45 @@ const MAX_MISBEHAVING_MESSAGE_LENGTH: usize = 128;
 
 /// Based on Bitcoin Core's MAX_PROTOCOL_MESSAGE_LENGTH. Longer messages are rejected.
 const MAX_P2P_MESSAGE_SIZE: usize = 4194304; // 4 MB
+/// A [`net_msg::DecodeFailure`] keeps at most this many bytes of the payload
+pub const MAX_DECODE_FAILURE_PAYLOAD: usize = 4096;
 
 const TXID_LENGTH: usize = 32;
 const REMOVAL_REASON_LENGTH: usize = 9;
 const REJECTION_REASON_LENGTH: usize = 118;
 const HASH_LENGTH: usize = 32;
+/// Longer wallet names are truncated
+const MAX_WALLET_NAME_LENGTH: usize = 128;
+const MAX_ALGORITHM_LENGTH: usize = 16;
+
+/// The string in a NUL-terminated C char array
+fn c_str(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|c| *c == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
+
+/// Every event starts with this header. Mirrors `struct EventHeader` in
+/// tracing.bpf.c.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct EventHeader {
+    /// `bpf_ktime_get_ns()` when the tracepoint fired. This is the
+    /// CLOCK_MONOTONIC time in nanoseconds.
+    pub ktime_ns: u64,
+    /// The number of events recorded before this one. Orders the events
+    /// of all ring buffers.
+    pub seq: u64,
+    /// The pid of the bitcoind process
+    pub pid: u32,
+    /// The index of the target the tracepoint was attached for
+    pub target: u32,
+}
+
+impl EventHeader {
+    pub fn from_bytes(x: &[u8]) -> Self {
+        unsafe { ptr::read_unaligned(x.as_ptr() as *const Self) }
+    }
+}
 
 /// The metadata for a P2P message.
 #[repr(C)]
@@ -37,19 +71,17 @@ pub struct P2PMessageMetadata {
 impl P2PMessageMetadata {
     // TODO: comment
     pub fn peer_addr(&self) -> String {
-        String::from_utf8_lossy(&self.peer_addr.split(|c| *c == 0x00u8).next().unwrap())
-            .into_owned()
+        c_str(&self.peer_addr)
     }
 
     // TODO: comment
     pub fn peer_conn_type(&self) -> String {
-        String::from_utf8_lossy(&self.peer_conn_type.split(|c| *c == 0x00u8).next().unwrap())
-            .into_owned()
+        c_str(&self.peer_conn_type)
     }
 
     // TODO: comment
     pub fn msg_type(&self) -> String {
-        String::from_utf8_lossy(&self.msg_type.split(|c| *c == 0x00u8).next().unwrap()).into_owned()
+        c_str(&self.msg_type)
     }
 
     pub fn from_bytes(x: &[u8]) -> Self {
@@ -86,18 +118,30 @@ impl fmt::Display for P2PMessageMetadata {
 }
 
 pub struct P2PMessage {
+    pub header: EventHeader,
     pub meta: P2PMessageMetadata,
     pub payload: Vec<u8>,
 }
 
 impl P2PMessage {
+    /// Parses a P2P message. The payload ends at the end of `x`, if that is
+    /// before the end of the message. Records with only the metadata have
+    /// an empty payload.
     pub fn from_bytes(x: &[u8]) -> P2PMessage {
+        const SIZEOF_HEADER_STRUCT: usize = mem::size_of::<EventHeader>();
         const SIZEOF_METADATA_STRUCT: usize = mem::size_of::<P2PMessageMetadata>();
-        let meta_bytes = &x[..SIZEOF_METADATA_STRUCT];
+        const PAYLOAD_START: usize = SIZEOF_HEADER_STRUCT + SIZEOF_METADATA_STRUCT;
+        let header = EventHeader::from_bytes(x);
+        let meta_bytes = &x[SIZEOF_HEADER_STRUCT..PAYLOAD_START];
         let meta = unsafe { ptr::read_unaligned(meta_bytes.as_ptr() as *const P2PMessageMetadata) };
         let payload_size = cmp::min(meta.msg_size as usize, MAX_P2P_MESSAGE_SIZE);
-        let payload = x[SIZEOF_METADATA_STRUCT..SIZEOF_METADATA_STRUCT + payload_size].to_vec();
-        P2PMessage { meta, payload }
+        let payload_end = cmp::min(PAYLOAD_START + payload_size, x.len());
+        let payload = x[PAYLOAD_START..payload_end].to_vec();
+        P2PMessage {
+            header,
+            meta,
+            payload,
+        }
     }
 
     pub fn decode_to_protobuf_network_message(
@@ -118,13 +162,12 @@ pub struct Connection {
 impl Connection {
     // TODO: comment
     pub fn addr(&self) -> String {
-        String::from_utf8_lossy(&self.addr.split(|c| *c == 0x00u8).next().unwrap()).into_owned()
+        c_str(&self.addr)
     }
 
     // TODO: comment
     pub fn conn_type(&self) -> String {
-        String::from_utf8_lossy(&self.conn_type.split(|c| *c == 0x00u8).next().unwrap())
-            .into_owned()
+        c_str(&self.conn_type)
     }
 }
 
@@ -143,6 +186,8 @@ impl fmt::Display for Connection {
 
 #[repr(C)]
 pub struct ClosedConnection {
+    /// When the tracepoint fired
+    pub header: EventHeader,
     /// The connection being closed
     pub connection: Connection,
     /// Connection established UNIX epoch timestamp
@@ -167,6 +212,8 @@ impl ClosedConnection {
 
 #[repr(C)]
 pub struct InboundConnection {
+    /// When the tracepoint fired
+    pub header: EventHeader,
     /// The inbound connection being opened
     pub connection: Connection,
     /// Number of inbound connections existing (not including this newly opened one)
@@ -191,6 +238,8 @@ impl fmt::Display for InboundConnection {
 
 #[repr(C)]
 pub struct OutboundConnection {
+    /// When the tracepoint fired
+    pub header: EventHeader,
     /// The outbound connection being opened
     pub connection: Connection,
     /// Number of outbound connections existing (not including this newly opened one)
@@ -215,6 +264,8 @@ impl fmt::Display for OutboundConnection {
 
 #[repr(C)]
 pub struct MisbehavingConnection {
+    /// When the tracepoint fired
+    pub header: EventHeader,
     pub id: u64,
     pub score_before: i32,
     pub score_increase: i32,
@@ -229,7 +280,7 @@ impl MisbehavingConnection {
 
     // TODO: comment
     pub fn message(&self) -> String {
-        String::from_utf8_lossy(&self.message.split(|c| *c == 0x00u8).next().unwrap()).into_owned()
+        c_str(&self.message)
     }
 }
 
@@ -249,6 +300,8 @@ impl fmt::Display for MisbehavingConnection {
 
 #[repr(C)]
 pub struct MempoolAdded {
+    /// When the tracepoint fired
+    pub header: EventHeader,
     /// Txid of the added transaction
     pub txid: [u8; TXID_LENGTH],
     /// Vsize of the added transaction
@@ -277,6 +330,8 @@ impl MempoolAdded {
 
 #[repr(C)]
 pub struct MempoolRemoved {
+    /// When the tracepoint fired
+    pub header: EventHeader,
     /// Txid of the removed transaction
     pub txid: [u8; TXID_LENGTH],
     /// Removal reason of the transaction
@@ -306,7 +361,7 @@ impl fmt::Display for MempoolRemoved {
 impl MempoolRemoved {
     /// Returns the removal reason as String
     pub fn reason(&self) -> String {
-        String::from_utf8_lossy(&self.reason.split(|c| *c == 0x00u8).next().unwrap()).into_owned()
+        c_str(&self.reason)
     }
 
     pub fn from_bytes(x: &[u8]) -> MempoolRemoved {
@@ -316,6 +371,8 @@ impl MempoolRemoved {
 
 #[repr(C)]
 pub struct MempoolReplaced {
+    /// When the tracepoint fired
+    pub header: EventHeader,
     /// Txid of the replaced transaction
     pub replaced_txid: [u8; TXID_LENGTH],
     /// Virtual size of the replaced transaction
@@ -356,6 +413,8 @@ impl fmt::Display for MempoolReplaced {
 
 #[repr(C)]
 pub struct MempoolRejected {
+    /// When the tracepoint fired
+    pub header: EventHeader,
     /// Txid of the added transaction
     pub txid: [u8; TXID_LENGTH],
     /// Reason why the transaction was rejected
@@ -376,7 +435,7 @@ impl fmt::Display for MempoolRejected {
 impl MempoolRejected {
     /// Returns the rejection reason as String
     pub fn reason(&self) -> String {
-        String::from_utf8_lossy(&self.reason.split(|c| *c == 0x00u8).next().unwrap()).into_owned()
+        c_str(&self.reason)
     }
 
     pub fn from_bytes(x: &[u8]) -> MempoolRejected {
@@ -386,6 +445,8 @@ impl MempoolRejected {
 
 #[repr(C)]
 pub struct ValidationBlockConnected {
+    /// When the tracepoint fired
+    pub header: EventHeader,
     /// Hash of the connected block
     pub hash: [u8; HASH_LENGTH],
     /// Height of the connected block
@@ -396,7 +457,9 @@ pub struct ValidationBlockConnected {
     pub inputs: i32,
     /// Number of sigops in the connected block
     pub sigops: u64,
-    /// Time it took to connect the block in microseconds (µs)
+    /// Time it took to connect the block. In microseconds (µs) up to Bitcoin
+    /// Core 24.x, in nanoseconds (ns) since 25.x, see
+    /// [`crate::validation::ConnectionTimeUnit`].
     pub connection_time: u64,
 }
 
@@ -404,7 +467,7 @@ impl fmt::Display for ValidationBlockConnected {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
             f,
-            "ValidationBlockConnected(hash={}, height={}, transactions={}, inputs={}, sigops={}, time={}µs)",
+            "ValidationBlockConnected(hash={}, height={}, transactions={}, inputs={}, sigops={}, time={})",
             bitcoin::BlockHash::from_slice(&self.hash).unwrap(),
             self.height, self.transactions, self.inputs, self.sigops, self.connection_time,
         )
@@ -417,6 +480,278 @@ impl ValidationBlockConnected {
     }
 }
 
+#[repr(C)]
+pub struct UtxocacheFlush {
+    /// When the tracepoint fired
+    pub header: EventHeader,
+    /// Time it took to flush in microseconds (µs)
+    pub duration: i64,
+    /// The flush state mode: NONE (0), IF_NEEDED (1), PERIODIC (2) or ALWAYS (3)
+    pub mode: u32,
+    /// Number of coins in the cache before the flush
+    pub coins_count: u64,
+    /// Memory usage of the cache in bytes before the flush
+    pub coins_mem_usage: u64,
+    /// If the flush was caused by pruning
+    pub is_flush_for_prune: bool,
+}
+
+impl fmt::Display for UtxocacheFlush {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "UtxocacheFlush(duration={}µs, mode={}, coins={}, mem_usage={}, for_prune={})",
+            self.duration,
+            self.mode,
+            self.coins_count,
+            self.coins_mem_usage,
+            self.is_flush_for_prune,
+        )
+    }
+}
+
+impl UtxocacheFlush {
+    pub fn from_bytes(x: &[u8]) -> Self {
+        unsafe { ptr::read_unaligned(x.as_ptr() as *const Self) }
+    }
+}
+
+/// A coin added to, spent from or uncached from the UTXO cache. The
+/// `utxocache:add`, `utxocache:spent` and `utxocache:uncache` tracepoints
+/// pass the same arguments.
+#[repr(C)]
+pub struct UtxocacheCoin {
+    /// When the tracepoint fired
+    pub header: EventHeader,
+    /// Txid of the transaction creating the coin
+    pub txid: [u8; TXID_LENGTH],
+    /// Output index of the coin in the transaction
+    pub index: u32,
+    /// Height of the block the coin was created in
+    pub height: u32,
+    /// Value of the coin in satoshis
+    pub value: i64,
+    /// If the coin was created by a coinbase transaction
+    pub is_coinbase: bool,
+}
+
+impl fmt::Display for UtxocacheCoin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "UtxocacheCoin(outpoint={}:{}, height={}, value={}, coinbase={})",
+            bitcoin::Txid::from_slice(&self.txid).unwrap(),
+            self.index,
+            self.height,
+            self.value,
+            self.is_coinbase,
+        )
+    }
+}
+
+impl UtxocacheCoin {
+    pub fn from_bytes(x: &[u8]) -> Self {
+        unsafe { ptr::read_unaligned(x.as_ptr() as *const Self) }
+    }
+}
+
+#[repr(C)]
+pub struct CoinSelectionSelectedCoins {
+    /// When the tracepoint fired
+    pub header: EventHeader,
+    /// Name of the wallet
+    pub wallet_name: [u8; MAX_WALLET_NAME_LENGTH],
+    /// Coin selection algorithm of the selected solution, e.g. "bnb", "knapsack", "srd" or "cg"
+    pub algorithm: [u8; MAX_ALGORITHM_LENGTH],
+    /// Selection target value in satoshis
+    pub target: i64,
+    /// Waste metric of the selected solution in satoshis
+    pub waste: i64,
+    /// Total value of the selected inputs in satoshis
+    pub selected_value: i64,
+}
+
+impl fmt::Display for CoinSelectionSelectedCoins {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "CoinSelectionSelectedCoins(wallet={}, algorithm={}, target={}, waste={}, selected_value={})",
+            self.wallet_name(),
+            self.algorithm(),
+            self.target,
+            self.waste,
+            self.selected_value,
+        )
+    }
+}
+
+impl CoinSelectionSelectedCoins {
+    /// Returns the wallet name as String
+    pub fn wallet_name(&self) -> String {
+        c_str(&self.wallet_name)
+    }
+
+    /// Returns the coin selection algorithm as String
+    pub fn algorithm(&self) -> String {
+        c_str(&self.algorithm)
+    }
+
+    pub fn from_bytes(x: &[u8]) -> Self {
+        unsafe { ptr::read_unaligned(x.as_ptr() as *const Self) }
+    }
+}
+
+#[repr(C)]
+pub struct CoinSelectionNormalCreateTx {
+    /// When the tracepoint fired
+    pub header: EventHeader,
+    /// Name of the wallet
+    pub wallet_name: [u8; MAX_WALLET_NAME_LENGTH],
+    /// If creating the transaction succeeded
+    pub success: bool,
+    /// Expected fee of the transaction in satoshis
+    pub fee: i64,
+    /// Position of the change output, -1 if there is none
+    pub change_pos: i32,
+}
+
+impl fmt::Display for CoinSelectionNormalCreateTx {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "CoinSelectionNormalCreateTx(wallet={}, success={}, fee={}, change_pos={})",
+            self.wallet_name(),
+            self.success,
+            self.fee,
+            self.change_pos,
+        )
+    }
+}
+
+impl CoinSelectionNormalCreateTx {
+    /// Returns the wallet name as String
+    pub fn wallet_name(&self) -> String {
+        c_str(&self.wallet_name)
+    }
+
+    pub fn from_bytes(x: &[u8]) -> Self {
+        unsafe { ptr::read_unaligned(x.as_ptr() as *const Self) }
+    }
+}
+
+#[repr(C)]
+pub struct CoinSelectionAttemptingApsCreateTx {
+    /// When the tracepoint fired
+    pub header: EventHeader,
+    /// Name of the wallet
+    pub wallet_name: [u8; MAX_WALLET_NAME_LENGTH],
+}
+
+impl fmt::Display for CoinSelectionAttemptingApsCreateTx {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "CoinSelectionAttemptingApsCreateTx(wallet={})",
+            self.wallet_name(),
+        )
+    }
+}
+
+impl CoinSelectionAttemptingApsCreateTx {
+    /// Returns the wallet name as String
+    pub fn wallet_name(&self) -> String {
+        c_str(&self.wallet_name)
+    }
+
+    pub fn from_bytes(x: &[u8]) -> Self {
+        unsafe { ptr::read_unaligned(x.as_ptr() as *const Self) }
+    }
+}
+
+/// The second, avoid-partial-spends (APS) attempt at creating a transaction
+#[repr(C)]
+pub struct CoinSelectionApsCreateTx {
+    /// When the tracepoint fired
+    pub header: EventHeader,
+    /// Name of the wallet
+    pub wallet_name: [u8; MAX_WALLET_NAME_LENGTH],
+    /// If the wallet uses the APS transaction over the normal one
+    pub use_aps: bool,
+    /// If creating the transaction succeeded
+    pub success: bool,
+    /// Expected fee of the transaction in satoshis
+    pub fee: i64,
+    /// Position of the change output, -1 if there is none
+    pub change_pos: i32,
+}
+
+impl fmt::Display for CoinSelectionApsCreateTx {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "CoinSelectionApsCreateTx(wallet={}, use_aps={}, success={}, fee={}, change_pos={})",
+            self.wallet_name(),
+            self.use_aps,
+            self.success,
+            self.fee,
+            self.change_pos,
+        )
+    }
+}
+
+impl CoinSelectionApsCreateTx {
+    /// Returns the wallet name as String
+    pub fn wallet_name(&self) -> String {
+        c_str(&self.wallet_name)
+    }
+
+    pub fn from_bytes(x: &[u8]) -> Self {
+        unsafe { ptr::read_unaligned(x.as_ptr() as *const Self) }
+    }
+}
+
+/// The events lost in the kernel for one ring buffer, from the
+/// `ringbuf_stats` BPF map
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RingBufferStats {
+    /// Events dropped because the ring buffer was full
+    pub reserve_failures: u64,
+    /// P2P messages larger than the largest ring buffer entry
+    pub oversized: u64,
+    /// Events with data that could not be read from bitcoind's memory
+    pub read_failures: u64,
+}
+
+impl RingBufferStats {
+    pub fn from_bytes(x: &[u8]) -> Self {
+        unsafe { ptr::read_unaligned(x.as_ptr() as *const Self) }
+    }
+
+    /// The number of lost events
+    pub fn lost(&self) -> u64 {
+        self.reserve_failures + self.oversized
+    }
+}
+
+impl std::ops::AddAssign for RingBufferStats {
+    fn add_assign(&mut self, other: Self) {
+        self.reserve_failures += other.reserve_failures;
+        self.oversized += other.oversized;
+        self.read_failures += other.read_failures;
+    }
+}
+
+impl fmt::Display for RingBufferStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "RingBufferStats(reserve_failures={}, oversized={}, read_failures={})",
+            self.reserve_failures, self.oversized, self.read_failures,
+        )
+    }
+}
+
 fn decode_network_message(
     meta: &P2PMessageMetadata,
     payload: &[u8],
@@ -501,6 +836,17 @@ impl P2PMessageDecodeError {
     fn new(meta: P2PMessageMetadata, error: consensus::encode::Error) -> Self {
         P2PMessageDecodeError { meta, error }
     }
+
+    /// The failure as protobuf message with the raw `payload` of the message,
+    /// truncated to [`MAX_DECODE_FAILURE_PAYLOAD`] bytes
+    pub fn to_protobuf_decode_failure(&self, payload: &[u8]) -> net_msg::DecodeFailure {
+        net_msg::DecodeFailure {
+            command: self.meta.msg_type(),
+            payload: payload[..payload.len().min(MAX_DECODE_FAILURE_PAYLOAD)].to_vec(),
+            error: self.error.to_string(),
+            payload_size: payload.len() as u64,
+        }
+    }
 }
 
 impl fmt::Display for P2PMessageDecodeError {
@@ -511,6 +857,8 @@ impl fmt::Display for P2PMessageDecodeError {
 
 #[repr(C)]
 pub struct AddrmanInsertNew {
+    /// When the tracepoint fired
+    pub header: EventHeader,
     pub inserted: bool,
     pub bucket: i32,
     pub bucket_pos: i32,
@@ -523,12 +871,12 @@ pub struct AddrmanInsertNew {
 impl AddrmanInsertNew {
     // TODO: comment
     pub fn addr(&self) -> String {
-        String::from_utf8_lossy(&self.addr.split(|c| *c == 0x00u8).next().unwrap()).into_owned()
+        c_str(&self.addr)
     }
 
     // TODO: comment
     pub fn source(&self) -> String {
-        String::from_utf8_lossy(&self.source.split(|c| *c == 0x00u8).next().unwrap()).into_owned()
+        c_str(&self.source)
     }
 
     pub fn from_bytes(x: &[u8]) -> AddrmanInsertNew {
@@ -554,6 +902,8 @@ impl fmt::Display for AddrmanInsertNew {
 
 #[repr(C)]
 pub struct AddrmanInsertTried {
+    /// When the tracepoint fired
+    pub header: EventHeader,
     pub bucket: i32,
     pub bucket_pos: i32,
     pub addr: [u8; MAX_PEER_ADDR_LENGTH],
@@ -565,12 +915,12 @@ pub struct AddrmanInsertTried {
 impl AddrmanInsertTried {
     // TODO: comment
     pub fn addr(&self) -> String {
-        String::from_utf8_lossy(&self.addr.split(|c| *c == 0x00u8).next().unwrap()).into_owned()
+        c_str(&self.addr)
     }
 
     // TODO: comment
     pub fn source(&self) -> String {
-        String::from_utf8_lossy(&self.source.split(|c| *c == 0x00u8).next().unwrap()).into_owned()
+        c_str(&self.source)
     }
 
     pub fn from_bytes(x: &[u8]) -> AddrmanInsertTried {
@@ -602,24 +952,87 @@ mod tests {
     fn p2p_message_from_bytes_1() {
         // The actual message ends after the "92e4200d3021c21b" payload. It's a few bytes larger
         // on purpose to test that it's still parsed correctly.
-        let data_hex = "c79e9300000000003230392e3232322e3235322e34303a36343830390000000069746e6573732076657273696f6e20726573657276656420666f7220736f66742d666f726b20757067726164696e626f756e64005583899738227ad1576a13fc70696e6700000000f5d60e67005930cb080000000000000092e4200d3021c21b649b92000000000033";
+        let data_hex = "00e40b54020000002a00000000000000d204000001000000c79e9300000000003230392e3232322e3235322e34303a36343830390000000069746e6573732076657273696f6e20726573657276656420666f7220736f66742d666f726b20757067726164696e626f756e64005583899738227ad1576a13fc70696e6700000000f5d60e67005930cb080000000000000092e4200d3021c21b649b92000000000033";
         let data = hex::decode(data_hex).unwrap();
         let message = P2PMessage::from_bytes(&data);
 
+        assert_eq!(message.header.ktime_ns, 10_000_000_000u64);
+        assert_eq!(message.header.seq, 42u64);
+        assert_eq!(message.header.pid, 1234u32);
+        assert_eq!(message.header.target, 1u32);
         assert_eq!(message.meta.peer_id, 9674439u64);
         assert_eq!(message.meta.peer_addr(), "209.222.252.40:64809");
         assert_eq!(message.meta.peer_conn_type(), "inbound");
         assert_eq!(message.meta.msg_type(), "ping");
-        assert_eq!(message.meta.msg_inbound, false);
+        assert!(!message.meta.msg_inbound);
         assert_eq!(message.meta.msg_size, 8u64);
         assert_eq!(message.payload, hex::decode("92e4200d3021c21b").unwrap());
 
         message.decode_to_protobuf_network_message().unwrap();
     }
 
+    #[test]
+    fn p2p_message_decode_failure() {
+        // The same ping as above, cut off after 3 of the 8 payload bytes
+        let data_hex = "00e40b54020000002a00000000000000d204000001000000c79e9300000000003230392e3232322e3235322e34303a36343830390000000069746e6573732076657273696f6e20726573657276656420666f7220736f66742d666f726b20757067726164696e626f756e64005583899738227ad1576a13fc70696e6700000000f5d60e67005930cb080000000000000092e420";
+        let data = hex::decode(data_hex).unwrap();
+        let message = P2PMessage::from_bytes(&data);
+        assert_eq!(message.payload, hex::decode("92e420").unwrap());
+
+        let error = message.decode_to_protobuf_network_message().unwrap_err();
+        let failure = error.to_protobuf_decode_failure(&message.payload);
+        assert_eq!(failure.command, "ping");
+        assert_eq!(failure.payload, hex::decode("92e420").unwrap());
+        assert_eq!(failure.payload_size, 3);
+        assert!(!failure.error.is_empty());
+
+        let failure = error.to_protobuf_decode_failure(&[0xab; MAX_P2P_MESSAGE_SIZE]);
+        assert_eq!(failure.payload, [0xab; MAX_DECODE_FAILURE_PAYLOAD]);
+        assert_eq!(failure.payload_size, MAX_P2P_MESSAGE_SIZE as u64);
+    }
+
+    #[test]
+    fn utxocache_coin_from_bytes() {
+        let mut data = vec![0u8; mem::size_of::<EventHeader>()];
+        data.extend([0xab; TXID_LENGTH]);
+        data.extend(1u32.to_le_bytes());
+        data.extend(840_000u32.to_le_bytes());
+        data.extend(312_500_000i64.to_le_bytes());
+        data.push(1);
+        data.resize(mem::size_of::<UtxocacheCoin>(), 0);
+        let coin = UtxocacheCoin::from_bytes(&data);
+
+        assert_eq!(coin.txid, [0xab; TXID_LENGTH]);
+        assert_eq!(coin.index, 1);
+        assert_eq!(coin.height, 840_000);
+        assert_eq!(coin.value, 312_500_000);
+        assert!(coin.is_coinbase);
+    }
+
+    #[test]
+    fn coin_selection_selected_coins_from_bytes() {
+        let mut data = vec![0u8; mem::size_of::<EventHeader>()];
+        let mut wallet_name = b"hot".to_vec();
+        wallet_name.resize(MAX_WALLET_NAME_LENGTH, 0);
+        data.extend(wallet_name);
+        let mut algorithm = b"bnb".to_vec();
+        algorithm.resize(MAX_ALGORITHM_LENGTH, 0);
+        data.extend(algorithm);
+        data.extend(100_000i64.to_le_bytes());
+        data.extend((-120i64).to_le_bytes());
+        data.extend(100_250i64.to_le_bytes());
+        let selected = CoinSelectionSelectedCoins::from_bytes(&data);
+
+        assert_eq!(selected.wallet_name(), "hot");
+        assert_eq!(selected.algorithm(), "bnb");
+        assert_eq!(selected.target, 100_000);
+        assert_eq!(selected.waste, -120);
+        assert_eq!(selected.selected_value, 100_250);
+    }
+
     #[test]
     fn p2p_message_from_bytes_huge() {
-        let metadata_hex = "c79e9300000000003230392e3232322e3235322e34303a36343830390000000069746e6573732076657273696f6e20726573657276656420666f7220736f66742d666f726b20757067726164696e626f756e64005583899738227ad1576a13fc70696e6700000000f5d60e67005930cb080000000000000092e4200d3021c21b";
+        let metadata_hex = "000000000000000000000000000000000000000000000000c79e9300000000003230392e3232322e3235322e34303a36343830390000000069746e6573732076657273696f6e20726573657276656420666f7220736f66742d666f726b20757067726164696e626f756e64005583899738227ad1576a13fc70696e6700000000f5d60e67005930cb080000000000000092e4200d3021c21b";
         let metadata = hex::decode(metadata_hex).unwrap();
         let max_len_payload = vec![0; MAX_P2P_MESSAGE_SIZE];
 
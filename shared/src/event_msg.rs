@@ -16,7 +16,30 @@ impl EventMsg {
         EventMsg {
             timestamp,
             timestamp_subsec_micros,
+            kernel_timestamp_ns: None,
+            kernel_sequence: None,
+            pid: None,
+            source: None,
             event: Some(event),
         }
     }
+
+    /// Set the time (nanoseconds since UNIX epoch) the tracepoint fired
+    pub fn with_kernel_timestamp_ns(mut self, ns: u64) -> EventMsg {
+        self.kernel_timestamp_ns = Some(ns);
+        self
+    }
+
+    /// Set the sequence number the kernel assigned to the event
+    pub fn with_kernel_sequence(mut self, seq: u64) -> EventMsg {
+        self.kernel_sequence = Some(seq);
+        self
+    }
+
+    /// Set the label and pid of the bitcoind process the event is from
+    pub fn with_source(mut self, source: String, pid: u32) -> EventMsg {
+        self.source = Some(source);
+        self.pid = Some(pid);
+        self
+    }
 }
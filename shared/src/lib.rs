@@ -1,11 +1,15 @@
 #![cfg_attr(feature = "strict", deny(warnings))]
 
+// Re-exported so the tools use the versions shared depends on
 pub extern crate bitcoin;
+pub extern crate clap;
 pub extern crate log;
 pub extern crate nats;
 pub extern crate prost;
+pub extern crate simple_logger;
 
 pub mod addrman;
+pub mod coin_selection;
 pub mod ctypes;
 pub mod event_msg;
 pub mod mempool;
@@ -13,6 +17,7 @@ pub mod nats_subjects;
 pub mod net_conn;
 pub mod net_msg;
 pub mod primitive;
+pub mod utxocache;
 pub mod validation;
 
 /// Utillity functions shared among peer-observer tools
@@ -5,6 +5,8 @@ const NATS_SUBJECT_MEMPOOL: &str = "mempool";
 const NATS_SUBJECT_NETMSG: &str = "netmsg";
 const NATS_SUBJECT_NETCONN: &str = "netconn";
 const NATS_SUBJECT_VALIDATION: &str = "validation";
+const NATS_SUBJECT_UTXOCACHE: &str = "utxocache";
+const NATS_SUBJECT_COIN_SELECTION: &str = "coinselection";
 
 pub enum Subject {
     Addrman,
@@ -12,6 +14,8 @@ pub enum Subject {
     NetMsg,
     NetConn,
     Validation,
+    Utxocache,
+    CoinSelection,
 }
 
 impl fmt::Display for Subject {
@@ -22,6 +26,8 @@ impl fmt::Display for Subject {
             Subject::NetConn => write!(f, "{}", NATS_SUBJECT_NETCONN),
             Subject::NetMsg => write!(f, "{}", NATS_SUBJECT_NETMSG),
             Subject::Validation => write!(f, "{}", NATS_SUBJECT_VALIDATION),
+            Subject::Utxocache => write!(f, "{}", NATS_SUBJECT_UTXOCACHE),
+            Subject::CoinSelection => write!(f, "{}", NATS_SUBJECT_COIN_SELECTION),
         }
     }
 }
@@ -558,6 +558,19 @@ impl fmt::Display for FilterLoad {
     }
 }
 
+impl fmt::Display for DecodeFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "DecodeFailure(command={}, error={}, payload_size={}, payload={})",
+            self.command,
+            self.error,
+            self.payload_size,
+            self.payload.to_lower_hex_string(),
+        )
+    }
+}
+
 impl fmt::Display for Unknown {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
@@ -634,6 +647,7 @@ impl fmt::Display for message::Msg {
             message::Msg::Sendaddrv2(_) => write!(f, "sendaddrv2"),
             message::Msg::Emptyaddrv2(_) => write!(f, "empty_addrv2"),
             message::Msg::Oldping(_) => write!(f, "old_ping"),
+            message::Msg::DecodeFailure(failure) => write!(f, "{}", failure),
         }
     }
 }
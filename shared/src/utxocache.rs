@@ -0,0 +1,69 @@
+use crate::bitcoin::hashes::Hash;
+use crate::ctypes;
+use std::fmt;
+
+// structs are generated via the utxocache.proto file
+include!(concat!(env!("OUT_DIR"), "/utxocache.rs"));
+
+impl From<ctypes::UtxocacheFlush> for Flush {
+    fn from(flush: ctypes::UtxocacheFlush) -> Self {
+        Flush {
+            duration: flush.duration,
+            mode: flush.mode as i32,
+            coins_count: flush.coins_count,
+            coins_mem_usage: flush.coins_mem_usage,
+            is_flush_for_prune: flush.is_flush_for_prune,
+        }
+    }
+}
+
+impl fmt::Display for Flush {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Flush(duration={}µs, mode={:?}, coins={}, mem_usage={}, for_prune={})",
+            self.duration,
+            self.mode(),
+            self.coins_count,
+            self.coins_mem_usage,
+            self.is_flush_for_prune,
+        )
+    }
+}
+
+impl From<ctypes::UtxocacheCoin> for Coin {
+    fn from(coin: ctypes::UtxocacheCoin) -> Self {
+        Coin {
+            txid: coin.txid.to_vec(),
+            index: coin.index,
+            height: coin.height,
+            value: coin.value,
+            is_coinbase: coin.is_coinbase,
+        }
+    }
+}
+
+impl fmt::Display for Coin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Coin({}:{}, height={}, value={}, coinbase={})",
+            bitcoin::Txid::from_slice(&self.txid).unwrap(),
+            self.index,
+            self.height,
+            self.value,
+            self.is_coinbase,
+        )
+    }
+}
+
+impl fmt::Display for utxocache_event::Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            utxocache_event::Event::Flush(flush) => write!(f, "{}", flush),
+            utxocache_event::Event::Add(coin) => write!(f, "Add{}", coin),
+            utxocache_event::Event::Spent(coin) => write!(f, "Spent{}", coin),
+            utxocache_event::Event::Uncache(coin) => write!(f, "Uncache{}", coin),
+        }
+    }
+}
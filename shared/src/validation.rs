@@ -1,11 +1,55 @@
 use crate::bitcoin::hashes::Hash;
 use crate::ctypes;
+use std::collections::VecDeque;
 use std::fmt;
 
 // structs are generated via the validation.proto file
 include!(concat!(env!("OUT_DIR"), "/validation.rs"));
 
+/// The number of blocks [`BlockSummaries`] computes the rolling percentiles
+/// over by default: about a day of blocks.
+pub const DEFAULT_SUMMARY_WINDOW: usize = 144;
+
+/// The unit of the connection time `validation:block_connected` passes. Due
+/// to an undetected API break, 23.x and 24.x pass microseconds, while 25.x
+/// and later pass nanoseconds.
+/// See https://github.com/bitcoin/bitcoin/pull/29877
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionTimeUnit {
+    Microseconds,
+    Nanoseconds,
+}
+
+impl ConnectionTimeUnit {
+    /// The unit of the Bitcoin Core release with the given major version
+    pub fn for_major_version(major: u32) -> Self {
+        match major {
+            // 0.x releases had no tracepoints, 22.x no validation:block_connected
+            0..=24 => ConnectionTimeUnit::Microseconds,
+            _ => ConnectionTimeUnit::Nanoseconds,
+        }
+    }
+
+    /// Convert a connection time in this unit to microseconds (µs)
+    pub fn to_micros(self, time: i64) -> i64 {
+        match self {
+            ConnectionTimeUnit::Microseconds => time,
+            ConnectionTimeUnit::Nanoseconds => time / 1000,
+        }
+    }
+}
+
+impl fmt::Display for ConnectionTimeUnit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConnectionTimeUnit::Microseconds => write!(f, "µs"),
+            ConnectionTimeUnit::Nanoseconds => write!(f, "ns"),
+        }
+    }
+}
+
 impl From<ctypes::ValidationBlockConnected> for BlockConnected {
+    /// The connection time is passed through as is, see [`ConnectionTimeUnit`]
     fn from(connected: ctypes::ValidationBlockConnected) -> Self {
         BlockConnected {
             hash: connected.hash.to_vec(),
@@ -14,6 +58,7 @@ impl From<ctypes::ValidationBlockConnected> for BlockConnected {
             inputs: connected.inputs,
             sigops: connected.sigops as i64,
             connection_time: connected.connection_time as i64,
+            bitcoind_version: None,
         }
     }
 }
@@ -40,3 +85,215 @@ impl fmt::Display for validation_event::Event {
         }
     }
 }
+
+/// The 50th, 90th and 99th percentile of a rolling window
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Percentiles {
+    pub p50: f64,
+    pub p90: f64,
+    pub p99: f64,
+}
+
+impl Percentiles {
+    /// Nearest-rank percentiles of the values, `None` without values
+    fn of(values: impl Iterator<Item = f64>) -> Option<Percentiles> {
+        let mut values: Vec<f64> = values.collect();
+        if values.is_empty() {
+            return None;
+        }
+        values.sort_by(f64::total_cmp);
+        let rank = |p: f64| {
+            let index = (p / 100.0 * values.len() as f64).ceil() as usize;
+            values[index.clamp(1, values.len()) - 1]
+        };
+        Some(Percentiles {
+            p50: rank(50.0),
+            p90: rank(90.0),
+            p99: rank(99.0),
+        })
+    }
+}
+
+impl fmt::Display for Percentiles {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "p50={:.3}µs, p90={:.3}µs, p99={:.3}µs",
+            self.p50, self.p90, self.p99
+        )
+    }
+}
+
+/// How long connecting a block took relative to its size, and how it
+/// compares to the blocks before it. See [`BlockSummaries`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockSummary {
+    pub hash: bitcoin::BlockHash,
+    pub height: i32,
+    /// Time it took to connect the block in microseconds (µs)
+    pub connection_time: i64,
+    /// Connection time per input in µs. `None` for a block without inputs.
+    pub time_per_input: Option<f64>,
+    /// Connection time per sigop in µs. `None` for a block without sigops.
+    pub time_per_sigop: Option<f64>,
+    /// The number of blocks in the rolling window, including this one
+    pub window: usize,
+    /// Percentiles of the connection time over the rolling window
+    pub connection_time_percentiles: Percentiles,
+    /// Percentiles of the connection time per input over the rolling window
+    pub time_per_input_percentiles: Option<Percentiles>,
+    /// Percentiles of the connection time per sigop over the rolling window
+    pub time_per_sigop_percentiles: Option<Percentiles>,
+}
+
+impl fmt::Display for BlockSummary {
+    // Only the percentiles of the connection time, to keep it readable
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let per = |time: Option<f64>| time.map_or("-".to_string(), |t| format!("{:.3}µs", t));
+        write!(
+            f,
+            "BlockSummary(hash={}, height={}, time={}µs, per_input={}, per_sigop={}, last {} blocks({}))",
+            self.hash,
+            self.height,
+            self.connection_time,
+            per(self.time_per_input),
+            per(self.time_per_sigop),
+            self.window,
+            self.connection_time_percentiles,
+        )
+    }
+}
+
+/// Derives a [`BlockSummary`] for each connected block, with percentiles over
+/// a rolling window of the last blocks. The connection times have to be in
+/// microseconds, as bitcointap publishes them.
+#[derive(Debug, Clone)]
+pub struct BlockSummaries {
+    window: usize,
+    /// (connection time, time per input, time per sigop) of the last blocks
+    blocks: VecDeque<(i64, Option<f64>, Option<f64>)>,
+}
+
+impl Default for BlockSummaries {
+    fn default() -> Self {
+        BlockSummaries::new(DEFAULT_SUMMARY_WINDOW)
+    }
+}
+
+impl BlockSummaries {
+    /// Compute the percentiles over the last `window` blocks
+    pub fn new(window: usize) -> Self {
+        let window = window.max(1);
+        BlockSummaries {
+            window,
+            blocks: VecDeque::with_capacity(window),
+        }
+    }
+
+    /// Add a connected block to the window and summarize it
+    pub fn push(&mut self, connected: &BlockConnected) -> BlockSummary {
+        let per = |count: i64| (count > 0).then(|| connected.connection_time as f64 / count as f64);
+        let time_per_input = per(connected.inputs.into());
+        let time_per_sigop = per(connected.sigops);
+
+        if self.blocks.len() == self.window {
+            self.blocks.pop_front();
+        }
+        self.blocks
+            .push_back((connected.connection_time, time_per_input, time_per_sigop));
+
+        BlockSummary {
+            hash: bitcoin::BlockHash::from_slice(&connected.hash).unwrap(),
+            height: connected.height,
+            connection_time: connected.connection_time,
+            time_per_input,
+            time_per_sigop,
+            window: self.blocks.len(),
+            connection_time_percentiles: Percentiles::of(
+                self.blocks.iter().map(|(time, _, _)| *time as f64),
+            )
+            .expect("the block was just added"),
+            time_per_input_percentiles: Percentiles::of(
+                self.blocks
+                    .iter()
+                    .filter_map(|(_, per_input, _)| *per_input),
+            ),
+            time_per_sigop_percentiles: Percentiles::of(
+                self.blocks
+                    .iter()
+                    .filter_map(|(_, _, per_sigop)| *per_sigop),
+            ),
+        }
+    }
+}
+
+/// Summarize a stream of connected blocks, see [`BlockSummaries`]
+pub fn summarize(
+    blocks: impl IntoIterator<Item = BlockConnected>,
+    window: usize,
+) -> impl Iterator<Item = BlockSummary> {
+    let mut summaries = BlockSummaries::new(window);
+    blocks.into_iter().map(move |block| summaries.push(&block))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(height: i32, inputs: i32, sigops: i64, connection_time: i64) -> BlockConnected {
+        BlockConnected {
+            hash: vec![0; 32],
+            height,
+            transactions: 1,
+            inputs,
+            sigops,
+            connection_time,
+            bitcoind_version: None,
+        }
+    }
+
+    #[test]
+    fn connection_time_unit_for_major_version() {
+        assert_eq!(
+            ConnectionTimeUnit::for_major_version(23),
+            ConnectionTimeUnit::Microseconds
+        );
+        assert_eq!(
+            ConnectionTimeUnit::for_major_version(24),
+            ConnectionTimeUnit::Microseconds
+        );
+        assert_eq!(
+            ConnectionTimeUnit::for_major_version(25),
+            ConnectionTimeUnit::Nanoseconds
+        );
+        assert_eq!(ConnectionTimeUnit::Nanoseconds.to_micros(1_500_000), 1500);
+        assert_eq!(ConnectionTimeUnit::Microseconds.to_micros(1500), 1500);
+    }
+
+    #[test]
+    fn block_summaries() {
+        let blocks = (1..=10).map(|i| block(i, i * 10, 0, i as i64 * 1000));
+        let summaries: Vec<BlockSummary> = summarize(blocks, 4).collect();
+
+        let first = &summaries[0];
+        assert_eq!(first.window, 1);
+        assert_eq!(first.time_per_input, Some(100.0));
+        assert_eq!(first.time_per_sigop, None);
+        assert_eq!(first.connection_time_percentiles.p50, 1000.0);
+        assert_eq!(first.time_per_sigop_percentiles, None);
+
+        // The window holds the blocks at height 7 to 10
+        let last = &summaries[9];
+        assert_eq!(last.height, 10);
+        assert_eq!(last.window, 4);
+        assert_eq!(
+            last.connection_time_percentiles,
+            Percentiles {
+                p50: 8000.0,
+                p90: 10000.0,
+                p99: 10000.0,
+            }
+        );
+        assert_eq!(last.time_per_input_percentiles.unwrap().p50, 100.0);
+    }
+}
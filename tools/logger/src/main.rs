@@ -44,11 +44,25 @@ struct Args {
     /// If passed, show validation events
     #[arg(long)]
     validation: bool,
+
+    /// If passed, show UTXO cache events
+    #[arg(long)]
+    utxocache: bool,
+
+    /// If passed, show coin selection events
+    #[arg(long)]
+    coin_selection: bool,
 }
 
 impl Args {
     fn should_show_all(&self) -> bool {
-        !(self.messages || self.connections || self.addrman || self.mempool || self.validation)
+        !(self.messages
+            || self.connections
+            || self.addrman
+            || self.mempool
+            || self.validation
+            || self.utxocache
+            || self.coin_selection)
     }
 }
 
@@ -61,6 +75,8 @@ fn main() {
     let addrman = args.addrman;
     let mempool = args.mempool;
     let validation = args.validation;
+    let utxocache = args.utxocache;
+    let coin_selection = args.coin_selection;
     simple_logger::init_with_level(args.log_level).unwrap();
 
     // TODO: handle unwraps
@@ -78,7 +94,10 @@ fn main() {
                                 if msg.meta.inbound { "from" } else { "to" },
                                 msg.meta.peer_id,
                                 msg.meta.conn_type,
-                                msg.msg.unwrap()
+                                msg.msg.map_or_else(
+                                    || format!("{} ({} bytes)", msg.meta.command, msg.meta.size),
+                                    |m| m.to_string()
+                                )
                             );
                         }
                     }
@@ -102,6 +121,16 @@ fn main() {
                             log::info!("+Validation {}", v.event.unwrap());
                         }
                     }
+                    Event::Utxocache(u) => {
+                        if should_show_all || utxocache {
+                            log::info!("~Utxocache {}", u.event.unwrap());
+                        }
+                    }
+                    Event::CoinSelection(c) => {
+                        if should_show_all || coin_selection {
+                            log::info!("%CoinSelection {}", c.event.unwrap());
+                        }
+                    }
                 }
             }
         }
@@ -1,7 +1,9 @@
 #![cfg_attr(feature = "strict", deny(warnings))]
+#![recursion_limit = "256"]
 
 use shared::addrman::addrman_event;
 use shared::clap::Parser;
+use shared::coin_selection::coin_selection_event;
 use shared::event_msg;
 use shared::event_msg::event_msg::Event;
 use shared::log;
@@ -12,7 +14,8 @@ use shared::net_msg::{message::Msg, reject::RejectReason};
 use shared::prost::Message;
 use shared::simple_logger;
 use shared::util;
-use shared::validation::validation_event;
+use shared::utxocache::utxocache_event;
+use shared::validation::{validation_event, BlockSummaries, Percentiles};
 use shared::{clap, nats};
 use std::collections::HashMap;
 use std::convert::TryFrom;
@@ -58,6 +61,7 @@ fn main() {
 
     let nc = nats::connect(args.nats_address).expect("should be able to connect to NATS server");
     let sub = nc.subscribe("*").expect("could not subscribe to topic '*'");
+    let mut block_summaries = BlockSummaries::default();
     for msg in sub.messages() {
         let unwrapped = event_msg::EventMsg::decode(msg.data.as_slice()).unwrap();
 
@@ -75,7 +79,11 @@ fn main() {
                 Event::Mempool(m) => {
                     handle_mempool_event(&m.event.unwrap());
                 }
-                Event::Validation(v) => handle_validation_event(&v.event.unwrap()),
+                Event::Validation(v) => {
+                    handle_validation_event(&v.event.unwrap(), &mut block_summaries)
+                }
+                Event::Utxocache(u) => handle_utxocache_event(&u.event.unwrap()),
+                Event::CoinSelection(c) => handle_coin_selection_event(&c.event.unwrap()),
             }
         }
     }
@@ -103,21 +111,92 @@ fn main() {
         }
     }
 
-    fn handle_validation_event(e: &validation_event::Event) {
+    fn handle_validation_event(e: &validation_event::Event, summaries: &mut BlockSummaries) {
         match e {
             validation_event::Event::BlockConnected(v) => {
-                // Due to an undetected API break, 23.x and 24.x used microseconds, while
-                // 25.x, 26.x, and 27.x use nanoseconds.
-                // See https://github.com/bitcoin/bitcoin/pull/29877
-                // Assume the tracepoint passed nanoseconds here, but we don't need the
-                // nanosecond precision and already have metrics recorded as microseconds.
-                let duration_microseconds = (v.connection_time / 1000) as u64;
+                // bitcointap converts the connection time to microseconds
                 metrics::VALIDATION_BLOCK_CONNECTED_LATEST_HEIGHT.set(v.height as i64);
-                metrics::VALIDATION_BLOCK_CONNECTED_LATEST_TIME.set(duration_microseconds as i64);
-                metrics::VALIDATION_BLOCK_CONNECTED_DURATION.inc_by(duration_microseconds);
+                metrics::VALIDATION_BLOCK_CONNECTED_LATEST_TIME.set(v.connection_time);
+                metrics::VALIDATION_BLOCK_CONNECTED_DURATION.inc_by(v.connection_time as u64);
                 metrics::VALIDATION_BLOCK_CONNECTED_LATEST_SIGOPS.set(v.sigops);
                 metrics::VALIDATION_BLOCK_CONNECTED_LATEST_INPUTS.set(v.inputs.into());
                 metrics::VALIDATION_BLOCK_CONNECTED_LATEST_TRANSACTIONS.set(v.transactions);
+
+                let summary = summaries.push(v);
+                if let Some(per_input) = summary.time_per_input {
+                    metrics::VALIDATION_BLOCK_CONNECTED_LATEST_TIME_PER_INPUT.set(per_input);
+                }
+                if let Some(per_sigop) = summary.time_per_sigop {
+                    metrics::VALIDATION_BLOCK_CONNECTED_LATEST_TIME_PER_SIGOP.set(per_sigop);
+                }
+                set_percentiles(
+                    &metrics::VALIDATION_BLOCK_CONNECTED_TIME_PERCENTILES,
+                    &summary.connection_time_percentiles,
+                );
+                if let Some(percentiles) = &summary.time_per_input_percentiles {
+                    set_percentiles(
+                        &metrics::VALIDATION_BLOCK_CONNECTED_TIME_PER_INPUT_PERCENTILES,
+                        percentiles,
+                    );
+                }
+            }
+        }
+    }
+
+    fn set_percentiles(gauge: &prometheus::GaugeVec, percentiles: &Percentiles) {
+        gauge.with_label_values(&["50"]).set(percentiles.p50);
+        gauge.with_label_values(&["90"]).set(percentiles.p90);
+        gauge.with_label_values(&["99"]).set(percentiles.p99);
+    }
+
+    fn handle_utxocache_event(e: &utxocache_event::Event) {
+        match e {
+            utxocache_event::Event::Flush(f) => {
+                metrics::UTXOCACHE_FLUSHES
+                    .with_label_values(&[&format!("{:?}", f.mode())])
+                    .inc();
+                metrics::UTXOCACHE_FLUSH_LATEST_DURATION.set(f.duration);
+                metrics::UTXOCACHE_FLUSH_DURATION.inc_by(f.duration as u64);
+                metrics::UTXOCACHE_FLUSH_LATEST_COINS.set(f.coins_count as i64);
+                metrics::UTXOCACHE_FLUSH_LATEST_MEM_USAGE.set(f.coins_mem_usage as i64);
+            }
+            utxocache_event::Event::Add(_) => {
+                metrics::UTXOCACHE_COINS.with_label_values(&["add"]).inc();
+            }
+            utxocache_event::Event::Spent(_) => {
+                metrics::UTXOCACHE_COINS.with_label_values(&["spent"]).inc();
+            }
+            utxocache_event::Event::Uncache(_) => {
+                metrics::UTXOCACHE_COINS
+                    .with_label_values(&["uncache"])
+                    .inc();
+            }
+        }
+    }
+
+    fn handle_coin_selection_event(e: &coin_selection_event::Event) {
+        match e {
+            coin_selection_event::Event::SelectedCoins(s) => {
+                metrics::COIN_SELECTION_SELECTED_COINS
+                    .with_label_values(&[&s.algorithm])
+                    .inc();
+                metrics::COIN_SELECTION_WASTE_HISTOGRAM
+                    .with_label_values(&[&s.algorithm])
+                    .observe(s.waste as f64);
+            }
+            coin_selection_event::Event::NormalCreateTx(c) => {
+                metrics::COIN_SELECTION_CREATE_TX
+                    .with_label_values(&["normal", &c.success.to_string()])
+                    .inc();
+            }
+            coin_selection_event::Event::AttemptingApsCreateTx(_) => (),
+            coin_selection_event::Event::ApsCreateTx(c) => {
+                metrics::COIN_SELECTION_CREATE_TX
+                    .with_label_values(&["aps", &c.success.to_string()])
+                    .inc();
+                if c.use_aps {
+                    metrics::COIN_SELECTION_APS_USED.inc();
+                }
             }
         }
     }
@@ -236,7 +315,11 @@ fn main() {
             .with_label_values(&[&direction, &subnet])
             .inc();
 
-        match msg.msg.as_ref().unwrap() {
+        // Messages recorded in metadata-only mode have no payload
+        let Some(inner) = msg.msg.as_ref() else {
+            return;
+        };
+        match inner {
             Msg::Addr(addr) => {
                 metrics::P2P_ADDR_ADDRESS_HISTOGRAM
                     .with_label_values(&[&direction])
@@ -305,6 +388,11 @@ fn main() {
                     .with_label_values(&[&direction, &ip])
                     .inc();
             }
+            Msg::DecodeFailure(failure) => {
+                metrics::P2P_DECODE_FAILURES
+                    .with_label_values(&[&failure.command, &direction])
+                    .inc();
+            }
             Msg::Inv(inv) => {
                 let mut count_by_invtype: HashMap<String, u64> = HashMap::new();
                 for item in inv.items.iter() {
@@ -1,8 +1,8 @@
 use lazy_static::lazy_static;
-use prometheus::{self, HistogramVec, IntCounter, IntCounterVec, IntGauge};
+use prometheus::{self, Gauge, GaugeVec, HistogramVec, IntCounter, IntCounterVec, IntGauge};
 use prometheus::{
-    register_histogram_vec, register_int_counter, register_int_counter_vec, register_int_gauge,
-    HistogramOpts, Opts,
+    register_gauge, register_gauge_vec, register_histogram_vec, register_int_counter,
+    register_int_counter_vec, register_int_gauge, HistogramOpts, Opts,
 };
 
 // Prometheus Metrics
@@ -15,6 +15,8 @@ const SUBSYSTEM_CONN: &str = "conn";
 const SUBSYSTEM_ADDRMAN: &str = "addrman";
 const SUBSYSTEM_MEMPOOL: &str = "mempool";
 const SUBSYSTEM_VALIDATION: &str = "validation";
+const SUBSYSTEM_UTXOCACHE: &str = "utxocache";
+const SUBSYSTEM_COIN_SELECTION: &str = "coin_selection";
 
 pub const LABEL_P2P_MSG_TYPE: &str = "message";
 pub const LABEL_P2P_CONNECTION_TYPE: &str = "connection_type";
@@ -36,6 +38,12 @@ pub const LABEL_CONN_MISBEHAVING_MESSAGE: &str = "misbehavingmessage";
 pub const LABEL_CONN_MISBEHAVING_ID: &str = "id";
 pub const LABEL_ADDRMAN_NEW_INSERT_SUCCESS: &str = "inserted";
 pub const LABEL_MEMPOOL_REASON: &str = "reason";
+pub const LABEL_VALIDATION_PERCENTILE: &str = "percentile";
+pub const LABEL_UTXOCACHE_FLUSH_MODE: &str = "mode";
+pub const LABEL_UTXOCACHE_COIN_EVENT: &str = "event";
+pub const LABEL_COIN_SELECTION_ALGORITHM: &str = "algorithm";
+pub const LABEL_COIN_SELECTION_ATTEMPT: &str = "attempt";
+pub const LABEL_COIN_SELECTION_SUCCESS: &str = "success";
 
 pub const BUCKETS_ADDR_ADDRESS_COUNT: [f64; 30] = [
     0f64, 1f64, 2f64, 3f64, 4f64, 5f64, 6f64, 7f64, 8f64, 9f64, 10f64, 15f64, 20f64, 25f64, 30f64,
@@ -68,6 +76,13 @@ pub const BUCKETS_ADDR_SERVICE_BITS: [f64; 32] = [
     24_f64, 25_f64, 26_f64, 27_f64, 28_f64, 29_f64, 30_f64, 31_f64,
 ];
 
+// Buckets for the waste of a coin selection solution in satoshis. The waste is
+// negative when spending the inputs now is cheaper than at the long-term feerate.
+pub const BUCKETS_COIN_SELECTION_WASTE: [f64; 17] = [
+    -10000f64, -5000f64, -2000f64, -1000f64, -500f64, -200f64, -100f64, 0f64, 100f64, 200f64,
+    500f64, 1000f64, 2000f64, 5000f64, 10000f64, 50000f64, 100000f64,
+];
+
 // Buckets for addr(v2) message timestamp offset in seconds.
 pub const BUCKETS_ADDR_ADDRESS_TIMESTAMP_OFFSET: [f64; 26] = [
     0f64,
@@ -238,6 +253,15 @@ lazy_static! {
             &[LABEL_P2P_DIRECTION, LABEL_CONN_ADDR]
         ).unwrap();
 
+    /// Number of P2P messages that could not be decoded (by message type).
+    pub static ref P2P_DECODE_FAILURES: IntCounterVec =
+        register_int_counter_vec!(
+            Opts::new("decode_failures", "Number of P2P messages that could not be decoded (by message type).")
+                .namespace(NAMESPACE)
+                .subsystem(SUBSYSTEM_P2P),
+            &[LABEL_P2P_MSG_TYPE, LABEL_P2P_DIRECTION]
+        ).unwrap();
+
     // -------------------- Connections
 
     /// Number of inbound connections.
@@ -633,4 +657,128 @@ lazy_static! {
             .namespace(NAMESPACE)
             .subsystem(SUBSYSTEM_VALIDATION)
     ).unwrap();
+
+    /// Last connected block connection time per input in µs.
+    pub static ref VALIDATION_BLOCK_CONNECTED_LATEST_TIME_PER_INPUT: Gauge =
+    register_gauge!(
+        Opts::new("block_connected_latest_time_per_input", "Last connected block connection time per input in µs.")
+            .namespace(NAMESPACE)
+            .subsystem(SUBSYSTEM_VALIDATION)
+    ).unwrap();
+
+    /// Last connected block connection time per sigop in µs.
+    pub static ref VALIDATION_BLOCK_CONNECTED_LATEST_TIME_PER_SIGOP: Gauge =
+    register_gauge!(
+        Opts::new("block_connected_latest_time_per_sigop", "Last connected block connection time per sigop in µs.")
+            .namespace(NAMESPACE)
+            .subsystem(SUBSYSTEM_VALIDATION)
+    ).unwrap();
+
+    /// Percentiles of the block connection time in µs over the last blocks (by percentile).
+    pub static ref VALIDATION_BLOCK_CONNECTED_TIME_PERCENTILES: GaugeVec =
+    register_gauge_vec!(
+        Opts::new("block_connected_time_percentiles", "Percentiles of the block connection time in µs over the last blocks (by percentile).")
+            .namespace(NAMESPACE)
+            .subsystem(SUBSYSTEM_VALIDATION),
+        &[LABEL_VALIDATION_PERCENTILE]
+    ).unwrap();
+
+    /// Percentiles of the block connection time per input in µs over the last blocks (by percentile).
+    pub static ref VALIDATION_BLOCK_CONNECTED_TIME_PER_INPUT_PERCENTILES: GaugeVec =
+    register_gauge_vec!(
+        Opts::new("block_connected_time_per_input_percentiles", "Percentiles of the block connection time per input in µs over the last blocks (by percentile).")
+            .namespace(NAMESPACE)
+            .subsystem(SUBSYSTEM_VALIDATION),
+        &[LABEL_VALIDATION_PERCENTILE]
+    ).unwrap();
+
+    // -------------------- UTXO cache
+
+    /// Number of UTXO cache flushes (by flush mode).
+    pub static ref UTXOCACHE_FLUSHES: IntCounterVec =
+    register_int_counter_vec!(
+        Opts::new("flushes", "Number of UTXO cache flushes (by flush mode).")
+            .namespace(NAMESPACE)
+            .subsystem(SUBSYSTEM_UTXOCACHE),
+        &[LABEL_UTXOCACHE_FLUSH_MODE]
+    ).unwrap();
+
+    /// Last UTXO cache flush duration in µs.
+    pub static ref UTXOCACHE_FLUSH_LATEST_DURATION: IntGauge =
+    register_int_gauge!(
+        Opts::new("flush_latest_duration", "Last UTXO cache flush duration in µs.")
+            .namespace(NAMESPACE)
+            .subsystem(SUBSYSTEM_UTXOCACHE)
+    ).unwrap();
+
+    /// Total UTXO cache flush duration in µs.
+    pub static ref UTXOCACHE_FLUSH_DURATION: IntCounter =
+    register_int_counter!(
+        Opts::new("flush_duration", "Total UTXO cache flush duration in µs.")
+            .namespace(NAMESPACE)
+            .subsystem(SUBSYSTEM_UTXOCACHE)
+    ).unwrap();
+
+    /// Number of coins in the UTXO cache before the last flush.
+    pub static ref UTXOCACHE_FLUSH_LATEST_COINS: IntGauge =
+    register_int_gauge!(
+        Opts::new("flush_latest_coins", "Number of coins in the UTXO cache before the last flush.")
+            .namespace(NAMESPACE)
+            .subsystem(SUBSYSTEM_UTXOCACHE)
+    ).unwrap();
+
+    /// Memory usage of the UTXO cache in bytes before the last flush.
+    pub static ref UTXOCACHE_FLUSH_LATEST_MEM_USAGE: IntGauge =
+    register_int_gauge!(
+        Opts::new("flush_latest_mem_usage", "Memory usage of the UTXO cache in bytes before the last flush.")
+            .namespace(NAMESPACE)
+            .subsystem(SUBSYSTEM_UTXOCACHE)
+    ).unwrap();
+
+    /// Number of coins added, spent and uncached (by event).
+    pub static ref UTXOCACHE_COINS: IntCounterVec =
+    register_int_counter_vec!(
+        Opts::new("coins", "Number of coins added, spent and uncached (by event).")
+            .namespace(NAMESPACE)
+            .subsystem(SUBSYSTEM_UTXOCACHE),
+        &[LABEL_UTXOCACHE_COIN_EVENT]
+    ).unwrap();
+
+    // -------------------- Coin selection
+
+    /// Number of coin selections (by winning algorithm).
+    pub static ref COIN_SELECTION_SELECTED_COINS: IntCounterVec =
+    register_int_counter_vec!(
+        Opts::new("selected_coins", "Number of coin selections (by winning algorithm).")
+            .namespace(NAMESPACE)
+            .subsystem(SUBSYSTEM_COIN_SELECTION),
+        &[LABEL_COIN_SELECTION_ALGORITHM]
+    ).unwrap();
+
+    /// Histogram of the waste of the selected coin selection solutions in satoshis (by algorithm).
+    pub static ref COIN_SELECTION_WASTE_HISTOGRAM: HistogramVec =
+        register_histogram_vec!(
+            HistogramOpts::new("waste", "Histogram of the waste of the selected coin selection solutions in satoshis (by algorithm).")
+                .namespace(NAMESPACE)
+                .subsystem(SUBSYSTEM_COIN_SELECTION)
+                .buckets(BUCKETS_COIN_SELECTION_WASTE.to_vec()),
+            &[LABEL_COIN_SELECTION_ALGORITHM]
+        ).unwrap();
+
+    /// Number of transactions the wallet tried to create (by normal or aps attempt and success).
+    pub static ref COIN_SELECTION_CREATE_TX: IntCounterVec =
+    register_int_counter_vec!(
+        Opts::new("create_tx", "Number of transactions the wallet tried to create (by normal or aps attempt and success).")
+            .namespace(NAMESPACE)
+            .subsystem(SUBSYSTEM_COIN_SELECTION),
+        &[LABEL_COIN_SELECTION_ATTEMPT, LABEL_COIN_SELECTION_SUCCESS]
+    ).unwrap();
+
+    /// Number of times the wallet used the transaction avoiding partial spends.
+    pub static ref COIN_SELECTION_APS_USED: IntCounter =
+    register_int_counter!(
+        Opts::new("aps_used", "Number of times the wallet used the transaction avoiding partial spends.")
+            .namespace(NAMESPACE)
+            .subsystem(SUBSYSTEM_COIN_SELECTION)
+    ).unwrap();
 }